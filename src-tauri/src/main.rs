@@ -1,10 +1,13 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use chrono::Local;
+use chrono::{Local, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs::OpenOptions;
 use std::path::{Path, PathBuf};
-use tauri::Manager;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Condvar, Mutex};
+use tauri::{Emitter, Manager};
 
 #[derive(Debug, Serialize)]
 struct DownloadPaths {
@@ -13,17 +16,597 @@ struct DownloadPaths {
     log_path: String,
 }
 
+struct RunningProcess {
+    child: Arc<Mutex<Child>>,
+    stopped: bool,
+    input: Option<String>,
+    started_at: String,
+    progress: Arc<Mutex<ProgressState>>,
+}
+
+// Latest percent plus a moving average of how fast it has been rising, which
+// the ETA is derived from.
+#[derive(Default)]
+struct ProgressState {
+    percent: Option<f64>,
+    speed: Option<f64>,
+    samples: u32,
+    last_sample: Option<(std::time::Instant, f64)>,
+}
+
+#[derive(Debug, Serialize)]
+struct ActiveOperation {
+    id: String,
+    kind: String,
+    input: Option<String>,
+    started_at: String,
+    progress: Option<f64>,
+    eta_secs: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+struct ExportProgress {
+    session_id: String,
+    percent: f64,
+    eta_secs: Option<f64>,
+}
+
+// Child processes that can be stopped from another command, keyed by the
+// id the frontend used to start them.
+#[derive(Default)]
+struct ProcessRegistry(Mutex<HashMap<String, RunningProcess>>);
+
+// Id of the most recently started download or export, cleared when it exits
+// so cancel_last never reaches an unrelated task.
+#[derive(Default)]
+struct LastOperation(Mutex<Option<String>>);
+
+#[derive(Default)]
+struct SlotState {
+    running: usize,
+    waiting: usize,
+    // Bumped by cancel_all so everything still waiting for a slot gives up.
+    generation: u64,
+}
+
+// Exports and downloads share this many slots; the rest wait their turn.
+#[derive(Default)]
+struct JobSlots {
+    state: Mutex<SlotState>,
+    freed: Condvar,
+}
+
+struct TrackedOutput {
+    status: ExitStatus,
+    stdout: String,
+    stderr: String,
+    stopped: bool,
+    timed_out: bool,
+}
+
+#[derive(Debug, Clone)]
+struct DownloadRequest {
+    url: String,
+    download_dir: PathBuf,
+    log_path: PathBuf,
+    playlist: bool,
+    max_attempts: u32,
+    format_id: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct PausedDownload {
+    request: DownloadRequest,
+    part_files: Vec<PathBuf>,
+}
+
+// Audio encoders compiled into the bundled ffmpeg, detected on first use.
+#[derive(Default)]
+struct EncoderCache(Mutex<Option<Vec<String>>>);
+
+// What the bundled ffmpeg can accelerate on this machine, detected once.
+#[derive(Default)]
+struct CapabilityCache(Mutex<Option<FfmpegCapabilities>>);
+
+// Probed durations keyed by path, with the size and modified time they were
+// read at so a replaced file is probed again.
+#[derive(Default)]
+struct DurationCache(Mutex<HashMap<PathBuf, CachedDuration>>);
+
+#[derive(Clone, Copy)]
+struct CachedDuration {
+    size: u64,
+    modified: std::time::SystemTime,
+    duration: Option<f64>,
+}
+
+// One lock per session log so the frontend's append_video_trace and backend
+// trace lines can't interleave mid-line or race the truncation rewrite.
+#[derive(Default)]
+struct SessionLogLocks(Mutex<HashMap<String, Arc<Mutex<()>>>>);
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+struct QueueItem {
+    id: String,
+    url: String,
+    playlist: bool,
+    state: String,
+    added_at: String,
+    output_path: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Default)]
+struct QueueState {
+    items: Vec<QueueItem>,
+    worker_running: bool,
+}
+
+#[derive(Default)]
+struct DownloadQueue(Mutex<QueueState>);
+
+// Downloads stopped by pause_download, keyed by download id.
+#[derive(Default)]
+struct DownloadRegistry(Mutex<HashMap<String, PausedDownload>>);
+
+#[derive(Debug, Default, Clone)]
+struct MediaProbe {
+    duration: Option<f64>,
+    has_audio: bool,
+    has_video: bool,
+    audio_codec: Option<String>,
+    video_codec: Option<String>,
+    sample_rate: Option<u32>,
+    channels: u32,
+    channel_layout: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+struct AudioOptions {
+    channels: Option<u32>,
+    custom_filter: Option<String>,
+    preflight: Option<bool>,
+    tempo: Option<f64>,
+    audio_encoder: Option<String>,
+    gain_db: Option<f64>,
+    quality: Option<u8>,
+    limit_clipping: Option<bool>,
+    web_optimized: Option<bool>,
+    sample_rate: Option<u32>,
+    resampler: Option<String>,
+    dither: Option<String>,
+    loudness_preset: Option<String>,
+    bit_depth: Option<String>,
+    denoise: Option<String>,
+    denoise_amount: Option<f64>,
+    highpass_hz: Option<f64>,
+    lowpass_hz: Option<f64>,
+    max_input_mb: Option<u64>,
+    make_readonly: Option<bool>,
+    // Set by export_multi_format when encoding from its decoded copy: the
+    // original file, which the size limit applies to and whose tags and
+    // cover art the output keeps.
+    #[serde(skip)]
+    source: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ExportPreset {
+    name: String,
+    format: String,
+    #[serde(default)]
+    audio_options: AudioOptions,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct VideoOptions {
+    custom_filter: Option<String>,
+    web_optimized: Option<bool>,
+    video_codec: Option<String>,
+    preset: Option<String>,
+    crf: Option<u32>,
+    passthrough_video: Option<bool>,
+    hwaccel: Option<String>,
+    watermark_path: Option<String>,
+    watermark_corner: Option<String>,
+    watermark_margin: Option<u32>,
+    watermark_opacity: Option<f64>,
+    watermark_scale: Option<f64>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct KenBurnsOptions {
+    direction: Option<String>,
+    speed: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+struct PreflightReport {
+    path: String,
+    size: u64,
+    decodable: bool,
+    has_audio: bool,
+    has_video: bool,
+    duration: Option<f64>,
+    empty: bool,
+    error: Option<String>,
+}
+
+#[derive(Debug)]
+struct AudioChain {
+    sample_rate: Option<u32>,
+    channels: u32,
+    filters: Vec<String>,
+}
+
+#[derive(Debug)]
+struct ChannelPlan {
+    channels: u32,
+    filters: Vec<String>,
+    warning: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ChaptersResult {
+    output_path: String,
+    chapters: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct ConcatResult {
+    output_path: String,
+    duration: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Default)]
+struct LevelAnalysis {
+    max_volume: Option<f64>,
+    mean_volume: Option<f64>,
+    channel_peaks: Vec<f64>,
+    clipping_risk: bool,
+}
+
+#[derive(Debug, Serialize, Default)]
+struct ChannelClipping {
+    channel: u32,
+    peak_db: Option<f64>,
+    clip_count: u64,
+}
+
+#[derive(Debug, Serialize, Default)]
+struct ClippingReport {
+    clipped: bool,
+    sample_peak_db: Option<f64>,
+    clip_count: u64,
+    channels: Vec<ChannelClipping>,
+}
+
+#[derive(Debug, Serialize)]
+struct AudioComparison {
+    similarity: f64,
+    identical: bool,
+    reference_db: Option<f64>,
+    difference_db: Option<f64>,
+    snr_db: Option<f64>,
+    sample_rate: u32,
+    resampled: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct PostExportHook {
+    command: Option<String>,
+    hooks_dir: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ExportVerification {
+    source_duration: f64,
+    output_duration: f64,
+    difference: f64,
+    tolerance: f64,
+    warning: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct DownloadListing {
+    name: String,
+    path: String,
+    size: u64,
+    duration: Option<f64>,
+    modified: String,
+}
+
+#[derive(Debug, Serialize, Clone, Copy)]
+struct WaveformBucket {
+    min: f32,
+    max: f32,
+    rms: f32,
+}
+
+#[derive(Debug, Serialize)]
+struct WaveformData {
+    sample_rate: u32,
+    bucket_seconds: f64,
+    channels: Vec<Vec<WaveformBucket>>,
+    output_path: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct SizeEstimate {
+    duration: f64,
+    audio_kbps: f64,
+    video_kbps: f64,
+    estimated_bytes: u64,
+    approximate: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct Chapter {
+    start: f64,
+    title: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MixInput {
+    path: String,
+    gain_db: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+struct LoudnessPreset {
+    name: &'static str,
+    label: &'static str,
+    integrated_lufs: f64,
+    true_peak_db: f64,
+    loudness_range: f64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MultiFormatTarget {
+    format: Option<String>,
+    preset: Option<String>,
+    audio_options: Option<AudioOptions>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+struct MultiFormatResult {
+    format: String,
+    preset: Option<String>,
+    export_session_id: String,
+    output_path: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+struct MultiFormatProgress {
+    session_id: String,
+    index: usize,
+    state: String,
+    result: MultiFormatResult,
+}
+
+#[derive(Debug, Serialize)]
+struct AlbumTrackGain {
+    input_path: String,
+    output_path: String,
+    loudness: Option<f64>,
+    gain_db: f64,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+struct YtDlpUpdateInfo {
+    current_version: String,
+    latest_version: String,
+    update_available: bool,
+    download_url: Option<String>,
+    checked_at: String,
+}
+
+#[derive(Debug, Serialize)]
+struct PathDiagnosis {
+    path: String,
+    writable: bool,
+    problem: Option<String>,
+    message: String,
+}
+
+#[derive(Debug, Serialize)]
+struct DiagnosticCheck {
+    name: String,
+    status: String,
+    detail: String,
+}
+
+#[derive(Debug, Serialize)]
+struct LogFileInfo {
+    name: String,
+    path: String,
+    size: u64,
+    modified: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+struct HistoryEntry {
+    url: String,
+    title: String,
+    timestamp: String,
+    output_path: String,
+    format: String,
+    format_id: Option<String>,
+}
+
 #[derive(Debug, Deserialize, Serialize, Default)]
 struct Settings {
     download_root: Option<String>,
     export_root: Option<String>,
+    video_export_root: Option<String>,
+    filename_template: Option<String>,
+    proxy_url: Option<String>,
+    rate_limit: Option<String>,
+    session_log_max_kb: Option<u64>,
+    quality: Option<u8>,
+    output_root_allowlist: Option<Vec<String>>,
+    export_presets: Option<Vec<ExportPreset>>,
+    folder_scheme: Option<String>,
+    video_bits_per_pixel: Option<f64>,
+    ffmpeg_log_level: Option<String>,
+    ffmpeg_threads: Option<u32>,
+    ffmpeg_timeout_factor: Option<f64>,
+    use_utc: Option<bool>,
+    max_input_mb: Option<u64>,
+    max_parallel_jobs: Option<u32>,
+    download_container: Option<String>,
+    process_priority: Option<String>,
+    organize_by: Option<String>,
+    post_export_command: Option<String>,
 }
 
-fn app_root() -> Result<PathBuf, String> {
+#[derive(Debug, Serialize)]
+struct SettingsValidation {
+    reset: Vec<String>,
+    warnings: Vec<String>,
+}
+
+// What the app actually runs with, as opposed to the stored Settings; keys
+// listed in `defaults` weren't set or held a value that was ignored.
+#[derive(Debug, Serialize)]
+struct EffectiveConfig {
+    download_root: String,
+    export_root: String,
+    video_export_root: String,
+    filename_template: Option<String>,
+    proxy_url: Option<String>,
+    rate_limit: Option<String>,
+    session_log_max_kb: u64,
+    quality: Option<u8>,
+    output_root_allowlist: Vec<String>,
+    export_preset_count: usize,
+    folder_scheme: String,
+    organize_by: String,
+    video_bits_per_pixel: f64,
+    ffmpeg_log_level: Option<String>,
+    ffmpeg_threads: Option<u32>,
+    ffmpeg_timeout_factor: f64,
+    use_utc: bool,
+    max_input_mb: u64,
+    max_parallel_jobs: u32,
+    download_container: Option<String>,
+    process_priority: String,
+    post_export_command: Option<String>,
+    defaults: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+struct FfmpegCapabilities {
+    hwaccels: Vec<String>,
+    video_encoders: Vec<String>,
+    hardware_h264: Vec<String>,
+    max_threads: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct FfmpegSettings {
+    log_level: Option<String>,
+    threads: Option<u32>,
+    max_threads: u32,
+    timeout_factor: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct ManagedPath {
+    managed: bool,
+    root: Option<String>,
+    root_path: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct DataMigration {
+    old_root: String,
+    new_root: String,
+    files_copied: usize,
+    bytes_copied: u64,
+    removed_old: bool,
+}
+
+#[derive(Debug, Serialize, Clone)]
+struct MigrationProgress {
+    files_copied: usize,
+    files_total: usize,
+    bytes_copied: u64,
+    bytes_total: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct RemoteAudioFormat {
+    format_id: String,
+    ext: Option<String>,
+    codec: Option<String>,
+    bitrate_kbps: Option<f64>,
+    sample_rate: Option<u64>,
+    filesize: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+struct RemoteMedia {
+    title: Option<String>,
+    uploader: Option<String>,
+    duration: Option<f64>,
+    audio_formats: Vec<RemoteAudioFormat>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct JournalRecord {
+    id: String,
+    state: String,
+    kind: String,
+    target: String,
+    time: String,
+    // Partial files already in a download's folder when it started; they
+    // belong to other downloads and survive recovery.
+    #[serde(default)]
+    existing: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct RecoveredOperation {
+    kind: String,
+    target: String,
+    started_at: String,
+    removed: Vec<String>,
+    kept: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct NetworkSettings {
+    proxy_url: Option<String>,
+    rate_limit: Option<String>,
+}
+
+fn base_app_root() -> Result<PathBuf, String> {
     let base = std::env::var_os("LOCALAPPDATA")
         .map(PathBuf::from)
         .ok_or("LOCALAPPDATA not set")?;
-    let root = base.join("AudioWorkshop");
+    Ok(base.join("AudioWorkshop"))
+}
+
+// After migrate_data_root the data lives elsewhere; the default location
+// keeps only this pointer to it.
+fn data_root_pointer() -> Result<PathBuf, String> {
+    Ok(base_app_root()?.join("data_root.txt"))
+}
+
+fn app_root() -> Result<PathBuf, String> {
+    let base = base_app_root()?;
+    let moved = std::fs::read_to_string(data_root_pointer()?)
+        .ok()
+        .map(|text| PathBuf::from(text.trim()))
+        .filter(|path| path.is_absolute() && path.is_dir());
+    let root = moved.unwrap_or(base);
     std::fs::create_dir_all(&root).map_err(|e| e.to_string())?;
     Ok(root)
 }
@@ -44,7 +627,118 @@ fn load_settings() -> Result<Settings, String> {
 fn save_settings(settings: &Settings) -> Result<(), String> {
     let path = settings_path()?;
     let contents = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
-    std::fs::write(&path, contents).map_err(|e| e.to_string())
+    atomic_write(&path, contents)
+}
+
+fn history_path() -> Result<PathBuf, String> {
+    Ok(app_root()?.join("history.json"))
+}
+
+fn load_history() -> Result<Vec<HistoryEntry>, String> {
+    let path = history_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    match serde_json::from_str(&contents) {
+        Ok(entries) => Ok(entries),
+        Err(_) => {
+            // A crash mid-write can leave a partial file; keep it for inspection
+            // and start a fresh index instead of failing every history call.
+            let backup = path.with_file_name(format!(
+                "history.corrupt_{}.json",
+                app_now().format("%Y%m%d_%H%M%S")
+            ));
+            std::fs::rename(&path, &backup).map_err(|e| e.to_string())?;
+            Ok(Vec::new())
+        }
+    }
+}
+
+fn save_history(entries: &[HistoryEntry]) -> Result<(), String> {
+    let path = history_path()?;
+    let contents = serde_json::to_string_pretty(entries).map_err(|e| e.to_string())?;
+    atomic_write(&path, contents)
+}
+
+fn queue_path() -> Result<PathBuf, String> {
+    Ok(app_root()?.join("queue.json"))
+}
+
+fn load_queue() -> Result<Vec<QueueItem>, String> {
+    let path = queue_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    match serde_json::from_str(&contents) {
+        Ok(items) => Ok(items),
+        Err(err) => {
+            // Set aside before the next save replaces it, so the URLs can
+            // still be recovered by hand.
+            let backup = path.with_file_name(format!(
+                "queue.corrupt_{}.json",
+                app_now().format("%Y%m%d_%H%M%S")
+            ));
+            std::fs::rename(&path, &backup).map_err(|e| e.to_string())?;
+            Err(format!(
+                "queue.json couldn't be read ({err}); it was moved to {}",
+                backup.to_string_lossy()
+            ))
+        }
+    }
+}
+
+fn save_queue(items: &[QueueItem]) -> Result<(), String> {
+    let path = queue_path()?;
+    let contents = serde_json::to_string_pretty(items).map_err(|e| e.to_string())?;
+    atomic_write(&path, contents)
+}
+
+fn journal_path() -> Result<PathBuf, String> {
+    Ok(app_root()?.join("operations.journal"))
+}
+
+static JOURNAL_LOCK: Mutex<()> = Mutex::new(());
+
+fn append_journal(record: &JournalRecord) -> Result<(), String> {
+    let line = serde_json::to_string(record).map_err(|e| e.to_string())?;
+    let _guard = JOURNAL_LOCK.lock().map_err(|e| e.to_string())?;
+    append_log_line(&journal_path()?, &line)
+}
+
+// Records an operation before it touches the disk; a record that is never
+// closed means the app stopped mid-operation. Journal failures are not
+// allowed to block the operation itself.
+fn journal_begin(kind: &str, target: &Path) -> Option<String> {
+    journal_begin_with(kind, target, Vec::new())
+}
+
+fn journal_begin_with(kind: &str, target: &Path, existing: Vec<String>) -> Option<String> {
+    let id = new_stamp();
+    let record = JournalRecord {
+        id: id.clone(),
+        state: "started".into(),
+        kind: kind.into(),
+        target: target.to_string_lossy().to_string(),
+        time: app_now().to_rfc3339(),
+        existing,
+    };
+    append_journal(&record).ok().map(|_| id)
+}
+
+fn journal_end(id: Option<String>) {
+    let Some(id) = id else {
+        return;
+    };
+    let _ = append_journal(&JournalRecord {
+        id,
+        state: "completed".into(),
+        kind: String::new(),
+        target: String::new(),
+        time: app_now().to_rfc3339(),
+        existing: Vec::new(),
+    });
 }
 
 fn default_download_root() -> Result<PathBuf, String> {
@@ -90,66 +784,453 @@ fn resolve_export_root() -> Result<PathBuf, String> {
     default_export_root()
 }
 
-fn validate_writable_dir(path: &Path) -> Result<(), String> {
-    std::fs::create_dir_all(path).map_err(|e| e.to_string())?;
-    let probe = path.join(".aw_write_test");
-    std::fs::write(&probe, b"test").map_err(|e| e.to_string())?;
-    std::fs::remove_file(&probe).map_err(|e| e.to_string())?;
+// Video exports go to their own folder when one is set, otherwise wherever
+// audio exports go.
+fn resolve_video_export_root() -> Result<PathBuf, String> {
+    let settings = load_settings()?;
+    if let Some(root) = settings.video_export_root {
+        let path = PathBuf::from(root);
+        if path.is_absolute() {
+            return Ok(path);
+        }
+        return Ok(app_root()?.join(path));
+    }
+    resolve_export_root()
+}
+
+// Writes to a temp file next to the target and renames it into place, so a
+// crash mid-write never leaves a truncated file behind.
+fn atomic_write(path: &Path, contents: impl AsRef<[u8]>) -> Result<(), String> {
+    use std::io::Write;
+    let dir = path.parent().ok_or("Invalid path")?;
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or("Invalid path")?;
+    let temp_path = dir.join(format!(
+        ".{name}.{}.{}.tmp",
+        std::process::id(),
+        app_now().timestamp_nanos_opt().unwrap_or_default()
+    ));
+    let result = std::fs::File::create(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(contents.as_ref())?;
+            file.sync_all()
+        })
+        .and_then(|_| std::fs::rename(&temp_path, path));
+    if let Err(err) = result {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(err.to_string());
+    }
     Ok(())
 }
 
-fn is_within(parent: &Path, child: &Path) -> Result<bool, String> {
-    let parent = parent
-        .canonicalize()
-        .map_err(|e| format!("Path error: {e}"))?;
-    let child_parent = child
-        .parent()
-        .ok_or("Invalid path")?
-        .canonicalize()
-        .map_err(|e| format!("Path error: {e}"))?;
-    Ok(child_parent.starts_with(parent))
+const WRITE_PROBE_PREFIX: &str = ".aw_write_test";
+// Older than any probe still in use, so a concurrent check keeps its file.
+const STALE_PROBE_SECS: u64 = 60;
+
+// Each probe gets its own name, so two checks of one folder can't remove
+// each other's file mid-test.
+fn write_probe(dir: &Path) -> std::io::Result<()> {
+    use std::io::Write;
+    remove_stale_probes(dir);
+    let probe = dir.join(format!(
+        "{WRITE_PROBE_PREFIX}_{}_{}",
+        std::process::id(),
+        new_stamp()
+    ));
+    let written = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+        .and_then(|mut file| file.write_all(b"test"));
+    let removed = std::fs::remove_file(&probe);
+    written.and(removed)
 }
 
-fn append_video_trace_line(session_id: &str, line: &str) -> Result<(), String> {
-    if !session_id
-        .chars()
-        .all(|c| c.is_ascii_digit() || c == '_')
-    {
-        return Err("Invalid session id".into());
+// A process killed between writing and removing a probe leaves it behind in
+// the user's folder; later checks of the same folder sweep those up.
+fn remove_stale_probes(dir: &Path) -> usize {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    let stale = std::time::Duration::from_secs(STALE_PROBE_SECS);
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let is_probe = entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| name.starts_with(WRITE_PROBE_PREFIX));
+        let old = entry
+            .metadata()
+            .ok()
+            .filter(|meta| meta.is_file())
+            .and_then(|meta| meta.modified().ok())
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age >= stale);
+        if is_probe && old && std::fs::remove_file(entry.path()).is_ok() {
+            removed += 1;
+        }
     }
-    let logs = logs_root()?;
-    std::fs::create_dir_all(&logs).map_err(|e| e.to_string())?;
-    let log_path = logs.join(format!("video_export_{}.log", session_id));
-    let mut file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(log_path)
-        .map_err(|e| e.to_string())?;
-    use std::io::Write;
-    writeln!(file, "{line}").map_err(|e| e.to_string())
+    removed
 }
 
-fn binaries_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
-    let mut diag: Vec<String> = Vec::new();
+fn cleanup_write_probes() -> usize {
+    [resolve_download_root(), resolve_export_root(), resolve_video_export_root()]
+        .into_iter()
+        .flatten()
+        .map(|root| remove_stale_probes(&root))
+        .sum()
+}
 
-    match app.path().resource_dir() {
-        Ok(resource_dir) => {
-            diag.push(format!(
-                "resource_dir={}",
-                resource_dir.to_string_lossy()
-            ));
+// What a preview checks instead of validate_writable_dir: nothing is created
+// or written, so only the part of the path that already exists is looked at.
+fn check_dir_usable(path: &Path) -> Result<(), String> {
+    if is_in_hooks_dir(path) {
+        return Err("The hooks folder can't be used for downloads or exports".into());
+    }
+    let existing = path.ancestors().find(|p| p.exists()).ok_or("Invalid folder")?;
+    if !existing.is_dir() {
+        return Err(format!("{} is not a folder", existing.to_string_lossy()));
+    }
+    Ok(())
+}
 
-            // Tauri sidecars can land either in `resources/binaries` or directly
-            // in the `resources` (or even the exe) directory depending on build
-            // and installer behavior. Check all common locations.
-            let parent_dir = resource_dir
-                .parent()
-                .map(|p| p.to_path_buf())
-                .unwrap_or_else(|| resource_dir.clone());
-            let candidates = [
-                resource_dir.join("binaries"),
-                resource_dir.clone(),
-                parent_dir,
+fn validate_writable_dir(path: &Path) -> Result<(), String> {
+    if is_in_hooks_dir(path) {
+        return Err("The hooks folder can't be used for downloads or exports".into());
+    }
+    let checked = (|| -> std::io::Result<()> {
+        std::fs::create_dir_all(path)?;
+        write_probe(path)
+    })();
+    checked.map_err(|e| {
+        let diagnosis = diagnose_dir(path);
+        if diagnosis.problem.is_some() {
+            diagnosis.message
+        } else {
+            e.to_string()
+        }
+    })
+}
+
+fn write_failure(path: &Path, err: &std::io::Error, creating: bool) -> (&'static str, String) {
+    let shown = path.to_string_lossy();
+    match err.kind() {
+        std::io::ErrorKind::ReadOnlyFilesystem => (
+            "read_only_filesystem",
+            format!("{shown} is on a read-only drive. Choose a folder on another drive."),
+        ),
+        std::io::ErrorKind::PermissionDenied if creating => (
+            "no_create_permission",
+            format!("You don't have permission to create folders in {shown}. Choose a folder you own, such as one under your user profile."),
+        ),
+        std::io::ErrorKind::PermissionDenied => (
+            "no_write_permission",
+            format!("You don't have permission to write to {shown}. Check the folder's security settings or choose another folder."),
+        ),
+        _ => ("io_error", format!("Can't write to {shown}: {err}")),
+    }
+}
+
+// Checks each failure mode separately without leaving anything behind, so a
+// folder that doesn't exist yet is never created here.
+fn diagnose_dir(path: &Path) -> PathDiagnosis {
+    let found = |problem: Option<&str>, message: String| PathDiagnosis {
+        path: path.to_string_lossy().to_string(),
+        writable: problem.is_none(),
+        problem: problem.map(|p| p.to_string()),
+        message,
+    };
+    let shown = path.to_string_lossy();
+
+    if !path.exists() {
+        let Some(ancestor) = path.ancestors().skip(1).find(|a| a.is_dir()) else {
+            return found(
+                Some("missing"),
+                format!("{shown} doesn't exist and its drive isn't available."),
+            );
+        };
+        let probe = ancestor.join(".aw_create_test");
+        return match std::fs::create_dir(&probe) {
+            Ok(()) => {
+                let _ = std::fs::remove_dir(&probe);
+                found(None, format!("{shown} doesn't exist yet and will be created."))
+            }
+            Err(err) => {
+                let (problem, message) = write_failure(ancestor, &err, true);
+                found(Some(problem), message)
+            }
+        };
+    }
+    if !path.is_dir() {
+        return found(
+            Some("not_a_directory"),
+            format!("{shown} is a file, not a folder."),
+        );
+    }
+    if std::fs::metadata(path).is_ok_and(|m| m.permissions().readonly()) {
+        return found(
+            Some("no_write_permission"),
+            format!("{shown} is marked read-only. Clear the read-only attribute or choose another folder."),
+        );
+    }
+    match write_probe(path) {
+        Ok(()) => found(None, format!("{shown} is writable.")),
+        Err(err) => {
+            let (problem, message) = write_failure(path, &err, false);
+            found(Some(problem), message)
+        }
+    }
+}
+
+#[tauri::command]
+fn diagnose_path(path: String) -> Result<PathDiagnosis, String> {
+    let path = PathBuf::from(path.trim());
+    if !path.is_absolute() {
+        return Err("Path must be absolute".into());
+    }
+    Ok(diagnose_dir(&path))
+}
+
+// Lexical stand-in for canonicalize: makes the path absolute and resolves
+// `.`/`..` without touching the filesystem. Symlinks are not followed.
+fn normalize_path(path: &Path) -> Result<PathBuf, String> {
+    use std::path::Component;
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map_err(|e| format!("Path error: {e}"))?
+            .join(path)
+    };
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !matches!(
+                    normalized.components().next_back(),
+                    Some(Component::RootDir | Component::Prefix(_)) | None
+                ) {
+                    normalized.pop();
+                }
+            }
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    Ok(normalized)
+}
+
+// canonicalize often fails on UNC/network shares even though the path is
+// there; fall back to the normalized form for paths that do exist.
+fn canonical_path(path: &Path) -> Result<PathBuf, String> {
+    match path.canonicalize() {
+        Ok(canonical) => Ok(canonical),
+        Err(_) if path.exists() => normalize_path(path),
+        Err(e) => Err(format!("Path error: {e}")),
+    }
+}
+
+fn is_within(parent: &Path, child: &Path) -> Result<bool, String> {
+    let child_parent = child.parent().ok_or("Invalid path")?;
+    match (parent.canonicalize(), child_parent.canonicalize()) {
+        (Ok(parent), Ok(child_parent)) => Ok(child_parent.starts_with(parent)),
+        (parent_result, child_result) => {
+            for (path, result) in [(parent, parent_result), (child_parent, child_result)] {
+                if let Err(e) = result {
+                    if !path.exists() {
+                        return Err(format!("Path error: {e}"));
+                    }
+                }
+            }
+            // Compare both sides in normalized form; a canonical path may
+            // carry a verbatim prefix the normalized one lacks.
+            Ok(normalize_path(child_parent)?.starts_with(normalize_path(parent)?))
+        }
+    }
+}
+
+fn is_managed_input(path: &Path) -> Result<bool, String> {
+    if is_within(&app_root()?, path)? {
+        return Ok(true);
+    }
+    Ok(is_within(&resolve_download_root()?, path).unwrap_or(false))
+}
+
+fn is_managed_or_export(path: &Path) -> Result<bool, String> {
+    Ok(is_managed_input(path)?
+        || is_within(&resolve_export_root()?, path).unwrap_or(false)
+        || is_within(&resolve_video_export_root()?, path).unwrap_or(false))
+}
+
+// The download and export roots are checked before the app root, which
+// contains both by default, so the most specific match is reported.
+#[tauri::command]
+fn path_is_managed(path: String) -> Result<ManagedPath, String> {
+    let path = PathBuf::from(path);
+    let roots = [
+        ("download", resolve_download_root()?),
+        ("export", resolve_export_root()?),
+        ("video_export", resolve_video_export_root()?),
+        ("app", app_root()?),
+    ];
+    for (name, root) in roots {
+        if is_within(&root, &path).unwrap_or(false) {
+            return Ok(ManagedPath {
+                managed: true,
+                root: Some(name.into()),
+                root_path: Some(root.to_string_lossy().to_string()),
+            });
+        }
+    }
+    Ok(ManagedPath {
+        managed: false,
+        root: None,
+        root_path: None,
+    })
+}
+
+thread_local! {
+    // Set while preview_command builds a plan, so planning skips the
+    // preflight scan and leaves the session log alone.
+    static DRY_RUN: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+fn is_dry_run() -> bool {
+    DRY_RUN.with(|flag| flag.get())
+}
+
+// Clears the flag even if the planning panics, since the async pool reuses
+// the thread for real exports.
+struct DryRunGuard;
+
+impl Drop for DryRunGuard {
+    fn drop(&mut self) {
+        DRY_RUN.with(|flag| flag.set(false));
+    }
+}
+
+fn dry_run<T>(work: impl FnOnce() -> T) -> T {
+    DRY_RUN.with(|flag| flag.set(true));
+    let _guard = DryRunGuard;
+    work()
+}
+
+fn append_video_trace_line(
+    app: &tauri::AppHandle,
+    session_id: &str,
+    line: &str,
+) -> Result<(), String> {
+    validate_session_id(session_id)?;
+    if is_dry_run() {
+        return Ok(());
+    }
+    let logs = logs_root()?;
+    std::fs::create_dir_all(&logs).map_err(|e| e.to_string())?;
+    let log_path = logs.join(format!("video_export_{}.log", session_id));
+    let max_bytes = session_log_max_kb() * 1024;
+    with_session_log_lock(&app.state::<SessionLogLocks>(), session_id, || {
+        append_capped_log_line(&log_path, line, max_bytes)
+    })
+}
+
+// Runs `work` holding the session's lock. The entry is dropped again once
+// nobody else is waiting on it, so the map only holds sessions being written.
+fn with_session_log_lock<T>(
+    locks: &SessionLogLocks,
+    session_id: &str,
+    work: impl FnOnce() -> Result<T, String>,
+) -> Result<T, String> {
+    let lock = locks
+        .0
+        .lock()
+        .map_err(|e| e.to_string())?
+        .entry(session_id.to_string())
+        .or_default()
+        .clone();
+    let result = {
+        let _guard = lock.lock().map_err(|e| e.to_string())?;
+        work()
+    };
+    let mut map = locks.0.lock().map_err(|e| e.to_string())?;
+    // Clones are only handed out under the map lock, so two owners (the map
+    // and this call) means no one else holds or waits on it.
+    if Arc::strong_count(&lock) == 2 {
+        map.remove(session_id);
+    }
+    result
+}
+
+const DEFAULT_SESSION_LOG_MAX_KB: u64 = 5 * 1024;
+const MIN_SESSION_LOG_MAX_KB: u64 = 64;
+const LOG_TRUNCATED_MARKER: &str = "[truncated]";
+
+fn session_log_max_kb() -> u64 {
+    load_settings()
+        .ok()
+        .and_then(|s| s.session_log_max_kb)
+        .unwrap_or(DEFAULT_SESSION_LOG_MAX_KB)
+        .max(MIN_SESSION_LOG_MAX_KB)
+}
+
+fn append_capped_log_line(path: &Path, line: &str, max_bytes: u64) -> Result<(), String> {
+    let current = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    if current + (line.len() as u64) < max_bytes {
+        return append_log_line(path, line);
+    }
+
+    // Keep the newest half of the budget so the rewrite doesn't happen on
+    // every following line.
+    let keep = (max_bytes / 2) as usize;
+    let mut combined = std::fs::read(path).unwrap_or_default();
+    combined.extend_from_slice(line.as_bytes());
+    combined.push(b'\n');
+    let mut start = combined.len().saturating_sub(keep);
+    if let Some(pos) = combined[start..].iter().position(|b| *b == b'\n') {
+        if start + pos + 1 < combined.len() {
+            start += pos + 1;
+        }
+    }
+    let mut contents = format!("{LOG_TRUNCATED_MARKER}\n").into_bytes();
+    contents.extend_from_slice(&combined[start..]);
+    atomic_write(path, contents)
+}
+
+fn append_log_line(path: &Path, line: &str) -> Result<(), String> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| e.to_string())?;
+    use std::io::Write;
+    // A single write per line keeps O_APPEND writes from other handles from
+    // landing between the text and its newline.
+    file.write_all(format!("{line}\n").as_bytes())
+        .map_err(|e| e.to_string())
+}
+
+fn binaries_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let mut diag: Vec<String> = Vec::new();
+
+    match app.path().resource_dir() {
+        Ok(resource_dir) => {
+            diag.push(format!(
+                "resource_dir={}",
+                resource_dir.to_string_lossy()
+            ));
+
+            // Tauri sidecars can land either in `resources/binaries` or directly
+            // in the `resources` (or even the exe) directory depending on build
+            // and installer behavior. Check all common locations.
+            let parent_dir = resource_dir
+                .parent()
+                .map(|p| p.to_path_buf())
+                .unwrap_or_else(|| resource_dir.clone());
+            let candidates = [
+                resource_dir.join("binaries"),
+                resource_dir.clone(),
+                parent_dir,
                 resource_dir.join("..").join("binaries"),
             ];
             for candidate in candidates {
@@ -281,19 +1362,77 @@ fn find_binaries_dir(start: &Path) -> Option<PathBuf> {
     None
 }
 
+// Any one tool is enough to identify the binaries folder; which tools are
+// actually usable is reported per tool by required_binaries_status.
 fn has_required_binaries(dir: &Path) -> bool {
-    let ffmpeg = dir.join("ffmpeg.exe");
-    let ffmpeg_triple = dir.join("ffmpeg-x86_64-pc-windows-msvc.exe");
-    let ffprobe = dir.join("ffprobe.exe");
-    let ffprobe_triple = dir.join("ffprobe-x86_64-pc-windows-msvc.exe");
-    let yt_dlp = dir.join("yt-dlp.exe");
-    let yt_dlp_triple = dir.join("yt-dlp-x86_64-pc-windows-msvc.exe");
-    ffmpeg.exists()
-        || ffmpeg_triple.exists()
-        || ffprobe.exists()
-        || ffprobe_triple.exists()
-        || yt_dlp.exists()
-        || yt_dlp_triple.exists()
+    REQUIRED_TOOLS
+        .iter()
+        .any(|(tool, _)| tool_in_dir(dir, tool).is_some())
+}
+
+// Each tool with the feature that can't work without it.
+const REQUIRED_TOOLS: [(&str, &str); 3] = [
+    ("ffmpeg", "exports"),
+    ("ffprobe", "probing"),
+    ("yt-dlp", "downloads"),
+];
+
+fn tool_in_dir(dir: &Path, tool: &str) -> Option<PathBuf> {
+    first_existing(&[
+        dir.join(format!("{tool}-x86_64-pc-windows-msvc.exe")),
+        dir.join(format!("{tool}.exe")),
+    ])
+}
+
+fn missing_tool_error(tool: &str) -> String {
+    let feature = REQUIRED_TOOLS
+        .iter()
+        .find(|(name, _)| *name == tool)
+        .map(|(_, feature)| *feature)
+        .unwrap_or("this feature");
+    format!("{tool} is missing, {feature} unavailable")
+}
+
+#[derive(Debug, Serialize)]
+struct BinariesStatus {
+    dir: Option<String>,
+    present: Vec<String>,
+    missing: Vec<String>,
+    unavailable: Vec<String>,
+}
+
+fn required_binaries_status(app: &tauri::AppHandle) -> BinariesStatus {
+    let dir = binaries_dir(app).ok();
+    let mut status = BinariesStatus {
+        dir: dir.as_ref().map(|d| d.to_string_lossy().to_string()),
+        present: Vec::new(),
+        missing: Vec::new(),
+        unavailable: Vec::new(),
+    };
+    for (tool, feature) in REQUIRED_TOOLS {
+        if dir.as_deref().and_then(|d| tool_in_dir(d, tool)).is_some() {
+            status.present.push(tool.to_string());
+        } else {
+            status.missing.push(tool.to_string());
+            status.unavailable.push(feature.to_string());
+        }
+    }
+    status
+}
+
+// Checks every tool a feature needs before it starts, so the error names the
+// missing tool instead of failing halfway through.
+fn require_tools(app: &tauri::AppHandle, tools: &[&str]) -> Result<(), String> {
+    let status = required_binaries_status(app);
+    match tools.iter().find(|tool| status.missing.iter().any(|m| m == *tool)) {
+        Some(tool) => Err(missing_tool_error(tool)),
+        None => Ok(()),
+    }
+}
+
+#[tauri::command(async)]
+fn get_binaries_status(app: tauri::AppHandle) -> BinariesStatus {
+    required_binaries_status(&app)
 }
 
 fn first_existing(paths: &[PathBuf]) -> Option<PathBuf> {
@@ -357,476 +1496,8341 @@ fn repair_binaries_layout(exe_dir: &Path) -> Result<Option<PathBuf>, String> {
     Ok(None)
 }
 
+fn tool_path(app: &tauri::AppHandle, tool: &str) -> Result<PathBuf, String> {
+    let bin_dir = binaries_dir(app).map_err(|_| missing_tool_error(tool))?;
+    tool_in_dir(&bin_dir, tool)
+        .ok_or_else(|| missing_tool_error(tool))?
+        .canonicalize()
+        .map_err(|e| e.to_string())
+}
+
 fn ffmpeg_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
-    let bin_dir = binaries_dir(app)?;
-    let candidates = [
-        bin_dir.join("ffmpeg-x86_64-pc-windows-msvc.exe"),
-        bin_dir.join("ffmpeg.exe"),
-    ];
-    for candidate in candidates {
-        if candidate.exists() {
-            return candidate
-                .canonicalize()
-                .map_err(|e| e.to_string());
-        }
-    }
-    Err("ffmpeg executable not found".into())
+    tool_path(app, "ffmpeg")
 }
 
-#[tauri::command]
-fn get_download_root() -> Result<String, String> {
-    let root = resolve_download_root()?;
-    validate_writable_dir(&root)?;
-    Ok(root.to_string_lossy().to_string())
+fn ffprobe_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    tool_path(app, "ffprobe")
 }
 
-#[tauri::command]
-fn set_download_root(path: String) -> Result<String, String> {
-    let mut settings = load_settings()?;
-    if path.trim().is_empty() {
-        settings.download_root = None;
-        save_settings(&settings)?;
-        return get_download_root();
+fn ffprobe_output(app: &tauri::AppHandle, path: &Path) -> Result<std::process::Output, String> {
+    let ffprobe = ffprobe_path(app)?;
+    std::process::Command::new(ffprobe)
+        .args(["-v", "error", "-show_streams", "-show_format", "-of", "json"])
+        .arg(path)
+        .output()
+        .map_err(|e| e.to_string())
+}
+
+fn probe_media(app: &tauri::AppHandle, path: &Path) -> Result<MediaProbe, String> {
+    let output = ffprobe_output(app, path)?;
+    if !output.status.success() {
+        return Err(format!(
+            "ffprobe failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
     }
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).map_err(|e| e.to_string())?;
+    Ok(parse_probe(&json))
+}
 
-    let candidate = {
-        let raw = PathBuf::from(path.trim());
-        if raw.is_absolute() {
-            raw
-        } else {
-            app_root()?.join(raw)
-        }
-    };
+#[tauri::command(async)]
+fn read_metadata(app: tauri::AppHandle, path: String) -> Result<HashMap<String, String>, String> {
+    let path = PathBuf::from(path);
+    if !path.is_file() || !is_managed_or_export(&path)? {
+        return Err("Invalid file path".into());
+    }
+    let output = ffprobe_output(&app, &path)?;
+    if !output.status.success() {
+        return Err(format!(
+            "ffprobe failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).map_err(|e| e.to_string())?;
 
-    validate_writable_dir(&candidate)?;
-    settings.download_root = Some(candidate.to_string_lossy().to_string());
-    save_settings(&settings)?;
-    get_download_root()
+    // Ogg/Opus keep tags on the audio stream rather than the container, so
+    // stream tags fill in anything the format block lacks.
+    let mut tags = HashMap::new();
+    let format_tags = json["format"]["tags"].as_object().into_iter();
+    let stream_tags = json["streams"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|stream| stream["tags"].as_object());
+    for object in format_tags.chain(stream_tags) {
+        for (key, value) in object {
+            if let Some(value) = value.as_str() {
+                tags.entry(key.to_ascii_lowercase())
+                    .or_insert_with(|| value.to_string());
+            }
+        }
+    }
+    Ok(tags)
 }
 
-#[tauri::command]
-fn ensure_downloads_dir(date_folder: String) -> Result<String, String> {
-    if !date_folder
-        .chars()
-        .all(|c| c.is_ascii_digit() || c == '-')
-    {
-        return Err("Invalid date folder".into());
+fn parse_probe(json: &serde_json::Value) -> MediaProbe {
+    let mut probe = MediaProbe {
+        duration: json["format"]["duration"]
+            .as_str()
+            .and_then(|d| d.parse::<f64>().ok()),
+        ..MediaProbe::default()
+    };
+    let streams = json["streams"].as_array().cloned().unwrap_or_default();
+    for stream in &streams {
+        match stream["codec_type"].as_str() {
+            Some("audio") if !probe.has_audio => {
+                probe.has_audio = true;
+                probe.audio_codec = stream["codec_name"].as_str().map(|s| s.to_string());
+                probe.sample_rate = stream["sample_rate"]
+                    .as_str()
+                    .and_then(|r| r.parse::<u32>().ok());
+                probe.channels = stream["channels"].as_u64().unwrap_or(0) as u32;
+                probe.channel_layout = stream["channel_layout"].as_str().map(|s| s.to_string());
+            }
+            Some("video") => {
+                probe.has_video = true;
+                // Embedded cover art is reported as a video stream too; only a
+                // real one is recorded as the video codec.
+                if probe.video_codec.is_none()
+                    && stream["disposition"]["attached_pic"].as_i64() != Some(1)
+                {
+                    probe.video_codec = stream["codec_name"].as_str().map(|s| s.to_string());
+                }
+            }
+            _ => {}
+        }
     }
+    probe
+}
 
-    let root = resolve_download_root()?;
-    validate_writable_dir(&root)?;
+const AAC_SAMPLE_RATES: [u32; 12] = [
+    8000, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000, 64000, 88200, 96000,
+];
 
-    let download_dir = root.join(date_folder);
-    std::fs::create_dir_all(&download_dir).map_err(|e| e.to_string())?;
-    Ok(download_dir.to_string_lossy().to_string())
-}
+const TEMPO_RANGE: (f64, f64) = (0.25, 4.0);
 
-#[tauri::command(rename_all = "camelCase")]
-fn prepare_temp_audio(date_folder: String, log_stamp: String) -> Result<String, String> {
-    if !date_folder
-        .chars()
-        .all(|c| c.is_ascii_digit() || c == '-')
-    {
-        return Err("Invalid date folder".into());
+// A single atempo instance only accepts 0.5-2.0, so larger changes are split
+// into several stages whose product is the requested factor.
+fn atempo_chain(factor: f64) -> Result<Vec<String>, String> {
+    if !factor.is_finite() || factor < TEMPO_RANGE.0 || factor > TEMPO_RANGE.1 {
+        return Err(format!(
+            "Tempo must be between {} and {}",
+            TEMPO_RANGE.0, TEMPO_RANGE.1
+        ));
     }
-    if !log_stamp
-        .chars()
-        .all(|c| c.is_ascii_digit() || c == '_')
-    {
-        return Err("Invalid log stamp".into());
+    let mut stages = Vec::new();
+    let mut remaining = factor;
+    while remaining > 2.0 {
+        stages.push("atempo=2.0".to_string());
+        remaining /= 2.0;
+    }
+    while remaining < 0.5 {
+        stages.push("atempo=0.5".to_string());
+        remaining /= 0.5;
+    }
+    if (remaining - 1.0).abs() > f64::EPSILON {
+        stages.push(format!("atempo={remaining:.6}"));
     }
+    Ok(stages)
+}
 
-    let root = tmp_root()?;
-    validate_writable_dir(&root)?;
+const COMMON_SAMPLE_RATES: [u32; 10] = [
+    8000, 16000, 22050, 24000, 32000, 44100, 48000, 88200, 96000, 192000,
+];
+const DITHER_METHODS: [&str; 10] = [
+    "rectangular",
+    "triangular",
+    "triangular_hp",
+    "lipshitz",
+    "shibata",
+    "low_shibata",
+    "high_shibata",
+    "f_weighted",
+    "e_weighted",
+    "modified_e_weighted",
+];
 
-    let tmp_dir = root.join(date_folder);
-    std::fs::create_dir_all(&tmp_dir).map_err(|e| e.to_string())?;
+fn validate_resample(
+    sample_rate: u32,
+    resampler: Option<&str>,
+    dither: Option<&str>,
+) -> Result<(), String> {
+    if !COMMON_SAMPLE_RATES.contains(&sample_rate) {
+        return Err(format!(
+            "Unsupported sample rate {sample_rate}. Use one of: {}",
+            COMMON_SAMPLE_RATES.map(|r| r.to_string()).join(", ")
+        ));
+    }
+    if resampler.is_some_and(|r| r != "soxr" && r != "swr") {
+        return Err("Resampler must be 'soxr' or 'swr'".into());
+    }
+    if let Some(dither) = dither {
+        if dither != "none" && !DITHER_METHODS.contains(&dither) {
+            return Err(format!(
+                "Unknown dither method '{dither}'. Use none or one of: {}",
+                DITHER_METHODS.join(", ")
+            ));
+        }
+    }
+    Ok(())
+}
 
-    let file_name = format!("audioworkshop__{}.wav", log_stamp);
-    let path = tmp_dir.join(file_name);
-    Ok(path.to_string_lossy().to_string())
+// soxr is an optional ffmpeg build dependency; the bundled build may lack it.
+fn ffmpeg_has_soxr(app: &tauri::AppHandle) -> bool {
+    let Ok(ffmpeg) = ffmpeg_path(app) else {
+        return false;
+    };
+    Command::new(ffmpeg)
+        .args(["-hide_banner", "-version"])
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).contains("--enable-libsoxr"))
+        .unwrap_or(false)
 }
 
-#[tauri::command(rename_all = "camelCase")]
-fn write_binary_file(path: String, bytes: Vec<u8>) -> Result<(), String> {
-    let root = app_root()?;
-    let path = PathBuf::from(path);
-    if !is_within(&root, &path)? {
-        return Err("Invalid output path".into());
+fn resample_filter(
+    app: &tauri::AppHandle,
+    options: &AudioOptions,
+    session_id: &str,
+) -> Result<Option<String>, String> {
+    let Some(rate) = options.sample_rate else {
+        return Ok(None);
+    };
+    let requested = options.resampler.as_deref().unwrap_or("soxr");
+    validate_resample(rate, Some(requested), options.dither.as_deref())?;
+    let resampler = if requested == "soxr" && !ffmpeg_has_soxr(app) {
+        let _ = append_video_trace_line(
+            app,
+            session_id,
+            "{\"stage\":\"backend_resample\",\"note\":\"soxr not available in this ffmpeg build; using swr\"}",
+        );
+        "swr"
+    } else {
+        requested
+    };
+    let mut filter = format!("aresample={rate}:resampler={resampler}");
+    if resampler == "soxr" {
+        filter.push_str(":precision=28");
     }
-    if let Some(parent) = path.parent() {
-        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    if let Some(dither) = options.dither.as_deref().filter(|d| *d != "none") {
+        filter.push_str(&format!(":dither_method={dither}"));
     }
-    std::fs::write(path, bytes).map_err(|e| e.to_string())
+    let _ = append_video_trace_line(
+        app,
+        session_id,
+        &format!(
+            "{{\"stage\":\"backend_resample\",\"sample_rate\":{rate},\"filter\":{}}}",
+            serde_json::to_string(&filter).unwrap_or_default()
+        ),
+    );
+    Ok(Some(filter))
 }
 
-#[tauri::command(rename_all = "camelCase")]
-fn export_black_video(
-    app: tauri::AppHandle,
-    input_audio_path: String,
-    session_id: String,
-    output_root: Option<String>,
-) -> Result<String, String> {
-    let now = Local::now();
-    let date_folder = now.format("%Y-%m-%d").to_string();
-    let stamp = session_id.clone();
+const ARNNDN_MODEL: &str = "rnnoise.rnnn";
 
-    let root = app_root()?;
-    let input_path = PathBuf::from(input_audio_path);
-    if !is_within(&root, &input_path)? {
-        let _ = append_video_trace_line(&session_id, "{\"stage\":\"backend_export_video_start\",\"error\":\"invalid_input_path\"}");
-        return Err("Invalid input path".into());
+fn validate_denoise(method: &str, amount: Option<f64>) -> Result<(), String> {
+    if method != "afftdn" && method != "arnndn" {
+        return Err("Denoise method must be 'afftdn' or 'arnndn'".into());
     }
+    if amount.is_some_and(|a| !a.is_finite() || !(1.0..=100.0).contains(&a)) {
+        return Err("Denoise amount must be between 1 and 100".into());
+    }
+    Ok(())
+}
 
-    let custom_root = output_root.is_some();
-    let output_root = if let Some(root) = output_root {
-        let raw = PathBuf::from(root);
-        if raw.is_absolute() {
-            raw
-        } else {
-            app_root()?.join(raw)
+// The amount is a 1-100 strength: for afftdn it scales the noise reduction
+// (up to its 97 dB limit), for arnndn it sets how much of the denoised
+// signal is mixed back in.
+fn denoise_filter(
+    app: &tauri::AppHandle,
+    method: &str,
+    amount: Option<f64>,
+) -> Result<String, String> {
+    validate_denoise(method, amount)?;
+    let amount = amount.unwrap_or(12.0);
+    if method == "afftdn" {
+        return Ok(format!("afftdn=nr={:.2}", amount * 0.97));
+    }
+    let model = binaries_dir(app)?.join(ARNNDN_MODEL);
+    if !model.is_file() {
+        return Err(format!(
+            "Denoise model {ARNNDN_MODEL} is missing from {}",
+            binaries_dir(app)?.to_string_lossy()
+        ));
+    }
+    let model = path_arg(&model)?.replace('\\', "/").replace(':', "\\:");
+    Ok(format!("arnndn=m='{model}':mix={:.2}", amount / 100.0))
+}
+
+fn pass_filters(
+    highpass_hz: Option<f64>,
+    lowpass_hz: Option<f64>,
+    sample_rate: Option<u32>,
+) -> Result<Vec<String>, String> {
+    for (name, cutoff) in [("High-pass", highpass_hz), ("Low-pass", lowpass_hz)] {
+        if cutoff.is_some_and(|f| !f.is_finite() || f <= 0.0) {
+            return Err(format!("{name} cutoff must be a positive frequency"));
         }
-    } else {
-        resolve_export_root()?
+        if let (Some(cutoff), Some(rate)) = (cutoff, sample_rate) {
+            let nyquist = rate as f64 / 2.0;
+            if cutoff >= nyquist {
+                return Err(format!(
+                    "{name} cutoff {cutoff} Hz must be below {nyquist} Hz for {rate} Hz audio"
+                ));
+            }
+        }
+    }
+    if let (Some(high), Some(low)) = (highpass_hz, lowpass_hz) {
+        if high >= low {
+            return Err("High-pass cutoff must be below the low-pass cutoff".into());
+        }
+    }
+    let mut filters = Vec::new();
+    if let Some(high) = highpass_hz {
+        filters.push(format!("highpass=f={high}"));
+    }
+    if let Some(low) = lowpass_hz {
+        filters.push(format!("lowpass=f={low}"));
+    }
+    Ok(filters)
+}
+
+fn aac_sample_rate(source: Option<u32>) -> u32 {
+    match source {
+        Some(rate) if AAC_SAMPLE_RATES.contains(&rate) => rate,
+        _ => 44100,
+    }
+}
+
+fn run_preflight(app: &tauri::AppHandle, path: &Path, session_id: Option<&str>) -> PreflightReport {
+    let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    let mut report = PreflightReport {
+        path: path.to_string_lossy().to_string(),
+        size,
+        decodable: false,
+        has_audio: false,
+        has_video: false,
+        duration: None,
+        empty: size == 0,
+        error: None,
     };
-    if let Err(err) = validate_writable_dir(&output_root) {
-        let _ = append_video_trace_line(&session_id, &format!("{{\"stage\":\"backend_export_video_start\",\"error\":\"{}\"}}", err));
-        return Err("Export failed. See logs.".into());
+    if report.empty {
+        report.error = Some("Input file is empty".into());
+        return report;
     }
 
-    let export_dir = if custom_root {
-        output_root.clone()
+    let output = match ffprobe_output(app, path) {
+        Ok(output) => output,
+        Err(err) => {
+            report.error = Some(err);
+            return report;
+        }
+    };
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    if let Some(session_id) = session_id {
+        let _ = append_video_trace_line(
+            app,
+            session_id,
+            &format!(
+                "{{\"stage\":\"backend_preflight\",\"ffprobe_code\":{},\"ffprobe_stderr\":{}}}",
+                output.status.code().unwrap_or(-1),
+                serde_json::to_string(&stderr).unwrap_or_default()
+            ),
+        );
+    }
+    if !output.status.success() {
+        report.error = Some(format!("Input could not be decoded: {}", last_error_line(&stderr)));
+        return report;
+    }
+
+    let probe = serde_json::from_slice::<serde_json::Value>(&output.stdout)
+        .map(|json| parse_probe(&json))
+        .unwrap_or_default();
+    report.has_audio = probe.has_audio;
+    report.has_video = probe.has_video;
+    report.duration = probe.duration;
+    report.empty = probe.duration.map(|d| d <= 0.0).unwrap_or(true)
+        && !probe.has_audio
+        && !probe.has_video;
+    report.error = if report.empty {
+        Some("Input contains no media streams".into())
+    } else if !probe.has_audio {
+        Some("Input has no audio stream".into())
     } else {
-        output_root.join(&date_folder)
+        None
     };
-    if let Err(err) = std::fs::create_dir_all(&export_dir).map_err(|e| e.to_string()) {
-        let _ = append_video_trace_line(&session_id, &format!("{{\"stage\":\"backend_export_video_start\",\"error\":\"{}\"}}", err));
-        return Err("Export failed. See logs.".into());
+    report.decodable = report.error.is_none();
+    report
+}
+
+#[tauri::command(async, rename_all = "camelCase")]
+fn preflight_input(
+    app: tauri::AppHandle,
+    input_path: String,
+    session_id: Option<String>,
+) -> Result<PreflightReport, String> {
+    let input_path = PathBuf::from(input_path);
+    if !input_path.is_file() || !is_managed_input(&input_path)? {
+        return Err("Invalid input path".into());
     }
+    Ok(run_preflight(&app, &input_path, session_id.as_deref()))
+}
 
-    let file_name = format!("audioworkshop__{}__1080x1920_30fps__black.mp4", stamp);
-    let output_path = export_dir.join(file_name);
+fn starts_on_keyframe(app: &tauri::AppHandle, path: &Path, start: f64) -> Result<bool, String> {
+    let ffprobe = ffprobe_path(app)?;
+    let output = std::process::Command::new(ffprobe)
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            "v:0",
+            "-skip_frame",
+            "nokey",
+            "-show_entries",
+            "frame=pts_time",
+            "-of",
+            "csv=p=0",
+            "-read_intervals",
+            &format!("{start}%+2"),
+        ])
+        .arg(path)
+        .output()
+        .map_err(|e| e.to_string())?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    Ok(text
+        .lines()
+        .filter_map(|line| line.trim().parse::<f64>().ok())
+        .any(|pts| (pts - start).abs() < 0.001))
+}
 
-    let _ = append_video_trace_line(
-        &session_id,
-        &format!(
-            "{{\"stage\":\"backend_export_video_start\",\"input\":\"{}\"}}",
-            input_path.to_string_lossy()
-        ),
-    );
+fn plan_channels(requested: u32, probe: &MediaProbe) -> Result<ChannelPlan, String> {
+    if !(1..=2).contains(&requested) {
+        return Err(format!(
+            "Unsupported channel count {requested}. Use 1 (mono) or 2 (stereo)."
+        ));
+    }
+    if !probe.has_audio || probe.channels == 0 {
+        return Err("Input has no audio stream".into());
+    }
 
-    let ffmpeg = match ffmpeg_path(&app) {
-        Ok(path) => path,
-        Err(err) => {
-            let _ = append_video_trace_line(&session_id, &format!("{{\"stage\":\"backend_export_video_start\",\"error\":\"{}\"}}", err));
-            return Err("Export failed. See logs.".into());
+    let mut filters: Vec<String> = Vec::new();
+    let mut warning = None;
+    match (probe.channels, requested) {
+        (input, output) if input == output => {}
+        (1, 2) => {
+            filters.push("pan=stereo|c0=c0|c1=c0".into());
+            warning = Some("upmix duplicates the mono channel into both sides".to_string());
+        }
+        (2, 1) => filters.push("pan=mono|c0=0.5*c0+0.5*c1".into()),
+        (6, output) => {
+            // ITU-R BS.775 style downmix; LFE is dropped. `<` renormalizes the
+            // gains so the summed surround channels can't clip.
+            let layout = probe.channel_layout.as_deref().unwrap_or("");
+            let (left_surround, right_surround) = if layout.contains("side") {
+                ("SL", "SR")
+            } else {
+                ("BL", "BR")
+            };
+            filters.push(format!(
+                "pan=stereo|FL<FL+0.707*FC+0.707*{left_surround}|FR<FR+0.707*FC+0.707*{right_surround}"
+            ));
+            if output == 1 {
+                filters.push("pan=mono|c0=0.5*c0+0.5*c1".into());
+            }
         }
+        // Other multichannel layouts fall back to ffmpeg's built-in -ac matrix.
+        _ => {}
+    }
+    let layout = if requested == 1 { "mono" } else { "stereo" };
+    filters.push(format!("aformat=channel_layouts={layout}"));
+
+    Ok(ChannelPlan {
+        channels: requested,
+        filters,
+        warning,
+    })
+}
+
+const MIN_FREE_SPACE_BYTES: u64 = 1024 * 1024 * 1024;
+
+fn tool_version(path: &Path, flag: &str) -> Result<String, String> {
+    let output = std::process::Command::new(path)
+        .arg(flag)
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(format!(
+            "exited with code {}",
+            output.status.code().unwrap_or(-1)
+        ));
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    Ok(text.lines().next().unwrap_or("").trim().to_string())
+}
+
+fn diagnostic_check(name: &str, result: Result<String, String>) -> DiagnosticCheck {
+    let (status, detail) = match result {
+        Ok(detail) => ("ok", detail),
+        Err(detail) => ("error", detail),
     };
-    let input_path_lossy = input_path.to_string_lossy();
-    let output_path_lossy = output_path.to_string_lossy();
-    let args = [
-        "-y",
-        "-f",
-        "lavfi",
-        "-i",
-        "color=black:s=1080x1920:r=30",
-        "-i",
-        input_path_lossy.as_ref(),
-        "-shortest",
-        "-c:v",
-        "libx264",
-        "-pix_fmt",
-        "yuv420p",
-        "-r",
-        "30",
-        "-c:a",
-        "aac",
-        "-ar",
-        "44100",
-        "-ac",
-        "2",
-        "-b:a",
-        "192k",
-        "-movflags",
-        "+faststart",
-        output_path_lossy.as_ref(),
-    ];
+    DiagnosticCheck {
+        name: name.to_string(),
+        status: status.to_string(),
+        detail,
+    }
+}
 
-    let _ = append_video_trace_line(
-        &session_id,
-        &format!(
-            "{{\"stage\":\"backend_ffmpeg_start\",\"args\":\"{}\"}}",
-            args.join(" ")
-        ),
+fn free_space_check(name: &str, path: &Path) -> DiagnosticCheck {
+    let available = match fs2::available_space(path) {
+        Ok(bytes) => bytes,
+        Err(err) => return diagnostic_check(name, Err(err.to_string())),
+    };
+    let detail = format!(
+        "{:.1} GB free at {}",
+        available as f64 / 1_073_741_824.0,
+        path.to_string_lossy()
     );
+    DiagnosticCheck {
+        name: name.to_string(),
+        status: if available < MIN_FREE_SPACE_BYTES {
+            "warning".to_string()
+        } else {
+            "ok".to_string()
+        },
+        detail,
+    }
+}
 
-    let output = std::process::Command::new(ffmpeg).args(args).output();
-    let output = match output {
-        Ok(output) => output,
-        Err(err) => {
-            let _ = append_video_trace_line(&session_id, &format!("{{\"stage\":\"backend_ffmpeg_exit\",\"error\":\"{}\"}}", err));
-            return Err("Export failed. See logs.".into());
+#[tauri::command(async)]
+fn run_diagnostics(app: tauri::AppHandle) -> Vec<DiagnosticCheck> {
+    let mut checks = vec![diagnostic_check(
+        "binaries",
+        binaries_dir(&app).map(|dir| dir.to_string_lossy().to_string()),
+    )];
+
+    let tools = [
+        ("ffmpeg", "-version"),
+        ("ffprobe", "-version"),
+        ("yt-dlp", "--version"),
+    ];
+    for (tool, flag) in tools {
+        let result = tool_path(&app, tool).and_then(|path| tool_version(&path, flag));
+        checks.push(diagnostic_check(tool, result));
+    }
+
+    let roots = [
+        ("download_root", resolve_download_root()),
+        ("export_root", resolve_export_root()),
+        ("video_export_root", resolve_video_export_root()),
+        ("tmp_root", tmp_root()),
+    ];
+    for (name, root) in roots {
+        let root = match root {
+            Ok(root) => root,
+            Err(err) => {
+                checks.push(diagnostic_check(name, Err(err)));
+                continue;
+            }
+        };
+        let writable = validate_writable_dir(&root).map(|_| root.to_string_lossy().to_string());
+        let is_writable = writable.is_ok();
+        checks.push(diagnostic_check(name, writable));
+        if is_writable {
+            checks.push(free_space_check(&format!("{name}_free_space"), &root));
         }
+    }
+
+    checks
+}
+
+const YT_DLP_LATEST_RELEASE_URL: &str =
+    "https://api.github.com/repos/yt-dlp/yt-dlp/releases/latest";
+const YT_DLP_UPDATE_CACHE_HOURS: i64 = 6;
+
+#[cfg(target_os = "windows")]
+const YT_DLP_ASSET_NAME: &str = "yt-dlp.exe";
+#[cfg(target_os = "macos")]
+const YT_DLP_ASSET_NAME: &str = "yt-dlp_macos";
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const YT_DLP_ASSET_NAME: &str = "yt-dlp";
+
+fn yt_dlp_update_cache_path() -> Result<PathBuf, String> {
+    Ok(app_root()?.join("yt_dlp_update.json"))
+}
+
+fn version_parts(version: &str) -> Vec<u64> {
+    version
+        .trim()
+        .split(['.', '-'])
+        .filter_map(|part| part.parse::<u64>().ok())
+        .collect()
+}
+
+fn http_agent() -> Result<ureq::Agent, String> {
+    // The OS TLS stack picks up system and corporate root certificates.
+    let tls = native_tls::TlsConnector::new().map_err(|e| e.to_string())?;
+    Ok(ureq::AgentBuilder::new()
+        .tls_connector(std::sync::Arc::new(tls))
+        .timeout(std::time::Duration::from_secs(15))
+        .build())
+}
+
+fn fetch_latest_yt_dlp_release() -> Result<serde_json::Value, String> {
+    http_agent()?
+        .get(YT_DLP_LATEST_RELEASE_URL)
+        .set("User-Agent", "AudioWorkshop")
+        .set("Accept", "application/vnd.github+json")
+        .call()
+        .map_err(|e| format!("Update check failed: {e}"))?
+        .into_json()
+        .map_err(|e| format!("Update check failed: {e}"))
+}
+
+fn release_asset_url(release: &serde_json::Value, name: &str) -> Option<String> {
+    release["assets"]
+        .as_array()?
+        .iter()
+        .find(|asset| asset["name"].as_str() == Some(name))
+        .and_then(|asset| asset["browser_download_url"].as_str())
+        .map(|url| url.to_string())
+}
+
+#[tauri::command(async)]
+fn check_yt_dlp_update(app: tauri::AppHandle) -> Result<YtDlpUpdateInfo, String> {
+    let yt_dlp = tool_path(&app, "yt-dlp")?;
+    let current_version = tool_version(&yt_dlp, "--version")?;
+
+    let cache_path = yt_dlp_update_cache_path()?;
+    let cached: Option<YtDlpUpdateInfo> = std::fs::read_to_string(&cache_path)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok());
+    if let Some(cached) = cached {
+        let fresh = chrono::DateTime::parse_from_rfc3339(&cached.checked_at)
+            .map(|checked| {
+                app_now().signed_duration_since(checked)
+                    < chrono::Duration::hours(YT_DLP_UPDATE_CACHE_HOURS)
+            })
+            .unwrap_or(false);
+        // A locally replaced binary invalidates the cached comparison.
+        if fresh && cached.current_version == current_version {
+            return Ok(cached);
+        }
+    }
+
+    let release = fetch_latest_yt_dlp_release()?;
+    let latest_version = release["tag_name"]
+        .as_str()
+        .ok_or("Update check failed: release has no tag")?
+        .to_string();
+    let info = YtDlpUpdateInfo {
+        update_available: version_parts(&latest_version) > version_parts(&current_version),
+        download_url: release_asset_url(&release, YT_DLP_ASSET_NAME),
+        current_version,
+        latest_version,
+        checked_at: app_now().to_rfc3339(),
     };
 
-    let mut log_text = String::new();
-    log_text.push_str(&String::from_utf8_lossy(&output.stdout));
-    log_text.push_str(&String::from_utf8_lossy(&output.stderr));
-    let tail_lines: Vec<&str> = log_text.lines().rev().take(50).collect();
-    let tail_joined = tail_lines.into_iter().rev().collect::<Vec<&str>>().join("\\n");
-    let _ = append_video_trace_line(
-        &session_id,
-        &format!(
-            "{{\"stage\":\"backend_ffmpeg_exit\",\"code\":{},\"tail\":{}}}",
-            output.status.code().unwrap_or(-1),
-            serde_json::to_string(&tail_joined).unwrap_or_default()
-        ),
-    );
-    let _ = append_video_trace_line(&session_id, &log_text);
+    if let Ok(contents) = serde_json::to_string_pretty(&info) {
+        let _ = atomic_write(&cache_path, contents);
+    }
+    Ok(info)
+}
 
-    if !output.status.success() {
-        return Err("Export failed. See logs.".into());
+fn expected_sha256(sums: &str, asset_name: &str) -> Option<String> {
+    sums.lines().find_map(|line| {
+        let (hash, name) = line.split_once(char::is_whitespace)?;
+        (name.trim().trim_start_matches('*') == asset_name).then(|| hash.to_ascii_lowercase())
+    })
+}
+
+fn download_with_sha256(url: &str, dest: &Path) -> Result<String, String> {
+    use sha2::Digest;
+    use std::io::{Read, Write};
+
+    let response = http_agent()?
+        .get(url)
+        .set("User-Agent", "AudioWorkshop")
+        .call()
+        .map_err(|e| format!("Download failed: {e}"))?;
+    let mut reader = response.into_reader();
+    let mut file = std::fs::File::create(dest).map_err(|e| e.to_string())?;
+    let mut hasher = sha2::Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer).map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        file.write_all(&buffer[..read]).map_err(|e| e.to_string())?;
     }
+    file.sync_all().map_err(|e| e.to_string())?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
 
-    Ok(output_path.to_string_lossy().to_string())
+fn hash_reader<D: sha2::Digest>(mut reader: impl std::io::Read) -> Result<String, String> {
+    let mut hasher = D::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer).map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect())
 }
 
-#[tauri::command]
-fn get_binaries_dir(app: tauri::AppHandle) -> Result<String, String> {
-    let dir = binaries_dir(&app)?;
-    Ok(dir.to_string_lossy().to_string())
+#[tauri::command(async)]
+fn hash_file(path: String, algorithm: Option<String>) -> Result<String, String> {
+    let path = PathBuf::from(path);
+    if !path.is_file() || !is_managed_or_export(&path)? {
+        return Err("Invalid file path".into());
+    }
+    let file = std::fs::File::open(&path).map_err(|e| e.to_string())?;
+    let reader = std::io::BufReader::new(file);
+    match algorithm.as_deref().unwrap_or("sha256") {
+        "sha256" => hash_reader::<sha2::Sha256>(reader),
+        "sha384" => hash_reader::<sha2::Sha384>(reader),
+        "sha512" => hash_reader::<sha2::Sha512>(reader),
+        other => Err(format!(
+            "Unsupported hash algorithm '{other}'. Use sha256, sha384 or sha512"
+        )),
+    }
+}
+
+#[tauri::command(async)]
+fn update_yt_dlp(app: tauri::AppHandle) -> Result<String, String> {
+    let current = tool_path(&app, "yt-dlp")?;
+    let bin_dir = current
+        .parent()
+        .ok_or("Invalid yt-dlp path")?
+        .to_path_buf();
+    validate_writable_dir(&bin_dir)
+        .map_err(|e| format!("Binaries directory is not writable: {e}"))?;
+
+    let release = fetch_latest_yt_dlp_release()?;
+    let binary_url = release_asset_url(&release, YT_DLP_ASSET_NAME)
+        .ok_or("Latest release has no binary for this platform")?;
+    let sums_url =
+        release_asset_url(&release, "SHA2-256SUMS").ok_or("Latest release has no checksum file")?;
+    let sums = http_agent()?
+        .get(&sums_url)
+        .set("User-Agent", "AudioWorkshop")
+        .call()
+        .map_err(|e| format!("Checksum download failed: {e}"))?
+        .into_string()
+        .map_err(|e| e.to_string())?;
+    let expected = expected_sha256(&sums, YT_DLP_ASSET_NAME)
+        .ok_or("Checksum file does not list the yt-dlp binary")?;
+
+    // Download next to the target so the final rename stays on one volume.
+    let temp_path = bin_dir.join("yt-dlp.download.tmp");
+    let actual = match download_with_sha256(&binary_url, &temp_path) {
+        Ok(actual) => actual,
+        Err(err) => {
+            let _ = std::fs::remove_file(&temp_path);
+            return Err(err);
+        }
+    };
+    if actual != expected {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(format!(
+            "Checksum mismatch for downloaded yt-dlp (expected {expected}, got {actual}). Keeping the current version."
+        ));
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&temp_path, std::fs::Permissions::from_mode(0o755))
+            .map_err(|e| e.to_string())?;
+    }
+
+    let backup_path = current.with_extension("exe.bak");
+    let _ = std::fs::remove_file(&backup_path);
+    std::fs::rename(&current, &backup_path).map_err(|e| e.to_string())?;
+    if let Err(err) = std::fs::rename(&temp_path, &current) {
+        let _ = std::fs::rename(&backup_path, &current);
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(err.to_string());
+    }
+
+    match tool_version(&current, "--version") {
+        Ok(version) => {
+            let _ = std::fs::remove_file(yt_dlp_update_cache_path()?);
+            Ok(version)
+        }
+        Err(err) => {
+            let _ = std::fs::remove_file(&current);
+            std::fs::rename(&backup_path, &current).map_err(|e| e.to_string())?;
+            Err(format!("Updated yt-dlp failed to run ({err}); restored the previous version"))
+        }
+    }
 }
 
 #[tauri::command]
-fn get_export_root() -> Result<String, String> {
-    let root = resolve_export_root()?;
+fn get_download_root() -> Result<String, String> {
+    let root = resolve_download_root()?;
     validate_writable_dir(&root)?;
     Ok(root.to_string_lossy().to_string())
 }
 
 #[tauri::command]
-fn set_export_root(path: String) -> Result<String, String> {
+fn set_download_root(path: String) -> Result<String, String> {
     let mut settings = load_settings()?;
     if path.trim().is_empty() {
-        settings.export_root = None;
+        settings.download_root = None;
         save_settings(&settings)?;
-        return get_export_root();
+        return get_download_root();
+    }
+
+    let candidate = {
+        let raw = PathBuf::from(path.trim());
+        if raw.is_absolute() {
+            raw
+        } else {
+            app_root()?.join(raw)
+        }
+    };
+
+    validate_writable_dir(&candidate)?;
+    settings.download_root = Some(candidate.to_string_lossy().to_string());
+    save_settings(&settings)?;
+    get_download_root()
+}
+
+const FOLDER_SCHEMES: [&str; 3] = ["by-date", "flat", "by-month"];
+
+fn folder_scheme() -> String {
+    load_settings()
+        .ok()
+        .and_then(|s| s.folder_scheme)
+        .filter(|scheme| FOLDER_SCHEMES.contains(&scheme.as_str()))
+        .unwrap_or_else(|| "by-date".into())
+}
+
+// Session ids and log stamps: digit groups joined by single underscores,
+// e.g. 20240131_153000 or 20240131_153000_123.
+fn is_valid_stamp(stamp: &str) -> bool {
+    !stamp.is_empty()
+        && stamp.len() <= 64
+        && stamp
+            .split('_')
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn validate_log_stamp(stamp: &str) -> Result<(), String> {
+    if is_valid_stamp(stamp) {
+        Ok(())
+    } else {
+        Err("Invalid log stamp, expected digits separated by underscores".into())
+    }
+}
+
+fn validate_session_id(session_id: &str) -> Result<(), String> {
+    if is_valid_stamp(session_id) {
+        Ok(())
+    } else {
+        Err("Invalid session id".into())
+    }
+}
+
+fn is_full_date(date_folder: &str) -> bool {
+    let bytes = date_folder.as_bytes();
+    bytes.len() == 10
+        && bytes[4] == b'-'
+        && bytes[7] == b'-'
+        && chrono::NaiveDate::parse_from_str(date_folder, "%Y-%m-%d").is_ok()
+}
+
+fn is_year_month(date_folder: &str) -> bool {
+    date_folder.len() == 7 && is_full_date(&format!("{date_folder}-01"))
+}
+
+fn validate_date_folder(date_folder: &str) -> Result<(), String> {
+    if is_full_date(date_folder) {
+        Ok(())
+    } else {
+        Err("Invalid date folder, expected YYYY-MM-DD".into())
+    }
+}
+
+const ORGANIZE_MODES: [&str; 3] = ["date", "title", "none"];
+
+fn organize_by() -> String {
+    load_settings()
+        .ok()
+        .and_then(|s| s.organize_by)
+        .filter(|mode| ORGANIZE_MODES.contains(&mode.as_str()))
+        .unwrap_or_else(|| "date".into())
+}
+
+const RESERVED_DEVICE_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+const MAX_TITLE_FOLDER_CHARS: usize = 80;
+
+// Same cleaning as imported file names, plus the rules that only bite on
+// folders: no leading dots, a length cap and Windows device names.
+fn title_folder_name(title: &str) -> Option<String> {
+    let cleaned: String = harden_file_name(title)
+        .trim_start_matches(['.', ' '])
+        .chars()
+        .take(MAX_TITLE_FOLDER_CHARS)
+        .collect();
+    let cleaned = cleaned.trim_end_matches(['.', ' ']).to_string();
+    if cleaned.is_empty() {
+        return None;
+    }
+    let device = cleaned.split('.').next().unwrap_or_default().to_ascii_uppercase();
+    if RESERVED_DEVICE_NAMES.contains(&device.as_str()) {
+        return Some(format!("_{cleaned}"));
+    }
+    Some(cleaned)
+}
+
+// Date folders stay the fallback: exports without a usable title are filed
+// exactly as they were before title mode existed.
+fn organized_dir(root: &Path, date_folder: &str, title: Option<&str>) -> Result<PathBuf, String> {
+    match organize_by().as_str() {
+        "none" => Ok(root.to_path_buf()),
+        "title" => match title.and_then(title_folder_name) {
+            Some(name) => {
+                let dir = root.join(name);
+                // A preview may name a root that doesn't exist yet, which
+                // is_within can't resolve.
+                let inside = if is_dry_run() {
+                    normalize_path(&dir)?.starts_with(normalize_path(root)?)
+                } else {
+                    is_within(root, &dir)?
+                };
+                if !inside {
+                    return Err("Invalid title folder".into());
+                }
+                Ok(dir)
+            }
+            None => scheme_dir(root, date_folder),
+        },
+        _ => scheme_dir(root, date_folder),
+    }
+}
+
+#[tauri::command]
+fn get_organize_by() -> String {
+    organize_by()
+}
+
+#[tauri::command]
+fn set_organize_by(mode: String) -> Result<String, String> {
+    if !ORGANIZE_MODES.contains(&mode.as_str()) {
+        return Err(format!(
+            "Unknown organize mode '{mode}'. Use one of: {}",
+            ORGANIZE_MODES.join(", ")
+        ));
+    }
+    let mut settings = load_settings()?;
+    settings.organize_by = Some(mode);
+    save_settings(&settings)?;
+    Ok(organize_by())
+}
+
+// Maps a caller-supplied date folder onto the configured scheme. Only a
+// strict YYYY-MM-DD (or YYYY-MM for by-month) is accepted, so the value can
+// never carry extra path segments.
+fn scheme_dir(root: &Path, date_folder: &str) -> Result<PathBuf, String> {
+    let scheme = folder_scheme();
+    let full_date = is_full_date(date_folder);
+    let month_only = is_year_month(date_folder);
+    match scheme.as_str() {
+        "flat" if full_date || month_only => Ok(root.to_path_buf()),
+        "by-month" if full_date || month_only => Ok(root.join(&date_folder[..7])),
+        "by-date" => {
+            validate_date_folder(date_folder)?;
+            Ok(root.join(date_folder))
+        }
+        _ => Err("Invalid date folder".into()),
+    }
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn validate_date_stamp(date_folder: String, log_stamp: String) -> Result<(), String> {
+    validate_date_folder(&date_folder)?;
+    validate_log_stamp(&log_stamp)?;
+    Ok(())
+}
+
+fn use_utc() -> bool {
+    load_settings()
+        .ok()
+        .and_then(|s| s.use_utc)
+        .unwrap_or(false)
+}
+
+// Folder dates, stamps and log times all go through here so the use_utc
+// setting applies to every one of them.
+fn app_time(time: std::time::SystemTime) -> chrono::DateTime<chrono::FixedOffset> {
+    if use_utc() {
+        chrono::DateTime::<Utc>::from(time).fixed_offset()
+    } else {
+        chrono::DateTime::<Local>::from(time).fixed_offset()
+    }
+}
+
+fn app_now() -> chrono::DateTime<chrono::FixedOffset> {
+    app_time(std::time::SystemTime::now())
+}
+
+// Large enough for multi-hour lossless recordings; anything bigger has to be
+// allowed per operation through AudioOptions.max_input_mb.
+const DEFAULT_MAX_INPUT_MB: u64 = 8192;
+
+fn validate_max_input_mb(limit: u64) -> Result<(), String> {
+    if !(1..=1_048_576).contains(&limit) {
+        return Err("Input size limit must be between 1 MB and 1 TB".into());
+    }
+    Ok(())
+}
+
+fn max_input_mb() -> u64 {
+    load_settings()
+        .ok()
+        .and_then(|s| s.max_input_mb)
+        .filter(|limit| validate_max_input_mb(*limit).is_ok())
+        .unwrap_or(DEFAULT_MAX_INPUT_MB)
+}
+
+// Runs before any probing so an oversized file is rejected without ffmpeg
+// having to open it.
+fn check_input_size(path: &Path, override_mb: Option<u64>) -> Result<(), String> {
+    let limit_mb = match override_mb {
+        Some(limit) => {
+            validate_max_input_mb(limit)?;
+            limit
+        }
+        None => max_input_mb(),
+    };
+    let size = std::fs::metadata(path).map_err(|e| e.to_string())?.len();
+    let limit = limit_mb * 1024 * 1024;
+    if size > limit {
+        return Err(format!(
+            "InputTooLarge: {} is {size} bytes, over the {limit} byte limit",
+            path.to_string_lossy()
+        ));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn get_max_input_mb() -> u64 {
+    max_input_mb()
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn set_max_input_mb(max_input_mb: u64) -> Result<u64, String> {
+    validate_max_input_mb(max_input_mb)?;
+    let mut settings = load_settings()?;
+    settings.max_input_mb = Some(max_input_mb);
+    save_settings(&settings)?;
+    Ok(max_input_mb)
+}
+
+#[tauri::command]
+fn get_use_utc() -> bool {
+    use_utc()
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn set_use_utc(use_utc: bool) -> Result<bool, String> {
+    let mut settings = load_settings()?;
+    settings.use_utc = Some(use_utc);
+    save_settings(&settings)?;
+    Ok(use_utc)
+}
+
+#[tauri::command]
+fn get_folder_scheme() -> String {
+    folder_scheme()
+}
+
+#[tauri::command]
+fn set_folder_scheme(scheme: String) -> Result<String, String> {
+    if !FOLDER_SCHEMES.contains(&scheme.as_str()) {
+        return Err(format!(
+            "Unknown folder scheme '{scheme}'. Use one of: {}",
+            FOLDER_SCHEMES.join(", ")
+        ));
+    }
+    let mut settings = load_settings()?;
+    settings.folder_scheme = Some(scheme);
+    save_settings(&settings)?;
+    Ok(folder_scheme())
+}
+
+#[tauri::command]
+fn ensure_downloads_dir(date_folder: String) -> Result<String, String> {
+
+    let root = resolve_download_root()?;
+    validate_writable_dir(&root)?;
+
+    let download_dir = scheme_dir(&root, &date_folder)?;
+    std::fs::create_dir_all(&download_dir).map_err(|e| e.to_string())?;
+    Ok(download_dir.to_string_lossy().to_string())
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn prepare_temp_audio(date_folder: String, log_stamp: String) -> Result<String, String> {
+    validate_log_stamp(&log_stamp)?;
+
+    let root = tmp_root()?;
+    validate_writable_dir(&root)?;
+
+    let tmp_dir = scheme_dir(&root, &date_folder)?;
+    std::fs::create_dir_all(&tmp_dir).map_err(|e| e.to_string())?;
+
+    let file_name = format!("audioworkshop__{}.wav", log_stamp);
+    let path = tmp_dir.join(file_name);
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn write_binary_file(path: String, bytes: Vec<u8>) -> Result<(), String> {
+    let root = app_root()?;
+    let path = PathBuf::from(path);
+    if !is_within(&root, &path)? {
+        return Err("Invalid output path".into());
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    atomic_write(&path, bytes)
+}
+
+// Custom filters are limited to these; anything that can read files, load
+// plugins (ladspa, lv2, frei0r) or open sockets stays out. None of the
+// listed filters has an option that takes a file name.
+const CUSTOM_AUDIO_FILTERS: [&str; 32] = [
+    "acompressor",
+    "acrusher",
+    "adelay",
+    "aecho",
+    "afade",
+    "afftdn",
+    "aformat",
+    "agate",
+    "alimiter",
+    "anlmdn",
+    "apad",
+    "aresample",
+    "areverse",
+    "asetrate",
+    "atempo",
+    "atrim",
+    "bandpass",
+    "bass",
+    "chorus",
+    "compand",
+    "deesser",
+    "dynaudnorm",
+    "equalizer",
+    "extrastereo",
+    "highpass",
+    "loudnorm",
+    "lowpass",
+    "pan",
+    "silenceremove",
+    "stereotools",
+    "treble",
+    "volume",
+];
+const CUSTOM_VIDEO_FILTERS: [&str; 21] = [
+    "boxblur",
+    "colorbalance",
+    "colorchannelmixer",
+    "crop",
+    "drawbox",
+    "eq",
+    "fade",
+    "format",
+    "fps",
+    "gblur",
+    "hflip",
+    "hue",
+    "negate",
+    "noise",
+    "pad",
+    "rotate",
+    "scale",
+    "setsar",
+    "transpose",
+    "unsharp",
+    "vflip",
+];
+
+// One plain chain of allowlisted filters. `;` and brackets would add graph
+// links (the video filter is spliced into a -filter_complex graph when a
+// watermark is used) and quotes or escapes could hide a comma.
+fn check_filter_names(filter: &str, kind: &str) -> Result<(), String> {
+    let allowed: &[&str] = match kind {
+        "audio" => &CUSTOM_AUDIO_FILTERS,
+        "video" => &CUSTOM_VIDEO_FILTERS,
+        _ => return Err("Filter kind must be 'audio' or 'video'".into()),
+    };
+    if filter
+        .chars()
+        .any(|c| matches!(c, ';' | '[' | ']' | '\'' | '"' | '\\') || c.is_control())
+    {
+        return Err("Only a single chain of filters is allowed, without quotes or links".into());
+    }
+    for step in filter.split(',') {
+        let name = step.split('=').next().unwrap_or_default().trim();
+        if !allowed.contains(&name) {
+            return Err(format!("Filter '{name}' is not allowed"));
+        }
+    }
+    Ok(())
+}
+
+fn check_filter(app: &tauri::AppHandle, filter: &str, kind: &str) -> Result<(), String> {
+    let filter = filter.trim();
+    if filter.is_empty() {
+        return Err("Filter is empty".into());
+    }
+    check_filter_names(filter, kind)?;
+
+    let (source, flag) = match kind {
+        "audio" => ("anullsrc=r=44100:cl=stereo", "-af"),
+        _ => ("color=black:s=64x64:r=30", "-vf"),
+    };
+    let ffmpeg = ffmpeg_path(app)?;
+    let output = std::process::Command::new(ffmpeg)
+        .args(["-v", "error", "-f", "lavfi", "-i", source, "-t", "0.1"])
+        .args([flag, filter, "-f", "null", "-"])
+        .output()
+        .map_err(|e| e.to_string())?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let tail: Vec<&str> = stderr.lines().rev().take(5).collect();
+    Err(format!(
+        "Invalid filter: {}",
+        tail.into_iter().rev().collect::<Vec<&str>>().join(" ")
+    ))
+}
+
+#[tauri::command(async)]
+fn validate_filter(app: tauri::AppHandle, filter: String, kind: String) -> Result<(), String> {
+    check_filter(&app, &filter, &kind)
+}
+
+struct FfmpegExit {
+    success: bool,
+    code: Option<i32>,
+    tail: String,
+}
+
+const FFMPEG_LOG_LEVELS: [&str; 9] = [
+    "quiet", "panic", "fatal", "error", "warning", "info", "verbose", "debug", "trace",
+];
+
+fn available_threads() -> u32 {
+    std::thread::available_parallelism()
+        .map(|n| n.get() as u32)
+        .unwrap_or(1)
+}
+
+fn validate_ffmpeg_settings(log_level: Option<&str>, threads: Option<u32>) -> Result<(), String> {
+    if let Some(level) = log_level {
+        if !FFMPEG_LOG_LEVELS.contains(&level) {
+            return Err(format!(
+                "Unknown ffmpeg log level '{level}'. Use one of: {}",
+                FFMPEG_LOG_LEVELS.join(", ")
+            ));
+        }
+    }
+    if let Some(threads) = threads {
+        let max = available_threads();
+        if threads == 0 || threads > max {
+            return Err(format!("Thread count must be between 1 and {max}"));
+        }
+    }
+    Ok(())
+}
+
+// ffmpeg gets this many seconds per second of input (0 disables the limit),
+// but never less than the minimum, so short clips on slow machines still
+// have room.
+const DEFAULT_FFMPEG_TIMEOUT_FACTOR: f64 = 4.0;
+const MIN_FFMPEG_TIMEOUT_SECS: f64 = 300.0;
+
+fn validate_timeout_factor(factor: f64) -> Result<(), String> {
+    if !factor.is_finite() || !(0.0..=100.0).contains(&factor) {
+        return Err("Timeout factor must be between 0 (no limit) and 100".into());
+    }
+    Ok(())
+}
+
+fn ffmpeg_timeout_factor() -> f64 {
+    load_settings()
+        .ok()
+        .and_then(|s| s.ffmpeg_timeout_factor)
+        .filter(|f| validate_timeout_factor(*f).is_ok())
+        .unwrap_or(DEFAULT_FFMPEG_TIMEOUT_FACTOR)
+}
+
+fn longest_input_duration(app: &tauri::AppHandle, args: &[String]) -> f64 {
+    args.windows(2)
+        .filter(|pair| pair[0] == "-i")
+        .map(|pair| Path::new(&pair[1]))
+        .filter(|path| path.is_file())
+        .filter_map(|path| probe_media(app, path).ok().and_then(|p| p.duration))
+        .fold(0.0, f64::max)
+}
+
+fn ffmpeg_timeout(longest_input: f64) -> Option<std::time::Duration> {
+    let factor = ffmpeg_timeout_factor();
+    if factor == 0.0 {
+        return None;
+    }
+    let secs = (longest_input * factor).max(MIN_FFMPEG_TIMEOUT_SECS);
+    Some(std::time::Duration::from_secs_f64(secs))
+}
+
+// Adds the configured -loglevel and -progress up front and -threads as an
+// output option (just before the output path), so the encoder is capped as
+// well as the filter graph.
+fn with_ffmpeg_settings(args: &[String]) -> Vec<String> {
+    let settings = load_settings().unwrap_or_default();
+    let log_level = settings
+        .ffmpeg_log_level
+        .filter(|level| FFMPEG_LOG_LEVELS.contains(&level.as_str()));
+    let threads = settings
+        .ffmpeg_threads
+        .filter(|t| *t > 0)
+        .map(|t| t.min(available_threads()));
+
+    let mut full: Vec<String> = Vec::new();
+    if let Some(level) = log_level {
+        full.extend(["-loglevel".into(), level]);
+    }
+    if let Some(threads) = threads {
+        full.extend(["-filter_threads".into(), threads.to_string()]);
+    }
+    // Machine-readable progress goes to stdout unless ffmpeg writes its
+    // output there.
+    if !args.iter().any(|arg| arg == "-" || arg == "pipe:1") {
+        full.extend(["-progress".into(), "pipe:1".into()]);
+    }
+    match (threads, args.split_last()) {
+        (Some(threads), Some((output, rest))) => {
+            full.extend(rest.iter().cloned());
+            full.extend(["-threads".into(), threads.to_string(), output.clone()]);
+        }
+        _ => full.extend(args.iter().cloned()),
+    }
+    full
+}
+
+#[tauri::command]
+fn get_ffmpeg_settings() -> Result<FfmpegSettings, String> {
+    let settings = load_settings()?;
+    Ok(FfmpegSettings {
+        log_level: settings.ffmpeg_log_level,
+        threads: settings.ffmpeg_threads,
+        max_threads: available_threads(),
+        timeout_factor: ffmpeg_timeout_factor(),
+    })
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn set_ffmpeg_settings(
+    log_level: Option<String>,
+    threads: Option<u32>,
+    timeout_factor: Option<f64>,
+) -> Result<FfmpegSettings, String> {
+    let log_level = log_level
+        .map(|l| l.trim().to_ascii_lowercase())
+        .filter(|l| !l.is_empty());
+    validate_ffmpeg_settings(log_level.as_deref(), threads)?;
+    if let Some(factor) = timeout_factor {
+        validate_timeout_factor(factor)?;
+    }
+    let mut settings = load_settings()?;
+    settings.ffmpeg_log_level = log_level;
+    settings.ffmpeg_threads = threads;
+    settings.ffmpeg_timeout_factor = timeout_factor;
+    save_settings(&settings)?;
+    get_ffmpeg_settings()
+}
+
+fn run_ffmpeg_traced(
+    app: &tauri::AppHandle,
+    ffmpeg: &Path,
+    session_id: &str,
+    args: &[String],
+) -> Result<FfmpegExit, String> {
+    let args = with_ffmpeg_settings(args);
+    let _ = append_video_trace_line(
+        app,
+        session_id,
+        &format!(
+            "{{\"stage\":\"backend_ffmpeg_start\",\"args\":\"{}\"}}",
+            args.join(" ")
+        ),
+    );
+
+    let longest_input = longest_input_duration(app, &args);
+    let timeout = ffmpeg_timeout(longest_input);
+    let output = run_tracked(
+        app,
+        &export_process_id(session_id),
+        Command::new(ffmpeg).args(&args),
+        timeout,
+        Some(longest_input).filter(|d| *d > 0.0),
+    );
+    let output = match output {
+        Ok(output) => output,
+        Err(err) => {
+            let _ = append_video_trace_line(app, session_id, &format!("{{\"stage\":\"backend_ffmpeg_exit\",\"error\":\"{}\"}}", err));
+            return Err("Export failed. See logs.".into());
+        }
+    };
+    if output.stopped {
+        let _ = append_video_trace_line(app, session_id, "{\"stage\":\"backend_ffmpeg_exit\",\"cancelled\":true}");
+        return Err("Export cancelled".into());
+    }
+    if output.timed_out {
+        let secs = timeout.map(|t| t.as_secs()).unwrap_or(0);
+        let _ = append_video_trace_line(
+            app,
+            session_id,
+            &format!(
+                "{{\"stage\":\"backend_ffmpeg_exit\",\"timeout_secs\":{secs},\"tail\":{}}}",
+                serde_json::to_string(&tail_lines_of(&output.stderr, 50)).unwrap_or_default()
+            ),
+        );
+        return Err(format!("Timeout: ffmpeg did not finish within {secs}s"));
+    }
+
+    // stdout only carries -progress key=value lines, which would crowd the
+    // errors out of the tail.
+    let log_text = &output.stderr;
+    let tail_lines: Vec<&str> = log_text.lines().rev().take(50).collect();
+    let tail_joined = tail_lines.into_iter().rev().collect::<Vec<&str>>().join("\\n");
+    let _ = append_video_trace_line(
+        app,
+        session_id,
+        &format!(
+            "{{\"stage\":\"backend_ffmpeg_exit\",\"code\":{},\"tail\":{}}}",
+            output.status.code().unwrap_or(-1),
+            serde_json::to_string(&tail_joined).unwrap_or_default()
+        ),
+    );
+    let _ = append_video_trace_line(app, session_id, log_text);
+
+    let stderr_tail: Vec<&str> = output.stderr.lines().rev().take(20).collect();
+    Ok(FfmpegExit {
+        success: output.status.success(),
+        code: output.status.code(),
+        tail: stderr_tail.into_iter().rev().collect::<Vec<&str>>().join("\n"),
+    })
+}
+
+fn run_ffmpeg_logged(
+    app: &tauri::AppHandle,
+    ffmpeg: &Path,
+    session_id: &str,
+    args: &[String],
+) -> Result<(), String> {
+    if !run_ffmpeg_traced(app, ffmpeg, session_id, args)?.success {
+        return Err("Export failed. See logs.".into());
+    }
+    Ok(())
+}
+
+fn plan_audio_chain(
+    app: &tauri::AppHandle,
+    input_path: &Path,
+    options: &AudioOptions,
+    session_id: &str,
+) -> Result<AudioChain, String> {
+    if options.preflight.unwrap_or(false) && !is_dry_run() {
+        let report = run_preflight(app, input_path, Some(session_id));
+        if let Some(err) = report.error {
+            return Err(err);
+        }
+    }
+
+    // The input may be an m4a/mp3 handed over directly rather than the WAV the
+    // editor writes, so derive the output rate/layout from the source.
+    let probe = match probe_media(app, input_path) {
+        Ok(probe) => {
+            if !probe.has_audio {
+                let _ = append_video_trace_line(app, session_id, "{\"stage\":\"backend_probe\",\"error\":\"no_audio_stream\"}");
+                return Err("Input has no audio stream".into());
+            }
+            let _ = append_video_trace_line(
+                app,
+                session_id,
+                &format!(
+                    "{{\"stage\":\"backend_probe\",\"codec\":{},\"sample_rate\":{},\"channels\":{}}}",
+                    serde_json::to_string(&probe.audio_codec).unwrap_or_default(),
+                    serde_json::to_string(&probe.sample_rate).unwrap_or_default(),
+                    probe.channels
+                ),
+            );
+            Some(probe)
+        }
+        Err(err) => {
+            let _ = append_video_trace_line(app, session_id, &format!("{{\"stage\":\"backend_probe\",\"error\":{}}}", serde_json::to_string(&err).unwrap_or_default()));
+            None
+        }
+    };
+    let mut channels = probe
+        .as_ref()
+        .map(|p| p.channels.clamp(1, 2))
+        .unwrap_or(2);
+
+    let mut audio_filters: Vec<String> = Vec::new();
+    if let Some(requested) = options.channels {
+        let plan = probe
+            .as_ref()
+            .ok_or_else(|| "Unable to probe input channels".to_string())
+            .and_then(|probe| plan_channels(requested, probe));
+        let plan = match plan {
+            Ok(plan) => plan,
+            Err(err) => {
+                let _ = append_video_trace_line(app, session_id, &format!("{{\"stage\":\"backend_channels\",\"error\":{}}}", serde_json::to_string(&err).unwrap_or_default()));
+                return Err(err);
+            }
+        };
+        let _ = append_video_trace_line(
+            app,
+            session_id,
+            &format!(
+                "{{\"stage\":\"backend_channels\",\"channels\":{},\"filters\":{},\"warning\":{}}}",
+                plan.channels,
+                serde_json::to_string(&plan.filters).unwrap_or_default(),
+                serde_json::to_string(&plan.warning).unwrap_or_default()
+            ),
+        );
+        channels = plan.channels;
+        audio_filters.extend(plan.filters);
+    }
+
+    if let Some(method) = &options.denoise {
+        let filter = match denoise_filter(app, method, options.denoise_amount) {
+            Ok(filter) => filter,
+            Err(err) => {
+                let _ = append_video_trace_line(app, session_id, &format!("{{\"stage\":\"backend_denoise\",\"error\":{}}}", serde_json::to_string(&err).unwrap_or_default()));
+                return Err(err);
+            }
+        };
+        let _ = append_video_trace_line(
+            app,
+            session_id,
+            &format!(
+                "{{\"stage\":\"backend_denoise\",\"filter\":{}}}",
+                serde_json::to_string(&filter).unwrap_or_default()
+            ),
+        );
+        audio_filters.push(filter);
+    }
+
+    if let Some(gain) = options.gain_db {
+        if !gain.is_finite() || gain.abs() > 60.0 {
+            return Err("Gain must be between -60 and 60 dB".into());
+        }
+        let _ = append_video_trace_line(
+            app,
+            session_id,
+            &format!("{{\"stage\":\"backend_gain\",\"gain_db\":{gain:.2}}}"),
+        );
+        audio_filters.push(format!("volume={gain:.2}dB"));
+    }
+
+    if let Some(tempo) = options.tempo {
+        let stages = match atempo_chain(tempo) {
+            Ok(stages) => stages,
+            Err(err) => {
+                let _ = append_video_trace_line(app, session_id, &format!("{{\"stage\":\"backend_tempo\",\"error\":{}}}", serde_json::to_string(&err).unwrap_or_default()));
+                return Err(err);
+            }
+        };
+        let _ = append_video_trace_line(
+            app,
+            session_id,
+            &format!(
+                "{{\"stage\":\"backend_tempo\",\"factor\":{},\"filter\":{}}}",
+                tempo,
+                serde_json::to_string(&stages.join(",")).unwrap_or_default()
+            ),
+        );
+        audio_filters.extend(stages);
+    }
+
+    if let Some(filter) = &options.custom_filter {
+        if let Err(err) = check_filter(app, filter, "audio") {
+            let _ = append_video_trace_line(app, session_id, &format!("{{\"stage\":\"backend_custom_filter\",\"kind\":\"audio\",\"error\":{}}}", serde_json::to_string(&err).unwrap_or_default()));
+            return Err(err);
+        }
+        audio_filters.push(filter.trim().to_string());
+    }
+
+    if let Some(name) = &options.loudness_preset {
+        let preset = find_loudness_preset(name)?;
+        let filter = loudnorm_filter(preset);
+        let _ = append_video_trace_line(
+            app,
+            session_id,
+            &format!(
+                "{{\"stage\":\"backend_loudness\",\"preset\":\"{}\",\"filter\":\"{filter}\"}}",
+                preset.name
+            ),
+        );
+        audio_filters.push(filter);
+    }
+
+    let mut sample_rate = probe.as_ref().and_then(|p| p.sample_rate);
+    if let Some(filter) = resample_filter(app, options, session_id)? {
+        audio_filters.push(filter);
+        sample_rate = options.sample_rate;
+    }
+
+    if options.highpass_hz.is_some() || options.lowpass_hz.is_some() {
+        let filters = match pass_filters(options.highpass_hz, options.lowpass_hz, sample_rate) {
+            Ok(filters) => filters,
+            Err(err) => {
+                let _ = append_video_trace_line(app, session_id, &format!("{{\"stage\":\"backend_pass_filters\",\"error\":{}}}", serde_json::to_string(&err).unwrap_or_default()));
+                return Err(err);
+            }
+        };
+        let _ = append_video_trace_line(
+            app,
+            session_id,
+            &format!(
+                "{{\"stage\":\"backend_pass_filters\",\"highpass_hz\":{},\"lowpass_hz\":{}}}",
+                serde_json::to_string(&options.highpass_hz).unwrap_or_default(),
+                serde_json::to_string(&options.lowpass_hz).unwrap_or_default()
+            ),
+        );
+        audio_filters.extend(filters);
+    }
+
+    // The limiter goes last so it also catches peaks pushed up by gain. It is
+    // added whenever the user opts in: scanning the raw input can't tell what
+    // gain, EQ or loudness will do to the peaks, and below the limit it
+    // leaves the signal alone.
+    if options.limit_clipping.unwrap_or(false) {
+        let _ = append_video_trace_line(app, session_id, "{\"stage\":\"backend_limiter\"}");
+        audio_filters.push("alimiter=limit=0.97:level=disabled".into());
+    }
+
+    Ok(AudioChain {
+        sample_rate,
+        channels,
+        filters: audio_filters,
+    })
+}
+
+// With an allowlist configured, custom output roots must sit under one of
+// its entries. The lexical check runs before anything is created; the
+// second pass re-checks against the real directory once it exists.
+fn check_output_allowlist(root: &Path, created: bool) -> Result<(), String> {
+    let allowlist = load_settings()?.output_root_allowlist.unwrap_or_default();
+    if allowlist.is_empty() {
+        return Ok(());
+    }
+    // Preview exports land in the app's own tmp tree, which is always allowed.
+    if normalize_path(root)?.starts_with(normalize_path(&pending_exports_root()?)?) {
+        return Ok(());
+    }
+    let candidate = normalize_path(root)?;
+    let allowed = allowlist.iter().any(|entry| {
+        let entry = Path::new(entry);
+        if created {
+            is_within(entry, &root.join(".aw_allowlist_check")).unwrap_or(false)
+        } else {
+            normalize_path(entry)
+                .map(|entry| candidate.starts_with(entry))
+                .unwrap_or(false)
+        }
+    });
+    if !allowed {
+        return Err(format!(
+            "Output folder {} is outside the allowed export locations",
+            root.to_string_lossy()
+        ));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn get_output_allowlist() -> Result<Vec<String>, String> {
+    Ok(load_settings()?.output_root_allowlist.unwrap_or_default())
+}
+
+#[tauri::command]
+fn set_output_allowlist(paths: Vec<String>) -> Result<Vec<String>, String> {
+    let paths: Vec<String> = paths
+        .into_iter()
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .collect();
+    if let Some(relative) = paths.iter().find(|p| !Path::new(p).is_absolute()) {
+        return Err(format!("Allowlist entries must be absolute paths: {relative}"));
+    }
+    let mut settings = load_settings()?;
+    settings.output_root_allowlist = if paths.is_empty() {
+        None
+    } else {
+        Some(paths.clone())
+    };
+    save_settings(&settings)?;
+    Ok(paths)
+}
+
+fn resolve_output_dir(output_root: Option<String>, date_folder: &str) -> Result<PathBuf, String> {
+    resolve_output_dir_in(output_root, date_folder, None, resolve_export_root)
+}
+
+fn resolve_titled_output_dir(
+    output_root: Option<String>,
+    date_folder: &str,
+    title: Option<&str>,
+) -> Result<PathBuf, String> {
+    resolve_output_dir_in(output_root, date_folder, title, resolve_export_root)
+}
+
+fn resolve_video_output_dir(
+    output_root: Option<String>,
+    date_folder: &str,
+    title: Option<&str>,
+) -> Result<PathBuf, String> {
+    resolve_output_dir_in(output_root, date_folder, title, resolve_video_export_root)
+}
+
+fn resolve_output_dir_in(
+    output_root: Option<String>,
+    date_folder: &str,
+    title: Option<&str>,
+    default_root: fn() -> Result<PathBuf, String>,
+) -> Result<PathBuf, String> {
+    let custom_root = output_root.is_some();
+    let output_root = if let Some(root) = output_root {
+        let raw = PathBuf::from(root);
+        if raw.is_absolute() {
+            raw
+        } else {
+            app_root()?.join(raw)
+        }
+    } else {
+        default_root()?
+    };
+    if custom_root {
+        check_output_allowlist(&output_root, false)?;
+    }
+    if is_dry_run() {
+        check_dir_usable(&output_root)?;
+        return if custom_root {
+            Ok(output_root)
+        } else {
+            organized_dir(&output_root, date_folder, title)
+        };
+    }
+    validate_writable_dir(&output_root)?;
+    if custom_root {
+        check_output_allowlist(&output_root, true)?;
+    }
+
+    let export_dir = if custom_root {
+        output_root
+    } else {
+        organized_dir(&output_root, date_folder, title)?
+    };
+    std::fs::create_dir_all(&export_dir).map_err(|e| e.to_string())?;
+    Ok(export_dir)
+}
+
+struct VideoCodecSpec {
+    name: &'static str,
+    encoder: &'static str,
+    containers: &'static [&'static str],
+    presets: &'static [&'static str],
+    max_crf: u32,
+    default_crf: u32,
+}
+
+const X26X_PRESETS: [&str; 9] = [
+    "ultrafast", "superfast", "veryfast", "faster", "fast", "medium", "slow", "slower", "veryslow",
+];
+
+const VIDEO_CODECS: [VideoCodecSpec; 3] = [
+    VideoCodecSpec {
+        name: "x264",
+        encoder: "libx264",
+        containers: &["mp4", "matroska"],
+        presets: &X26X_PRESETS,
+        max_crf: 51,
+        default_crf: 23,
+    },
+    VideoCodecSpec {
+        name: "x265",
+        encoder: "libx265",
+        containers: &["mp4", "matroska"],
+        presets: &X26X_PRESETS,
+        max_crf: 51,
+        default_crf: 28,
+    },
+    // vp9 has no x26x-style presets; these map onto -deadline.
+    VideoCodecSpec {
+        name: "vp9",
+        encoder: "libvpx-vp9",
+        containers: &["mp4", "webm", "matroska"],
+        presets: &["realtime", "good", "best"],
+        max_crf: 63,
+        default_crf: 33,
+    },
+];
+
+// The background is a single static frame, so nearly every frame after the
+// first is a skip block; slow presets shrink the file at almost no extra
+// encode time.
+fn video_codec_args(options: &VideoOptions, container: &str) -> Result<Vec<String>, String> {
+    let name = options.video_codec.as_deref().unwrap_or("x264");
+    let spec = VIDEO_CODECS.iter().find(|c| c.name == name).ok_or_else(|| {
+        format!(
+            "Unknown video codec '{name}'. Use one of: {}",
+            VIDEO_CODECS.map(|c| c.name).join(", ")
+        )
+    })?;
+    if !spec.containers.contains(&container) {
+        return Err(format!("{} can't be stored in a {container} container", spec.name));
+    }
+    let mut args: Vec<String> = vec!["-c:v".into(), spec.encoder.into()];
+    if let Some(preset) = &options.preset {
+        if !spec.presets.contains(&preset.as_str()) {
+            return Err(format!(
+                "Unknown {} preset '{preset}'. Use one of: {}",
+                spec.name,
+                spec.presets.join(", ")
+            ));
+        }
+        let flag = if spec.name == "vp9" { "-deadline" } else { "-preset" };
+        args.extend([flag.into(), preset.clone()]);
+    }
+    let crf = options.crf.unwrap_or(spec.default_crf);
+    if crf > spec.max_crf {
+        return Err(format!("CRF for {} must be between 0 and {}", spec.name, spec.max_crf));
+    }
+    args.extend(["-crf".into(), crf.to_string()]);
+    match spec.name {
+        // Without -b:v 0 libvpx treats -crf as a cap on a default bitrate.
+        "vp9" => args.extend(["-b:v".into(), "0".into()]),
+        // Apple players only recognise HEVC in mp4 under the hvc1 tag.
+        "x265" if container == "mp4" => args.extend(["-tag:v".into(), "hvc1".into()]),
+        _ => {}
+    }
+    Ok(args)
+}
+
+const MP4_COPY_VIDEO_CODECS: [&str; 5] = ["h264", "hevc", "av1", "mpeg4", "vp9"];
+
+const WATERMARK_CORNERS: [&str; 5] =
+    ["top-left", "top-right", "bottom-left", "bottom-right", "center"];
+
+struct Watermark {
+    path: PathBuf,
+    // Filters for the logo input, ending in `[wm]`, and the overlay filter
+    // that places it on the background.
+    logo_chain: String,
+    overlay: String,
+}
+
+fn plan_watermark(
+    app: &tauri::AppHandle,
+    options: &VideoOptions,
+) -> Result<Option<Watermark>, String> {
+    let Some(raw) = options.watermark_path.as_deref().map(str::trim).filter(|p| !p.is_empty())
+    else {
+        return Ok(None);
+    };
+    let path = PathBuf::from(raw);
+    let is_png = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("png"));
+    if !path.is_file() || !is_png || !is_within(&app_root()?, &path)? {
+        return Err("Watermark must be a PNG inside the app folder".into());
+    }
+    if !probe_media(app, &path).is_ok_and(|probe| probe.has_video) {
+        return Err("Watermark image can't be decoded".into());
+    }
+    let opacity = options.watermark_opacity.unwrap_or(1.0);
+    if !opacity.is_finite() || !(0.0..=1.0).contains(&opacity) {
+        return Err("Watermark opacity must be between 0 and 1".into());
+    }
+    let scale = options.watermark_scale.unwrap_or(1.0);
+    if !scale.is_finite() || !(0.01..=4.0).contains(&scale) {
+        return Err("Watermark scale must be between 0.01 and 4".into());
+    }
+    let margin = options.watermark_margin.unwrap_or(24);
+    if margin > 1000 {
+        return Err("Watermark margin must be at most 1000 pixels".into());
+    }
+    let corner = options.watermark_corner.as_deref().unwrap_or("bottom-right");
+    let (x, y) = match corner {
+        "top-left" => (format!("{margin}"), format!("{margin}")),
+        "top-right" => (format!("main_w-overlay_w-{margin}"), format!("{margin}")),
+        "bottom-left" => (format!("{margin}"), format!("main_h-overlay_h-{margin}")),
+        "bottom-right" => (
+            format!("main_w-overlay_w-{margin}"),
+            format!("main_h-overlay_h-{margin}"),
+        ),
+        "center" => ("(main_w-overlay_w)/2".into(), "(main_h-overlay_h)/2".into()),
+        other => {
+            return Err(format!(
+                "Unknown watermark corner '{other}'. Use one of: {}",
+                WATERMARK_CORNERS.join(", ")
+            ))
+        }
+    };
+    Ok(Some(Watermark {
+        path,
+        logo_chain: format!(
+            "format=rgba,scale=iw*{scale}:-1,colorchannelmixer=aa={opacity}[wm]"
+        ),
+        overlay: format!("overlay={x}:{y}:shortest=1"),
+    }))
+}
+
+// Everything needed to run one ffmpeg export; building it runs all of the
+// validation, so previews and real runs reject the same inputs.
+struct FfmpegPlan {
+    ffmpeg: PathBuf,
+    args: Vec<String>,
+    output_dir: PathBuf,
+    file_name: String,
+    readonly: bool,
+}
+
+fn run_planned(
+    app: &tauri::AppHandle,
+    session_id: &str,
+    plan: FfmpegPlan,
+) -> Result<String, String> {
+    let output_path = run_ffmpeg_to_unique(
+        app,
+        &plan.ffmpeg,
+        session_id,
+        plan.args,
+        &plan.output_dir,
+        &plan.file_name,
+    )?;
+    if plan.readonly {
+        set_file_readonly(&output_path, true)?;
+    }
+    Ok(output_path.to_string_lossy().to_string())
+}
+
+fn is_readonly(path: &Path) -> bool {
+    std::fs::metadata(path).is_ok_and(|m| m.permissions().readonly())
+}
+
+fn set_file_readonly(path: &Path, readonly: bool) -> Result<(), String> {
+    let mut permissions = std::fs::metadata(path).map_err(|e| e.to_string())?.permissions();
+    if readonly {
+        permissions.set_readonly(true);
+    } else {
+        // set_readonly(false) would make the file world-writable on Unix, so
+        // only the owner's write bit is restored there.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            permissions.set_mode(permissions.mode() | 0o200);
+        }
+        #[cfg(not(unix))]
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+    }
+    std::fs::set_permissions(path, permissions).map_err(|e| e.to_string())
+}
+
+fn readonly_conflict(path: &Path) -> String {
+    format!(
+        "ReadOnly: {} is read-only; make it writable with clear_readonly or save under a new name",
+        path.to_string_lossy()
+    )
+}
+
+#[tauri::command]
+fn clear_readonly(path: String) -> Result<(), String> {
+    let path = PathBuf::from(path);
+    if !path.is_file() || !is_managed_or_export(&path)? {
+        return Err("Invalid file path".into());
+    }
+    set_file_readonly(&path, false)
+}
+
+fn plan_black_video(
+    app: &tauri::AppHandle,
+    input_audio_path: String,
+    session_id: String,
+    output_root: Option<String>,
+    audio_options: Option<AudioOptions>,
+    video_options: Option<VideoOptions>,
+    title: Option<String>,
+) -> Result<FfmpegPlan, String> {
+    let now = app_now();
+    let date_folder = now.format("%Y-%m-%d").to_string();
+    let stamp = session_id.clone();
+    let audio_options = audio_options.unwrap_or_default();
+    let video_options = video_options.unwrap_or_default();
+
+    let root = app_root()?;
+    let input_path = PathBuf::from(input_audio_path);
+    if !is_within(&root, &input_path)? {
+        let _ = append_video_trace_line(app, &session_id, "{\"stage\":\"backend_export_video_start\",\"error\":\"invalid_input_path\"}");
+        return Err("Invalid input path".into());
+    }
+    if let Err(err) = check_input_size(&input_path, audio_options.max_input_mb) {
+        let _ = append_video_trace_line(app, &session_id, &format!("{{\"stage\":\"backend_export_video_start\",\"error\":{}}}", serde_json::to_string(&err).unwrap_or_default()));
+        return Err(err);
+    }
+    let source_video = if video_options.passthrough_video.unwrap_or(false) {
+        let codec = probe_media(app, &input_path)?.video_codec;
+        let _ = append_video_trace_line(
+            app,
+            &session_id,
+            &format!(
+                "{{\"stage\":\"backend_video_passthrough\",\"codec\":{}}}",
+                serde_json::to_string(&codec).unwrap_or_default()
+            ),
+        );
+        codec
+    } else {
+        None
+    };
+    let (variant, resolution) = match source_video {
+        Some(_) => ("passthrough", "source"),
+        None => ("black", "1080x1920_30fps"),
+    };
+
+    let export_dir = match resolve_video_output_dir(output_root, &date_folder, title.as_deref()) {
+        Ok(dir) => dir,
+        Err(err) => {
+            let _ = append_video_trace_line(app, &session_id, &format!("{{\"stage\":\"backend_export_video_start\",\"error\":\"{}\"}}", err));
+            return Err("Export failed. See logs.".into());
+        }
+    };
+
+    let file_name = match apply_filename_template(
+        format!("audioworkshop__{stamp}__{resolution}__{variant}.mp4"),
+        &[
+            ("title", title.as_deref().unwrap_or("audioworkshop")),
+            ("date", &date_folder),
+            ("stamp", &stamp),
+            ("resolution", resolution),
+        ],
+        "mp4",
+    ) {
+        Ok(name) => name,
+        Err(err) => {
+            let _ = append_video_trace_line(app, &session_id, &format!("{{\"stage\":\"backend_export_video_start\",\"error\":{}}}", serde_json::to_string(&err).unwrap_or_default()));
+            return Err(err);
+        }
+    };
+    let _ = append_video_trace_line(
+        app,
+        &session_id,
+        &format!(
+            "{{\"stage\":\"backend_export_video_start\",\"input\":\"{}\"}}",
+            input_path.to_string_lossy()
+        ),
+    );
+
+    let ffmpeg = match ffmpeg_path(app) {
+        Ok(path) => path,
+        Err(err) => {
+            let _ = append_video_trace_line(app, &session_id, &format!("{{\"stage\":\"backend_export_video_start\",\"error\":\"{}\"}}", err));
+            return Err("Export failed. See logs.".into());
+        }
+    };
+
+    let chain = plan_audio_chain(app, &input_path, &audio_options, &session_id)?;
+    if let Some(filter) = &video_options.custom_filter {
+        if let Err(err) = check_filter(app, filter, "video") {
+            let _ = append_video_trace_line(app, &session_id, &format!("{{\"stage\":\"backend_custom_filter\",\"kind\":\"video\",\"error\":{}}}", serde_json::to_string(&err).unwrap_or_default()));
+            return Err(err);
+        }
+    }
+    let watermark = match plan_watermark(app, &video_options) {
+        Ok(watermark) => watermark,
+        Err(err) => {
+            let _ = append_video_trace_line(app, &session_id, &format!("{{\"stage\":\"backend_watermark\",\"error\":{}}}", serde_json::to_string(&err).unwrap_or_default()));
+            return Err(err);
+        }
+    };
+    let encoder = match select_audio_encoder(app, audio_options.audio_encoder.as_deref(), "aac", "mp4") {
+        Ok(encoder) => encoder,
+        Err(err) => {
+            let _ = append_video_trace_line(app, &session_id, &format!("{{\"stage\":\"backend_audio_encoder\",\"error\":{}}}", serde_json::to_string(&err).unwrap_or_default()));
+            return Err(err);
+        }
+    };
+
+    let software = || video_codec_args(&video_options, "mp4").map(|args| (args, "yuv420p"));
+    let video_plan = hardware_video_args(app, &video_options)
+        .and_then(|hardware| hardware.map_or_else(software, Ok));
+    let (video_args, pix_fmt) = match video_plan {
+        Ok(plan) => plan,
+        Err(err) => {
+            let _ = append_video_trace_line(app, &session_id, &format!("{{\"stage\":\"backend_video_codec\",\"error\":{}}}", serde_json::to_string(&err).unwrap_or_default()));
+            return Err(err);
+        }
+    };
+    let _ = append_video_trace_line(
+        app,
+        &session_id,
+        &format!(
+            "{{\"stage\":\"backend_video_codec\",\"args\":\"{}\"}}",
+            video_args.join(" ")
+        ),
+    );
+
+    // A kept video stream is copied as-is unless it needs filtering or mp4
+    // can't hold its codec, in which case it goes through the video encoder.
+    let copy_video = video_options.custom_filter.is_none()
+        && watermark.is_none()
+        && source_video
+            .as_deref()
+            .is_some_and(|codec| MP4_COPY_VIDEO_CODECS.contains(&codec));
+    let mut args: Vec<String> = if source_video.is_some() {
+        vec!["-y".into(), "-i".into(), path_arg(&input_path)?]
+    } else {
+        vec![
+            "-y".into(),
+            "-f".into(),
+            "lavfi".into(),
+            "-i".into(),
+            "color=black:s=1080x1920:r=30".into(),
+            "-i".into(),
+            path_arg(&input_path)?,
+        ]
+    };
+    // The logo is a single frame, so it is looped to last as long as the
+    // background; overlay's shortest=1 then ends it with the background (the
+    // black frame itself runs until -shortest stops at the audio).
+    if let Some(watermark) = &watermark {
+        args.extend(["-loop".into(), "1".into(), "-i".into(), path_arg(&watermark.path)?]);
+    }
+    let audio_input = if source_video.is_some() { 0 } else { 1 };
+    let video_map = if watermark.is_some() { "[v]" } else { "0:v:0" };
+    if source_video.is_some() || watermark.is_some() {
+        args.extend([
+            "-map".into(),
+            video_map.into(),
+            "-map".into(),
+            format!("{audio_input}:a:0"),
+        ]);
+    }
+    if source_video.is_none() {
+        args.push("-shortest".into());
+    }
+    if copy_video {
+        args.extend(["-c:v".into(), "copy".into()]);
+    } else {
+        args.extend(video_args);
+        args.extend(["-pix_fmt".into(), pix_fmt.into()]);
+        if source_video.is_none() {
+            args.extend(["-r".into(), "30".into()]);
+        }
+        let custom_filter = video_options.custom_filter.as_deref().map(str::trim);
+        match (&watermark, custom_filter) {
+            (Some(watermark), filter) => {
+                let logo_input = audio_input + 1;
+                let background = match filter {
+                    Some(filter) => format!("[0:v]{filter}[bg];[bg]"),
+                    None => "[0:v]".into(),
+                };
+                args.push("-filter_complex".into());
+                args.push(format!(
+                    "[{logo_input}:v]{};{background}[wm]{}[v]",
+                    watermark.logo_chain, watermark.overlay
+                ));
+            }
+            (None, Some(filter)) => {
+                args.push("-vf".into());
+                args.push(filter.to_string());
+            }
+            (None, None) => {}
+        }
+    }
+    if !chain.filters.is_empty() {
+        args.push("-af".into());
+        args.push(chain.filters.join(","));
+    }
+    args.extend(["-c:a".into(), encoder.clone()]);
+    if let Some(rate) = output_sample_rate(&encoder, chain.sample_rate) {
+        args.extend(["-ar".into(), rate.to_string()]);
+    }
+    args.extend(["-ac".into(), chain.channels.to_string()]);
+    let quality = effective_quality(audio_options.quality)?;
+    args.extend(quality_or_default(app, &session_id, &encoder, quality, Some("192k")));
+    args.extend(streaming_args(app, &session_id, "mp4", video_options.web_optimized));
+
+    Ok(FfmpegPlan {
+        ffmpeg,
+        args,
+        output_dir: export_dir,
+        file_name,
+        readonly: audio_options.make_readonly.unwrap_or(false),
+    })
+}
+
+#[tauri::command(async, rename_all = "camelCase")]
+fn export_black_video(
+    app: tauri::AppHandle,
+    input_audio_path: String,
+    session_id: String,
+    output_root: Option<String>,
+    audio_options: Option<AudioOptions>,
+    video_options: Option<VideoOptions>,
+    title: Option<String>,
+) -> Result<String, String> {
+    let plan = plan_black_video(
+        &app,
+        input_audio_path,
+        session_id.clone(),
+        output_root,
+        audio_options,
+        video_options,
+        title,
+    )?;
+    run_planned(&app, &session_id, plan)
+}
+
+const KENBURNS_FPS: u32 = 30;
+
+// zoompan expressions for one pass over the image; `on` is the output frame
+// number, so the motion always spans the whole clip.
+fn zoompan_filter(options: &KenBurnsOptions, frames: u64) -> Result<String, String> {
+    let speed = options.speed.unwrap_or(1.0);
+    if !speed.is_finite() || !(0.1..=5.0).contains(&speed) {
+        return Err("Zoom speed must be between 0.1 and 5".into());
+    }
+    let dz = (0.2 * speed).min(1.0);
+    let center_x = "iw/2-(iw/zoom/2)";
+    let center_y = "ih/2-(ih/zoom/2)";
+    let (z, x) = match options.direction.as_deref().unwrap_or("in") {
+        "in" => (format!("1+{dz}*on/{frames}"), center_x.to_string()),
+        "out" => (format!("{}-{dz}*on/{frames}", 1.0 + dz), center_x.to_string()),
+        "right" => (format!("{}", 1.0 + dz), format!("(iw-iw/zoom)*on/{frames}")),
+        "left" => (format!("{}", 1.0 + dz), format!("(iw-iw/zoom)*(1-on/{frames})")),
+        other => {
+            return Err(format!(
+                "Zoom direction must be in, out, left or right, not '{other}'"
+            ))
+        }
+    };
+    // The image is cropped to the 9:16 frame so zoompan never stretches it,
+    // and upscaled so the sub-pixel steps of a slow zoom don't jitter.
+    Ok(format!(
+        "scale=2160:3840:force_original_aspect_ratio=increase,crop=2160:3840,setsar=1,\
+         zoompan=z='{z}':x='{x}':y='{center_y}':d={frames}:s=1080x1920:fps={KENBURNS_FPS},\
+         format=yuv420p"
+    ))
+}
+
+#[tauri::command(async, rename_all = "camelCase")]
+fn export_kenburns_video(
+    app: tauri::AppHandle,
+    input_audio_path: String,
+    image_path: String,
+    session_id: String,
+    output_root: Option<String>,
+    motion: Option<KenBurnsOptions>,
+    title: Option<String>,
+) -> Result<String, String> {
+    validate_session_id(&session_id)?;
+    let input_path = PathBuf::from(input_audio_path);
+    let image_path = PathBuf::from(image_path);
+    for path in [&input_path, &image_path] {
+        if !path.is_file() || !is_managed_input(path)? {
+            let _ = append_video_trace_line(&app, &session_id, "{\"stage\":\"backend_export_kenburns_start\",\"error\":\"invalid_input_path\"}");
+            return Err("Invalid input path".into());
+        }
+        check_input_size(path, None)?;
+    }
+    let image = probe_media(&app, &image_path).map_err(|e| format!("Image can't be read: {e}"))?;
+    if !image.has_video {
+        return Err("Image can't be decoded".into());
+    }
+    let duration = probe_media(&app, &input_path)?
+        .duration
+        .filter(|d| *d > 0.0)
+        .ok_or("Unable to read the audio duration")?;
+    let frames = (duration * KENBURNS_FPS as f64).ceil() as u64;
+    let filter = zoompan_filter(&motion.unwrap_or_default(), frames)?;
+
+    let date_folder = app_now().format("%Y-%m-%d").to_string();
+    let export_dir = resolve_video_output_dir(output_root, &date_folder, title.as_deref())?;
+    let file_name = apply_filename_template(
+        format!("audioworkshop__{session_id}__1080x1920_30fps__kenburns.mp4"),
+        &[
+            ("title", title.as_deref().unwrap_or("audioworkshop")),
+            ("date", &date_folder),
+            ("stamp", &session_id),
+            ("resolution", "1080x1920_30fps"),
+        ],
+        "mp4",
+    )?;
+    let _ = append_video_trace_line(
+        &app,
+        &session_id,
+        &format!(
+            "{{\"stage\":\"backend_export_kenburns_start\",\"frames\":{frames},\"filter\":{}}}",
+            serde_json::to_string(&filter).unwrap_or_default()
+        ),
+    );
+
+    let chain = plan_audio_chain(&app, &input_path, &AudioOptions::default(), &session_id)?;
+    let quality = effective_quality(None)?;
+    let mut args: Vec<String> = vec![
+        "-y".into(),
+        "-i".into(),
+        path_arg(&image_path)?,
+        "-i".into(),
+        path_arg(&input_path)?,
+        "-filter_complex".into(),
+        format!("[0:v]{filter}[v]"),
+        "-map".into(),
+        "[v]".into(),
+        "-map".into(),
+        "1:a".into(),
+    ];
+    args.extend(video_codec_args(&VideoOptions::default(), "mp4")?);
+    if !chain.filters.is_empty() {
+        args.push("-af".into());
+        args.push(chain.filters.join(","));
+    }
+    args.extend(["-c:a".into(), "aac".into()]);
+    if let Some(rate) = output_sample_rate("aac", chain.sample_rate) {
+        args.extend(["-ar".into(), rate.to_string()]);
+    }
+    args.extend(["-ac".into(), chain.channels.to_string()]);
+    args.extend(quality_or_default(&app, &session_id, "aac", quality, Some("192k")));
+    args.extend(["-t".into(), format!("{duration:.3}")]);
+    args.extend(streaming_args(&app, &session_id, "mp4", None));
+
+    let ffmpeg = ffmpeg_path(&app)?;
+    let output_path =
+        run_ffmpeg_to_unique(&app, &ffmpeg, &session_id, args, &export_dir, &file_name)?;
+    Ok(output_path.to_string_lossy().to_string())
+}
+
+// Filters the generic run_ffmpeg command may use. Each entry is a single
+// filter name; chains are assembled server-side from validated steps.
+const ALLOWED_FFMPEG_FILTERS: [&str; 16] = [
+    "acompressor",
+    "adelay",
+    "afade",
+    "aformat",
+    "alimiter",
+    "aresample",
+    "atempo",
+    "atrim",
+    "bandpass",
+    "dynaudnorm",
+    "equalizer",
+    "highpass",
+    "loudnorm",
+    "lowpass",
+    "pan",
+    "volume",
+];
+
+#[derive(Debug, Deserialize)]
+struct FilterStep {
+    name: String,
+    args: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FfmpegJob {
+    input_path: String,
+    format: String,
+    output_name: Option<String>,
+    start: Option<f64>,
+    duration: Option<f64>,
+    channels: Option<u32>,
+    filters: Option<Vec<FilterStep>>,
+}
+
+#[derive(Debug, Serialize)]
+struct FfmpegRunResult {
+    exit_code: Option<i32>,
+    output_path: String,
+    stderr_tail: String,
+}
+
+fn filter_step_arg(step: &FilterStep) -> Result<String, String> {
+    let name = step.name.trim();
+    if !ALLOWED_FFMPEG_FILTERS.contains(&name) {
+        return Err(format!("Filter '{name}' is not allowed"));
+    }
+    match step.args.as_deref().map(str::trim).filter(|a| !a.is_empty()) {
+        Some(args) => {
+            // Commas, semicolons and brackets would let one step smuggle in
+            // further filters or extra graph links.
+            if args
+                .chars()
+                .any(|c| matches!(c, ',' | ';' | '[' | ']' | '\'' | '"' | '\\') || c.is_control())
+            {
+                return Err(format!("Invalid arguments for filter '{name}'"));
+            }
+            Ok(format!("{name}={args}"))
+        }
+        None => Ok(name.to_string()),
+    }
+}
+
+fn ffmpeg_job_args(
+    job: &FfmpegJob,
+    spec: &AudioFormatSpec,
+    input_path: &Path,
+    bitrate_args: Vec<String>,
+) -> Result<Vec<String>, String> {
+    let mut args: Vec<String> = vec!["-y".into()];
+    if let Some(start) = job.start {
+        if !start.is_finite() || start < 0.0 {
+            return Err("Start must be a non-negative number of seconds".into());
+        }
+        args.extend(["-ss".into(), format!("{start:.3}")]);
+    }
+    args.extend(["-i".into(), path_arg(input_path)?]);
+    if let Some(duration) = job.duration {
+        if !duration.is_finite() || duration <= 0.0 {
+            return Err("Duration must be a positive number of seconds".into());
+        }
+        args.extend(["-t".into(), format!("{duration:.3}")]);
+    }
+    args.push("-vn".into());
+
+    let filters = job
+        .filters
+        .iter()
+        .flatten()
+        .map(filter_step_arg)
+        .collect::<Result<Vec<String>, String>>()?;
+    if !filters.is_empty() {
+        args.extend(["-af".into(), filters.join(",")]);
+    }
+
+    let channels = job.channels.unwrap_or(2);
+    if !(1..=2).contains(&channels) {
+        return Err("Channels must be 1 (mono) or 2 (stereo)".into());
+    }
+    args.extend(audio_codec_args(
+        spec,
+        spec.codec,
+        None,
+        channels,
+        bitrate_args,
+    )?);
+    Ok(args)
+}
+
+#[tauri::command(async, rename_all = "camelCase")]
+fn run_ffmpeg(
+    app: tauri::AppHandle,
+    job: FfmpegJob,
+    session_id: String,
+) -> Result<FfmpegRunResult, String> {
+    validate_session_id(&session_id)?;
+    let input_path = PathBuf::from(&job.input_path);
+    if !input_path.is_file() || !is_within(&app_root()?, &input_path)? {
+        return Err("Invalid input path".into());
+    }
+    check_input_size(&input_path, None)?;
+    let spec = audio_format(&job.format.trim().to_ascii_lowercase()).ok_or_else(|| {
+        format!(
+            "Unsupported export format '{}'. Use one of: {}",
+            job.format,
+            supported_format_names()
+        )
+    })?;
+    let stem = job
+        .output_name
+        .clone()
+        .unwrap_or_else(|| format!("audioworkshop__{session_id}"));
+    validate_file_stem(&stem)?;
+    let file_name = format!("{stem}.{}", spec.extension);
+
+    let quality = effective_quality(None)?;
+    let bitrate_args = quality_or_default(&app, &session_id, spec.codec, quality, spec.bitrate);
+    let args = ffmpeg_job_args(&job, spec, &input_path, bitrate_args)?;
+    let ffmpeg = ffmpeg_path(&app)?;
+    let (output_path, exit) = run_ffmpeg_to_unique_traced(
+        &app,
+        &ffmpeg,
+        &session_id,
+        args,
+        &dated_export_dir()?,
+        &file_name,
+    )?;
+    Ok(FfmpegRunResult {
+        exit_code: exit.code,
+        output_path: output_path.to_string_lossy().to_string(),
+        stderr_tail: exit.tail,
+    })
+}
+
+// Uncommitted preview exports older than this are removed by the cleanup.
+const PENDING_EXPORT_GRACE: std::time::Duration = std::time::Duration::from_secs(2 * 60 * 60);
+
+fn pending_exports_root() -> Result<PathBuf, String> {
+    Ok(tmp_root()?.join("pending"))
+}
+
+fn cleanup_pending_exports() -> Result<usize, String> {
+    let root = pending_exports_root()?;
+    let Ok(entries) = std::fs::read_dir(&root) else {
+        return Ok(0);
+    };
+    let now = std::time::SystemTime::now();
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let expired = entry
+            .metadata()
+            .and_then(|meta| meta.modified())
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| age > PENDING_EXPORT_GRACE);
+        if expired && std::fs::remove_dir_all(entry.path()).is_ok() {
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+// Where a previewed export would have gone, kept beside it in the session
+// folder so commit_export can put it there.
+#[derive(Debug, Deserialize, Serialize)]
+struct PendingDestination {
+    kind: String,
+    output_root: Option<String>,
+    title: Option<String>,
+    date_folder: String,
+}
+
+const PENDING_DESTINATION_FILE: &str = ".destination.json";
+
+// Returns a per-session folder to pass as outputRoot to any export command;
+// the result can then be auditioned and kept with commit_export. `kind`,
+// `output_root` and `title` are what the export would have been given
+// without the preview.
+#[tauri::command(rename_all = "camelCase")]
+fn preview_export_dir(
+    session_id: String,
+    kind: Option<String>,
+    output_root: Option<String>,
+    title: Option<String>,
+) -> Result<String, String> {
+    validate_session_id(&session_id)?;
+    let kind = kind.unwrap_or_else(|| "audio".into());
+    if kind != "audio" && kind != "video" {
+        return Err(format!("Export kind must be 'audio' or 'video', not '{kind}'"));
+    }
+    let _ = cleanup_pending_exports();
+    let dir = pending_exports_root()?.join(&session_id);
+    validate_writable_dir(&dir)?;
+    let destination = PendingDestination {
+        kind,
+        output_root,
+        title,
+        date_folder: app_now().format("%Y-%m-%d").to_string(),
+    };
+    let contents = serde_json::to_string_pretty(&destination).map_err(|e| e.to_string())?;
+    atomic_write(&dir.join(PENDING_DESTINATION_FILE), contents)?;
+    Ok(dir.to_string_lossy().to_string())
+}
+
+fn pending_export_path(path: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(path);
+    if !path.is_file()
+        || path.file_name().is_some_and(|name| name == PENDING_DESTINATION_FILE)
+        || !is_within(&pending_exports_root()?, &path)?
+    {
+        return Err("Not a pending export".into());
+    }
+    Ok(path)
+}
+
+// Resolved the way the export itself would have; previews from before the
+// destination was recorded go to today's audio folder.
+fn pending_destination_dir(session_dir: &Path) -> Result<PathBuf, String> {
+    let destination = std::fs::read_to_string(session_dir.join(PENDING_DESTINATION_FILE))
+        .ok()
+        .and_then(|contents| serde_json::from_str::<PendingDestination>(&contents).ok());
+    let Some(destination) = destination else {
+        return dated_export_dir();
+    };
+    let title = destination.title.as_deref();
+    match destination.kind.as_str() {
+        "video" => {
+            resolve_video_output_dir(destination.output_root, &destination.date_folder, title)
+        }
+        _ => resolve_titled_output_dir(destination.output_root, &destination.date_folder, title),
+    }
+}
+
+// The session folder goes once its last preview is committed or discarded;
+// until then the destination record stays for the others.
+fn remove_pending_session_dir(source: &Path) {
+    let Some(session_dir) = source.parent() else {
+        return;
+    };
+    let waiting = std::fs::read_dir(session_dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .any(|entry| entry.file_name() != PENDING_DESTINATION_FILE)
+        })
+        .unwrap_or(true);
+    if !waiting {
+        let _ = std::fs::remove_file(session_dir.join(PENDING_DESTINATION_FILE));
+        let _ = std::fs::remove_dir(session_dir);
+    }
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn commit_export(pending_path: String, overwrite: Option<bool>) -> Result<String, String> {
+    let source = pending_export_path(&pending_path)?;
+    let file_name = source
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or("Invalid pending export")?
+        .to_string();
+    let export_dir = pending_destination_dir(source.parent().ok_or("Invalid pending export")?)?;
+    let dest = if overwrite.unwrap_or(false) {
+        export_dir.join(&file_name)
+    } else {
+        unique_path(&export_dir, &file_name)
+    };
+    if is_readonly(&dest) {
+        return Err(readonly_conflict(&dest));
+    }
+
+    // rename fails across volumes (custom export roots), so fall back to a
+    // copy followed by removing the preview.
+    if std::fs::rename(&source, &dest).is_err() {
+        std::fs::copy(&source, &dest).map_err(|e| e.to_string())?;
+        std::fs::remove_file(&source).map_err(|e| e.to_string())?;
+    }
+    remove_pending_session_dir(&source);
+    Ok(dest.to_string_lossy().to_string())
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn discard_export(pending_path: String) -> Result<(), String> {
+    let source = pending_export_path(&pending_path)?;
+    std::fs::remove_file(&source).map_err(|e| e.to_string())?;
+    remove_pending_session_dir(&source);
+    Ok(())
+}
+
+fn dated_export_dir() -> Result<PathBuf, String> {
+    let date_folder = app_now().format("%Y-%m-%d").to_string();
+    resolve_output_dir(None, &date_folder)
+}
+
+#[tauri::command(async, rename_all = "camelCase")]
+fn transcode_audio(
+    app: tauri::AppHandle,
+    input_path: String,
+    format: String,
+    session_id: String,
+    output_root: Option<String>,
+    audio_options: Option<AudioOptions>,
+) -> Result<String, String> {
+    let input_path = PathBuf::from(input_path);
+    if !input_path.is_file() || !is_managed_input(&input_path)? {
+        return Err("Invalid input path".into());
+    }
+    encode_audio_export(
+        &app,
+        &input_path,
+        &format,
+        &session_id,
+        output_root,
+        audio_options.unwrap_or_default(),
+        "",
+    )
+}
+
+const VIDEO_INPUT_EXTENSIONS: [&str; 7] = ["mp4", "mov", "m4v", "mkv", "webm", "avi", "wmv"];
+
+#[tauri::command(async, rename_all = "camelCase")]
+fn extract_audio_from_video(
+    app: tauri::AppHandle,
+    video_path: String,
+    format: String,
+    session_id: String,
+    output_root: Option<String>,
+    audio_options: Option<AudioOptions>,
+) -> Result<String, String> {
+    let video_path = PathBuf::from(video_path);
+    let ext = video_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+    if !video_path.is_file()
+        || !VIDEO_INPUT_EXTENSIONS.contains(&ext.as_str())
+        || !is_managed_input(&video_path)?
+    {
+        return Err("Invalid video path".into());
+    }
+    let video_path = canonical_path(&video_path)?;
+    let audio_options = audio_options.unwrap_or_default();
+    check_input_size(&video_path, audio_options.max_input_mb)?;
+
+    let probe = probe_media(&app, &video_path)?;
+    if !probe.has_audio {
+        return Err("This video has no audio track".into());
+    }
+    encode_audio_export(
+        &app,
+        &video_path,
+        &format,
+        &session_id,
+        output_root,
+        audio_options,
+        "",
+    )
+}
+
+fn convert_channels(
+    app: &tauri::AppHandle,
+    input_path: String,
+    session_id: &str,
+    channels: u32,
+    suffix: &str,
+) -> Result<String, String> {
+    let input_path = PathBuf::from(input_path);
+    if !input_path.is_file() || !is_managed_input(&input_path)? {
+        return Err("Invalid input path".into());
+    }
+    let format = input_format(&input_path)?;
+    encode_audio_export(
+        app,
+        &input_path,
+        format,
+        session_id,
+        None,
+        AudioOptions {
+            channels: Some(channels),
+            ..AudioOptions::default()
+        },
+        suffix,
+    )
+}
+
+fn validate_export_preset(app: &tauri::AppHandle, preset: &ExportPreset) -> Result<(), String> {
+    if preset.name.trim().is_empty() {
+        return Err("Preset name is empty".into());
+    }
+    let spec = audio_format(&preset.format).ok_or_else(|| {
+        format!(
+            "Unsupported export format '{}'. Use one of: {}",
+            preset.format,
+            supported_format_names()
+        )
+    })?;
+    let options = &preset.audio_options;
+    if options.channels.is_some_and(|c| !(1..=2).contains(&c)) {
+        return Err("Channels must be 1 (mono) or 2 (stereo)".into());
+    }
+    if let Some(tempo) = options.tempo {
+        atempo_chain(tempo)?;
+    }
+    if options.gain_db.is_some_and(|g| !g.is_finite() || g.abs() > 60.0) {
+        return Err("Gain must be between -60 and 60 dB".into());
+    }
+    if options.quality.is_some_and(|q| q > 100) {
+        return Err("Quality must be between 0 and 100".into());
+    }
+    select_audio_encoder(
+        app,
+        options.audio_encoder.as_deref(),
+        spec.codec,
+        spec.container,
+    )?;
+    if let Some(filter) = &options.custom_filter {
+        check_filter(app, filter, "audio")?;
+    }
+    if let Some(rate) = options.sample_rate {
+        validate_resample(rate, options.resampler.as_deref(), options.dither.as_deref())?;
+    }
+    if let Some(name) = &options.loudness_preset {
+        find_loudness_preset(name)?;
+    }
+    wav_bit_depth_codec(options.bit_depth.as_deref(), spec.container)?;
+    if let Some(method) = &options.denoise {
+        denoise_filter(app, method, options.denoise_amount)?;
+    }
+    pass_filters(options.highpass_hz, options.lowpass_hz, options.sample_rate)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn list_export_presets() -> Result<Vec<ExportPreset>, String> {
+    Ok(load_settings()?.export_presets.unwrap_or_default())
+}
+
+#[tauri::command(async)]
+fn save_export_preset(app: tauri::AppHandle, preset: ExportPreset) -> Result<(), String> {
+    let preset = ExportPreset {
+        name: preset.name.trim().to_string(),
+        format: preset.format.trim().to_ascii_lowercase(),
+        audio_options: preset.audio_options,
+    };
+    validate_export_preset(&app, &preset)?;
+    let mut settings = load_settings()?;
+    let presets = settings.export_presets.get_or_insert_with(Vec::new);
+    if presets
+        .iter()
+        .any(|existing| existing.name.eq_ignore_ascii_case(&preset.name))
+    {
+        return Err(format!("A preset named '{}' already exists", preset.name));
+    }
+    presets.push(preset);
+    save_settings(&settings)
+}
+
+#[tauri::command]
+fn delete_export_preset(name: String) -> Result<(), String> {
+    let mut settings = load_settings()?;
+    let presets = settings.export_presets.get_or_insert_with(Vec::new);
+    let before = presets.len();
+    presets.retain(|preset| preset.name != name);
+    if presets.len() == before {
+        return Err(format!("No preset named '{name}'"));
+    }
+    save_settings(&settings)
+}
+
+// Presets are stored in display order, so reordering rewrites the list itself.
+#[tauri::command]
+fn reorder_export_presets(names: Vec<String>) -> Result<Vec<ExportPreset>, String> {
+    let mut settings = load_settings()?;
+    let mut presets = settings.export_presets.take().unwrap_or_default();
+    if names.len() != presets.len() {
+        return Err(format!(
+            "Expected {} preset names but got {}",
+            presets.len(),
+            names.len()
+        ));
+    }
+    let mut ordered = Vec::with_capacity(presets.len());
+    for name in &names {
+        let index = presets
+            .iter()
+            .position(|preset| &preset.name == name)
+            .ok_or_else(|| format!("No preset named '{name}', or it is listed twice"))?;
+        ordered.push(presets.remove(index));
+    }
+    settings.export_presets = Some(ordered);
+    save_settings(&settings)?;
+    list_export_presets()
+}
+
+#[tauri::command(async, rename_all = "camelCase")]
+fn apply_export_preset(
+    app: tauri::AppHandle,
+    name: String,
+    input_path: String,
+    session_id: String,
+    output_root: Option<String>,
+) -> Result<String, String> {
+    let preset = list_export_presets()?
+        .into_iter()
+        .find(|preset| preset.name == name)
+        .ok_or_else(|| format!("No preset named '{name}'"))?;
+    let input_path = PathBuf::from(input_path);
+    if !input_path.is_file() || !is_managed_input(&input_path)? {
+        return Err("Invalid input path".into());
+    }
+    encode_audio_export(
+        &app,
+        &input_path,
+        &preset.format,
+        &session_id,
+        output_root,
+        preset.audio_options,
+        "",
+    )
+}
+
+// Only ever removes a folder under tmp, whatever the session id resolved to.
+fn remove_work_dir(dir: &Path) {
+    if tmp_root().and_then(|root| is_within(&root, dir)).unwrap_or(false) {
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
+
+// The source is decoded once into a float WAV that every target encodes
+// from, so an mp3 or m4a input isn't decoded again for each format. Each
+// target runs as `{session_id}_{index}`, which keys its own process entry and
+// video-export-progress events; multi-format-progress reports its state.
+#[tauri::command(async, rename_all = "camelCase")]
+fn export_multi_format(
+    app: tauri::AppHandle,
+    input_path: String,
+    targets: Vec<MultiFormatTarget>,
+    session_id: String,
+    output_root: Option<String>,
+) -> Result<Vec<MultiFormatResult>, String> {
+    validate_session_id(&session_id)?;
+    if targets.is_empty() {
+        return Err("No export formats given".into());
+    }
+    let input_path = PathBuf::from(input_path);
+    if !input_path.is_file() || !is_managed_input(&input_path)? {
+        return Err("Invalid input path".into());
+    }
+    let presets = list_export_presets()?;
+    let mut jobs = Vec::new();
+    for target in targets {
+        let (format, options) = match (&target.preset, target.format) {
+            (Some(name), _) => {
+                let preset = presets
+                    .iter()
+                    .find(|preset| &preset.name == name)
+                    .ok_or_else(|| format!("No preset named '{name}'"))?;
+                (preset.format.clone(), preset.audio_options.clone())
+            }
+            (None, Some(format)) => (format, target.audio_options.unwrap_or_default()),
+            (None, None) => return Err("Each target needs a format or a preset".into()),
+        };
+        // The size limit is checked against the source here, not against the
+        // much larger decoded copy each format is encoded from.
+        check_input_size(&input_path, options.max_input_mb)?;
+        let options = AudioOptions {
+            source: Some(input_path.clone()),
+            ..options
+        };
+        jobs.push((format, target.preset, options));
+    }
+
+    let stem = input_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("audioworkshop-output");
+    let work_dir = tmp_root()?.join("multi").join(&session_id);
+    std::fs::create_dir_all(&work_dir).map_err(|e| e.to_string())?;
+    let decoded = work_dir.join(format!("{stem}.wav"));
+    let ffmpeg = ffmpeg_path(&app)?;
+    // Float WAV passes 4 GB after about three hours of stereo; -rf64 switches
+    // the header to RF64 once it would, so long recordings decode intact.
+    let decode_args: Vec<String> = vec![
+        "-y".into(),
+        "-i".into(),
+        path_arg(&input_path)?,
+        "-vn".into(),
+        "-c:a".into(),
+        "pcm_f32le".into(),
+        "-rf64".into(),
+        "auto".into(),
+        path_arg(&decoded)?,
+    ];
+    if let Err(err) = run_ffmpeg_logged(&app, &ffmpeg, &session_id, &decode_args) {
+        remove_work_dir(&work_dir);
+        return Err(err);
+    }
+
+    let next = std::sync::atomic::AtomicUsize::new(0);
+    let results = Mutex::new(vec![None; jobs.len()]);
+    // Each encode also takes a shared job slot, so exports and downloads
+    // running elsewhere count against the same limit.
+    let workers = jobs.len().min(max_parallel_jobs());
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                let Some((format, preset, options)) = jobs.get(index) else {
+                    break;
+                };
+                let mut result = MultiFormatResult {
+                    format: format.clone(),
+                    preset: preset.clone(),
+                    export_session_id: format!("{session_id}_{index}"),
+                    output_path: None,
+                    error: None,
+                };
+                let _ = app.emit(
+                    "multi-format-progress",
+                    MultiFormatProgress {
+                        session_id: session_id.clone(),
+                        index,
+                        state: "running".into(),
+                        result: result.clone(),
+                    },
+                );
+                // A failed format is reported and the others carry on.
+                match encode_audio_export(
+                    &app,
+                    &decoded,
+                    format,
+                    &result.export_session_id,
+                    output_root.clone(),
+                    options.clone(),
+                    "",
+                ) {
+                    Ok(path) => result.output_path = Some(path),
+                    Err(err) => result.error = Some(err),
+                }
+                let state = if result.error.is_some() { "failed" } else { "done" };
+                let _ = app.emit(
+                    "multi-format-progress",
+                    MultiFormatProgress {
+                        session_id: session_id.clone(),
+                        index,
+                        state: state.into(),
+                        result: result.clone(),
+                    },
+                );
+                if let Ok(mut results) = results.lock() {
+                    results[index] = Some(result);
+                }
+            });
+        }
+    });
+    remove_work_dir(&work_dir);
+
+    let results: Vec<MultiFormatResult> = results
+        .into_inner()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .flatten()
+        .collect();
+    let failed = results.iter().filter(|r| r.error.is_some()).count();
+    let _ = append_video_trace_line(
+        &app,
+        &session_id,
+        &format!(
+            "{{\"stage\":\"backend_multi_format\",\"formats\":{},\"failed\":{failed}}}",
+            results.len()
+        ),
+    );
+    Ok(results)
+}
+
+#[tauri::command(async, rename_all = "camelCase")]
+fn resample_audio(
+    app: tauri::AppHandle,
+    input_path: String,
+    sample_rate: u32,
+    session_id: String,
+    output_root: Option<String>,
+    resampler: Option<String>,
+    dither: Option<String>,
+) -> Result<String, String> {
+    validate_resample(sample_rate, resampler.as_deref(), dither.as_deref())?;
+    let input_path = PathBuf::from(input_path);
+    if !input_path.is_file() || !is_managed_input(&input_path)? {
+        return Err("Invalid input path".into());
+    }
+    let format = input_format(&input_path)?;
+    encode_audio_export(
+        &app,
+        &input_path,
+        format,
+        &session_id,
+        output_root,
+        AudioOptions {
+            sample_rate: Some(sample_rate),
+            resampler,
+            dither,
+            ..AudioOptions::default()
+        },
+        &format!("_{sample_rate}hz"),
+    )
+}
+
+#[tauri::command(async, rename_all = "camelCase")]
+fn to_mono(app: tauri::AppHandle, input_path: String, session_id: String) -> Result<String, String> {
+    convert_channels(&app, input_path, &session_id, 1, "_mono")
+}
+
+#[tauri::command(async, rename_all = "camelCase")]
+fn to_stereo(app: tauri::AppHandle, input_path: String, session_id: String) -> Result<String, String> {
+    convert_channels(&app, input_path, &session_id, 2, "_stereo")
+}
+
+fn encode_audio_export(
+    app: &tauri::AppHandle,
+    input_path: &Path,
+    format: &str,
+    session_id: &str,
+    output_root: Option<String>,
+    audio_options: AudioOptions,
+    name_suffix: &str,
+) -> Result<String, String> {
+    let plan = plan_audio_export(
+        app,
+        input_path,
+        format,
+        session_id,
+        output_root,
+        audio_options,
+        name_suffix,
+    )?;
+    run_planned(app, session_id, plan)
+}
+
+// Adds the original file as a second input for its tags and, where the
+// format can hold it, its cover art; a real video stream is never copied.
+fn source_metadata_args(
+    app: &tauri::AppHandle,
+    source: &Path,
+    spec: &AudioFormatSpec,
+) -> Result<Vec<String>, String> {
+    let mut args: Vec<String> = vec![
+        "-i".into(),
+        path_arg(source)?,
+        "-map".into(),
+        "0:a:0".into(),
+        "-map_metadata".into(),
+        "1".into(),
+    ];
+    let probe = probe_media(app, source)?;
+    if spec.cover_art && probe.has_video && probe.video_codec.is_none() {
+        args.extend([
+            "-map".into(),
+            "1:v:0".into(),
+            "-c:v".into(),
+            "copy".into(),
+            "-disposition:v:0".into(),
+            "attached_pic".into(),
+        ]);
+    }
+    Ok(args)
+}
+
+fn plan_audio_export(
+    app: &tauri::AppHandle,
+    input_path: &Path,
+    format: &str,
+    session_id: &str,
+    output_root: Option<String>,
+    audio_options: AudioOptions,
+    name_suffix: &str,
+) -> Result<FfmpegPlan, String> {
+    let source = audio_options.source.as_deref();
+    check_input_size(source.unwrap_or(input_path), audio_options.max_input_mb)?;
+    let now = app_now();
+    let date_folder = now.format("%Y-%m-%d").to_string();
+    let format = format.trim().to_ascii_lowercase();
+    let spec = audio_format(&format).ok_or_else(|| {
+        format!(
+            "Unsupported export format '{format}'. Use one of: {}",
+            supported_format_names()
+        )
+    })?;
+
+    let title = input_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("audioworkshop-output");
+    let title = format!("{title}{name_suffix}");
+    let file_name = apply_filename_template(
+        format!("{title}.{}", spec.extension),
+        &[
+            ("title", &title),
+            ("date", &date_folder),
+            ("stamp", session_id),
+            ("resolution", ""),
+        ],
+        spec.extension,
+    )?;
+    let output_dir = resolve_output_dir(output_root, &date_folder)?;
+
+    let chain = plan_audio_chain(app, input_path, &audio_options, session_id)?;
+    let depth_codec = wav_bit_depth_codec(audio_options.bit_depth.as_deref(), spec.container)?;
+    let encoder = select_audio_encoder(
+        app,
+        audio_options.audio_encoder.as_deref(),
+        depth_codec.unwrap_or(spec.codec),
+        spec.container,
+    )?;
+    if depth_codec.is_some_and(|codec| codec != encoder) {
+        return Err(format!(
+            "Audio encoder '{encoder}' doesn't match the requested bit depth"
+        ));
+    }
+    if spec.container == "wav" {
+        let _ = append_video_trace_line(
+            app,
+            session_id,
+            &format!("{{\"stage\":\"backend_wav_codec\",\"codec\":\"{encoder}\"}}"),
+        );
+    }
+    let quality = effective_quality(audio_options.quality)?;
+    let bitrate_args = quality_or_default(app, session_id, &encoder, quality, spec.bitrate);
+    let codec_args = audio_codec_args(
+        spec,
+        &encoder,
+        chain.sample_rate,
+        chain.channels,
+        bitrate_args,
+    )?;
+
+    let ffmpeg = ffmpeg_path(app)?;
+    let mut args: Vec<String> = vec![
+        "-y".into(),
+        "-i".into(),
+        path_arg(input_path)?,
+    ];
+    match source {
+        Some(source) => args.extend(source_metadata_args(app, source, spec)?),
+        None => args.push("-vn".into()),
+    }
+    if !chain.filters.is_empty() {
+        args.push("-af".into());
+        args.push(chain.filters.join(","));
+    }
+    args.extend(codec_args);
+    args.extend(streaming_args(app, session_id, spec.container, audio_options.web_optimized));
+    Ok(FfmpegPlan {
+        ffmpeg,
+        args,
+        output_dir,
+        file_name,
+        readonly: audio_options.make_readonly.unwrap_or(false),
+    })
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PreviewRequest {
+    operation: String,
+    input_path: String,
+    format: Option<String>,
+    output_root: Option<String>,
+    audio_options: Option<AudioOptions>,
+    video_options: Option<VideoOptions>,
+    title: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct CommandPreview {
+    program: String,
+    args: Vec<String>,
+    input_path: String,
+    output_path: String,
+}
+
+// Builds the same plan the export would run and shows its final argv; the
+// output path is where the file would land if nothing else claims it first.
+#[tauri::command(async, rename_all = "camelCase")]
+fn preview_command(
+    app: tauri::AppHandle,
+    session_id: String,
+    request: PreviewRequest,
+) -> Result<CommandPreview, String> {
+    validate_session_id(&session_id)?;
+    let input_path = PathBuf::from(&request.input_path);
+    let plan = dry_run(|| match request.operation.as_str() {
+        "audio" => {
+            if !input_path.is_file() || !is_managed_input(&input_path)? {
+                return Err("Invalid input path".into());
+            }
+            plan_audio_export(
+                &app,
+                &input_path,
+                request.format.as_deref().unwrap_or("mp3"),
+                &session_id,
+                request.output_root,
+                request.audio_options.unwrap_or_default(),
+                "",
+            )
+        }
+        "black_video" => plan_black_video(
+            &app,
+            request.input_path.clone(),
+            session_id.clone(),
+            request.output_root,
+            request.audio_options,
+            request.video_options,
+            request.title,
+        ),
+        other => Err(format!(
+            "Operation must be 'audio' or 'black_video', not '{other}'"
+        )),
+    })?;
+    let output_path = unique_path(&plan.output_dir, &plan.file_name);
+    // The output goes on before the settings are applied, as in
+    // run_ffmpeg_traced, so per-output options land in front of it.
+    let mut args = plan.args.clone();
+    args.push(path_arg(&output_path)?);
+    let args = with_ffmpeg_settings(&args);
+    Ok(CommandPreview {
+        program: plan.ffmpeg.to_string_lossy().to_string(),
+        args,
+        input_path: input_path.to_string_lossy().to_string(),
+        output_path: output_path.to_string_lossy().to_string(),
+    })
+}
+
+struct RemuxTarget {
+    extension: &'static str,
+    container: &'static str,
+    fallback_codec: &'static str,
+}
+
+const REMUX_TARGETS: [RemuxTarget; 8] = [
+    RemuxTarget {
+        extension: "mp4",
+        container: "mp4",
+        fallback_codec: "aac",
+    },
+    RemuxTarget {
+        extension: "m4a",
+        container: "ipod",
+        fallback_codec: "aac",
+    },
+    RemuxTarget {
+        extension: "mp3",
+        container: "mp3",
+        fallback_codec: "libmp3lame",
+    },
+    RemuxTarget {
+        extension: "wav",
+        container: "wav",
+        fallback_codec: "pcm_s16le",
+    },
+    RemuxTarget {
+        extension: "flac",
+        container: "flac",
+        fallback_codec: "flac",
+    },
+    RemuxTarget {
+        extension: "ogg",
+        container: "ogg",
+        fallback_codec: "libopus",
+    },
+    RemuxTarget {
+        extension: "opus",
+        container: "ogg",
+        fallback_codec: "libopus",
+    },
+    RemuxTarget {
+        extension: "webm",
+        container: "webm",
+        fallback_codec: "libopus",
+    },
+];
+
+#[derive(Debug, Serialize)]
+struct RemuxResult {
+    output_path: String,
+    copied: bool,
+    source_codec: String,
+    output_codec: String,
+}
+
+// ffprobe reports decoder names; codec_fits_container speaks encoder names.
+fn stream_encoder_name(codec: &str) -> &str {
+    match codec {
+        "mp3" => "libmp3lame",
+        "opus" => "libopus",
+        "vorbis" => "libvorbis",
+        other => other,
+    }
+}
+
+fn remux_target(container: &str) -> Result<&'static RemuxTarget, String> {
+    let wanted = container.trim().trim_start_matches('.').to_ascii_lowercase();
+    REMUX_TARGETS
+        .iter()
+        .find(|t| t.extension == wanted)
+        .ok_or_else(|| {
+            let names: Vec<&str> = REMUX_TARGETS.iter().map(|t| t.extension).collect();
+            format!("Unsupported container '{wanted}'. Use one of: {}", names.join(", "))
+        })
+}
+
+struct RemuxPlan {
+    args: Vec<String>,
+    copied: bool,
+    source_codec: String,
+    output_codec: String,
+}
+
+// Arguments up to (not including) the output path; the stream is copied when
+// the target container can hold its codec and re-encoded otherwise.
+fn plan_remux(
+    app: &tauri::AppHandle,
+    input_path: &Path,
+    target: &RemuxTarget,
+) -> Result<RemuxPlan, String> {
+    let probe = probe_media(app, input_path)?;
+    if !probe.has_audio {
+        return Err("Input has no audio stream".into());
+    }
+    let source_codec = probe.audio_codec.clone().unwrap_or_default();
+    let copied = codec_fits_container(target.container, stream_encoder_name(&source_codec));
+
+    let mut args: Vec<String> = vec![
+        "-y".into(),
+        "-i".into(),
+        path_arg(input_path)?,
+        "-vn".into(),
+        "-map_metadata".into(),
+        "0".into(),
+    ];
+    let output_codec = if copied {
+        args.extend(["-c:a".into(), "copy".into()]);
+        source_codec.clone()
+    } else {
+        let encoder = target.fallback_codec;
+        args.extend(["-c:a".into(), encoder.into()]);
+        if let Some(rate) = output_sample_rate(encoder, probe.sample_rate) {
+            args.extend(["-ar".into(), rate.to_string()]);
+        }
+        encoder.to_string()
+    };
+    args.extend(["-f".into(), target.container.into()]);
+    Ok(RemuxPlan {
+        args,
+        copied,
+        source_codec,
+        output_codec,
+    })
+}
+
+#[tauri::command(async, rename_all = "camelCase")]
+fn remux(
+    app: tauri::AppHandle,
+    input_path: String,
+    container: String,
+    session_id: String,
+    output_root: Option<String>,
+) -> Result<RemuxResult, String> {
+    validate_session_id(&session_id)?;
+    let input_path = PathBuf::from(input_path);
+    if !input_path.is_file() || !is_managed_input(&input_path)? {
+        return Err("Invalid input path".into());
+    }
+    let target = remux_target(&container)?;
+    check_input_size(&input_path, None)?;
+    let plan = plan_remux(&app, &input_path, target)?;
+    if !plan.copied {
+        let _ = append_video_trace_line(
+            &app,
+            &session_id,
+            &format!(
+                "{{\"stage\":\"backend_remux\",\"warning\":\"reencode\",\"codec\":{},\"container\":\"{}\",\"encoder\":\"{}\"}}",
+                serde_json::to_string(&plan.source_codec).unwrap_or_default(),
+                target.container,
+                plan.output_codec
+            ),
+        );
+    }
+    let mut args = plan.args;
+    args.extend(streaming_args(&app, &session_id, target.container, None));
+
+    let date_folder = app_now().format("%Y-%m-%d").to_string();
+    let output_dir = resolve_output_dir(output_root, &date_folder)?;
+    let stem = input_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("audioworkshop-output");
+    let file_name = format!("{stem}.{}", target.extension);
+    let ffmpeg = ffmpeg_path(&app)?;
+    let output_path =
+        run_ffmpeg_to_unique(&app, &ffmpeg, &session_id, args, &output_dir, &file_name)?;
+    Ok(RemuxResult {
+        output_path: output_path.to_string_lossy().to_string(),
+        copied: plan.copied,
+        source_codec: plan.source_codec,
+        output_codec: plan.output_codec,
+    })
+}
+
+const NAMED_COLORS: [(&str, &str); 16] = [
+    ("black", "000000"),
+    ("white", "FFFFFF"),
+    ("red", "FF0000"),
+    ("green", "008000"),
+    ("lime", "00FF00"),
+    ("blue", "0000FF"),
+    ("yellow", "FFFF00"),
+    ("cyan", "00FFFF"),
+    ("magenta", "FF00FF"),
+    ("orange", "FFA500"),
+    ("purple", "800080"),
+    ("pink", "FFC0CB"),
+    ("gray", "808080"),
+    ("grey", "808080"),
+    ("silver", "C0C0C0"),
+    ("navy", "000080"),
+];
+
+// Accepts #RGB, #RRGGBB, #RRGGBBAA or a color name and returns ffmpeg's
+// 0xRRGGBB form, with @alpha (0-1) appended when the color isn't opaque.
+fn parse_color(input: &str) -> Result<String, String> {
+    let input = input.trim();
+    let invalid = || {
+        format!("Color '{input}' must be a name like 'white' or #RGB, #RRGGBB or #RRGGBBAA")
+    };
+    if let Some((_, hex)) = NAMED_COLORS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(input))
+    {
+        return Ok(format!("0x{hex}"));
+    }
+    let hex = input.strip_prefix('#').ok_or_else(invalid)?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    let hex = hex.to_ascii_uppercase();
+    let (rgb, alpha) = match hex.len() {
+        3 => (hex.chars().flat_map(|c| [c, c]).collect::<String>(), None),
+        6 => (hex, None),
+        8 => (hex[..6].to_string(), Some(&hex[6..])),
+        _ => return Err(invalid()),
+    };
+    match alpha.map(|a| u8::from_str_radix(a, 16).unwrap_or(255)) {
+        Some(alpha) if alpha < 255 => Ok(format!("0x{rgb}@{:.3}", alpha as f64 / 255.0)),
+        _ => Ok(format!("0x{rgb}")),
+    }
+}
+
+#[tauri::command]
+fn normalize_color(color: String) -> Result<String, String> {
+    parse_color(&color)
+}
+
+#[tauri::command(async, rename_all = "camelCase")]
+fn generate_waveform_image(
+    app: tauri::AppHandle,
+    input_path: String,
+    session_id: String,
+    width: Option<u32>,
+    height: Option<u32>,
+    color: Option<String>,
+) -> Result<String, String> {
+    let input_path = PathBuf::from(input_path);
+    if !input_path.is_file() || !is_managed_input(&input_path)? {
+        return Err("Invalid input path".into());
+    }
+    let width = width.unwrap_or(1200);
+    let height = height.unwrap_or(300);
+    if !(64..=7680).contains(&width) || !(32..=4320).contains(&height) {
+        return Err("Waveform size must be 64-7680 wide and 32-4320 high".into());
+    }
+    let color = parse_color(color.as_deref().unwrap_or("white"))?;
+
+    let stem = input_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("audioworkshop");
+    let file_name = format!("{stem}__waveform_{width}x{height}.png");
+    let args: Vec<String> = vec![
+        "-y".into(),
+        "-i".into(),
+        path_arg(&input_path)?,
+        "-filter_complex".into(),
+        format!("showwavespic=s={width}x{height}:colors={color}"),
+        "-frames:v".into(),
+        "1".into(),
+    ];
+    let ffmpeg = ffmpeg_path(&app)?;
+    let output_path =
+        run_ffmpeg_to_unique(&app, &ffmpeg, &session_id, args, &dated_export_dir()?, &file_name)?;
+    Ok(output_path.to_string_lossy().to_string())
+}
+
+const WAVEFORM_DATA_RATE: u32 = 8000;
+const MAX_WAVEFORM_BUCKETS: u32 = 100_000;
+
+#[tauri::command(async, rename_all = "camelCase")]
+fn export_waveform_data(
+    app: tauri::AppHandle,
+    input_path: String,
+    buckets: u32,
+    start: Option<f64>,
+    duration: Option<f64>,
+    write_file: Option<bool>,
+) -> Result<WaveformData, String> {
+    use std::io::Read;
+
+    let input_path = PathBuf::from(input_path);
+    if !input_path.is_file() || !is_managed_input(&input_path)? {
+        return Err("Invalid input path".into());
+    }
+    if !(1..=MAX_WAVEFORM_BUCKETS).contains(&buckets) {
+        return Err(format!("Bucket count must be between 1 and {MAX_WAVEFORM_BUCKETS}"));
+    }
+    let probe = probe_media(&app, &input_path)?;
+    if !probe.has_audio {
+        return Err("Input has no audio stream".into());
+    }
+    let total = probe.duration.ok_or("Unable to read the file duration")?;
+    let start = start.unwrap_or(0.0);
+    if !start.is_finite() || start < 0.0 || start >= total {
+        return Err("Start must be within the file".into());
+    }
+    let span = duration.unwrap_or(total - start).min(total - start);
+    if !span.is_finite() || span <= 0.0 {
+        return Err("Duration must be positive".into());
+    }
+    let channel_count = probe.channels.clamp(1, 2) as usize;
+
+    // Decoded PCM is folded into buckets as it arrives, so memory stays
+    // proportional to the bucket count rather than the file length.
+    let expected_frames = (span * WAVEFORM_DATA_RATE as f64).ceil().max(1.0) as u64;
+    let frames_per_bucket = expected_frames.div_ceil(buckets as u64).max(1);
+    let mut child = Command::new(ffmpeg_path(&app)?)
+        .args(["-hide_banner", "-nostats", "-ss", &start.to_string()])
+        .args(["-t", &span.to_string(), "-i"])
+        .arg(&input_path)
+        .args(["-vn", "-ac", &channel_count.to_string()])
+        .args(["-ar", &WAVEFORM_DATA_RATE.to_string(), "-f", "s16le", "-"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    let mut stdout = child.stdout.take().ok_or("Unable to read decoder output")?;
+
+    let mut channels: Vec<Vec<WaveformBucket>> = vec![Vec::new(); channel_count];
+    let mut current = vec![(f32::MAX, f32::MIN, 0.0f64); channel_count];
+    let mut frames_in_bucket = 0u64;
+    let frame_bytes = 2 * channel_count;
+    let mut buf = vec![0u8; frame_bytes * 4096];
+    let mut pending: Vec<u8> = Vec::new();
+    let mut flush = |current: &mut Vec<(f32, f32, f64)>, frames: u64| {
+        for (channel, (min, max, squares)) in current.iter_mut().enumerate() {
+            channels[channel].push(WaveformBucket {
+                min: *min,
+                max: *max,
+                rms: (*squares / frames as f64).sqrt() as f32,
+            });
+            *min = f32::MAX;
+            *max = f32::MIN;
+            *squares = 0.0;
+        }
+    };
+    loop {
+        let read = match stdout.read(&mut buf) {
+            Ok(read) => read,
+            Err(err) => {
+                let _ = child.kill();
+                return Err(err.to_string());
+            }
+        };
+        if read == 0 {
+            break;
+        }
+        pending.extend_from_slice(&buf[..read]);
+        let whole = pending.len() - pending.len() % frame_bytes;
+        for frame in pending[..whole].chunks_exact(frame_bytes) {
+            for (channel, sample) in frame.chunks_exact(2).enumerate() {
+                let value = i16::from_le_bytes([sample[0], sample[1]]) as f32 / 32768.0;
+                let (min, max, squares) = &mut current[channel];
+                *min = min.min(value);
+                *max = max.max(value);
+                *squares += (value as f64) * (value as f64);
+            }
+            frames_in_bucket += 1;
+            if frames_in_bucket == frames_per_bucket {
+                flush(&mut current, frames_in_bucket);
+                frames_in_bucket = 0;
+            }
+        }
+        pending.drain(..whole);
+    }
+    if frames_in_bucket > 0 {
+        flush(&mut current, frames_in_bucket);
+    }
+    let status = child.wait().map_err(|e| e.to_string())?;
+    if !status.success() {
+        return Err(format!(
+            "Waveform decode failed with code {}",
+            status.code().unwrap_or(-1)
+        ));
+    }
+
+    let mut data = WaveformData {
+        sample_rate: WAVEFORM_DATA_RATE,
+        bucket_seconds: frames_per_bucket as f64 / WAVEFORM_DATA_RATE as f64,
+        channels,
+        output_path: None,
+    };
+    if write_file.unwrap_or(false) {
+        let stem = input_path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("audioworkshop");
+        let file_name = format!("{stem}__waveform.json");
+        let path = reserve_output_path(&dated_export_dir()?, &file_name)?;
+        let json = serde_json::to_string(&data.channels).map_err(|e| e.to_string())?;
+        atomic_write(&path, json)?;
+        data.output_path = Some(path.to_string_lossy().to_string());
+    }
+    Ok(data)
+}
+
+const SPECTRUM_COLOR_MODES: [&str; 15] = [
+    "channel", "intensity", "rainbow", "moreland", "nebulae", "fire", "fiery", "fruit", "cool",
+    "magma", "green", "viridis", "plasma", "cividis", "terrain",
+];
+
+#[tauri::command(async, rename_all = "camelCase")]
+fn generate_spectrogram(
+    app: tauri::AppHandle,
+    input_path: String,
+    session_id: String,
+    width: Option<u32>,
+    height: Option<u32>,
+    color_mode: Option<String>,
+    scale: Option<String>,
+) -> Result<String, String> {
+    let input_path = PathBuf::from(input_path);
+    if !input_path.is_file() || !is_managed_input(&input_path)? {
+        return Err("Invalid input path".into());
+    }
+    let width = width.unwrap_or(1200);
+    let height = height.unwrap_or(600);
+    if !(64..=7680).contains(&width) || !(32..=4320).contains(&height) {
+        return Err("Spectrogram size must be 64-7680 wide and 32-4320 high".into());
+    }
+    let color_mode = color_mode.unwrap_or_else(|| "intensity".into());
+    if !SPECTRUM_COLOR_MODES.contains(&color_mode.as_str()) {
+        return Err(format!(
+            "Unknown color mode '{color_mode}'. Use one of: {}",
+            SPECTRUM_COLOR_MODES.join(", ")
+        ));
+    }
+    let fscale = match scale.as_deref().unwrap_or("linear") {
+        "linear" => "lin",
+        "log" => "log",
+        other => return Err(format!("Frequency scale must be 'linear' or 'log', not '{other}'")),
+    };
+
+    let stem = input_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("audioworkshop");
+    let file_name = format!("{stem}__spectrogram_{width}x{height}.png");
+    let args: Vec<String> = vec![
+        "-y".into(),
+        "-i".into(),
+        path_arg(&input_path)?,
+        "-filter_complex".into(),
+        format!("showspectrumpic=s={width}x{height}:color={color_mode}:fscale={fscale}"),
+        "-frames:v".into(),
+        "1".into(),
+    ];
+    let ffmpeg = ffmpeg_path(&app)?;
+    let output_path =
+        run_ffmpeg_to_unique(&app, &ffmpeg, &session_id, args, &dated_export_dir()?, &file_name)?;
+    Ok(output_path.to_string_lossy().to_string())
+}
+
+#[tauri::command(async, rename_all = "camelCase")]
+fn split_audio(
+    app: tauri::AppHandle,
+    input_path: String,
+    segment_seconds: f64,
+    session_id: String,
+) -> Result<Vec<String>, String> {
+    validate_session_id(&session_id)?;
+    let input_path = PathBuf::from(input_path);
+    if !input_path.is_file() || !is_managed_input(&input_path)? {
+        return Err("Invalid input path".into());
+    }
+    let spec = audio_format(input_format(&input_path)?).ok_or("Unsupported input format")?;
+    let duration = probe_media(&app, &input_path)?
+        .duration
+        .ok_or("Unable to read input duration")?;
+    if !segment_seconds.is_finite() || segment_seconds <= 0.0 {
+        return Err("Segment length must be a positive number of seconds".into());
+    }
+    if segment_seconds >= duration {
+        return Err(format!(
+            "Segment length must be shorter than the input ({duration:.1}s)"
+        ));
+    }
+
+    let stem = input_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("audioworkshop");
+    let out_dir = reserve_output_dir(&dated_export_dir()?, &format!("{stem}__segments"))?;
+    validate_writable_dir(&out_dir)?;
+    let pattern = out_dir.join(format!("{stem}_%03d.{}", spec.extension));
+
+    let ffmpeg = ffmpeg_path(&app)?;
+    let input_arg = path_arg(&input_path)?;
+    let pattern_arg = path_arg(&pattern)?;
+    let segment_args = |codec_args: Vec<String>| -> Vec<String> {
+        let mut args: Vec<String> = vec![
+            "-y".into(),
+            "-i".into(),
+            input_arg.clone(),
+            "-map".into(),
+            "0:a".into(),
+        ];
+        args.extend(codec_args);
+        args.extend([
+            "-f".into(),
+            "segment".into(),
+            "-segment_time".into(),
+            format!("{segment_seconds:.3}"),
+            "-reset_timestamps".into(),
+            "1".into(),
+            pattern_arg.clone(),
+        ]);
+        args
+    };
+    // Stream copy is fast and lossless; some inputs can't be cut that way,
+    // so fall back to re-encoding with the format's default codec.
+    let journal_id = journal_begin("segments", &out_dir);
+    let copied = run_ffmpeg_logged(&app, &ffmpeg, &session_id, &segment_args(vec!["-c".into(), "copy".into()]));
+    if copied.as_ref().is_err_and(|err| err == "Export cancelled") {
+        journal_end(journal_id);
+        return copied.map(|_| Vec::new());
+    }
+    if copied.is_err() {
+        for entry in std::fs::read_dir(&out_dir).map_err(|e| e.to_string())?.flatten() {
+            let _ = std::fs::remove_file(entry.path());
+        }
+        let mut codec_args: Vec<String> = vec!["-c:a".into(), spec.codec.into()];
+        if let Some(bitrate) = spec.bitrate {
+            codec_args.extend(["-b:a".into(), bitrate.into()]);
+        }
+        let encoded = run_ffmpeg_logged(&app, &ffmpeg, &session_id, &segment_args(codec_args));
+        journal_end(journal_id);
+        encoded?;
+    } else {
+        journal_end(journal_id);
+    }
+
+    let mut segments: Vec<PathBuf> = std::fs::read_dir(&out_dir)
+        .map_err(|e| e.to_string())?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect();
+    segments.sort();
+    // When the duration is an exact multiple of the segment length the muxer
+    // can leave an empty trailing file.
+    if let Some(last) = segments.last() {
+        if std::fs::metadata(last).map(|m| m.len() == 0).unwrap_or(false) {
+            let _ = std::fs::remove_file(last);
+            segments.pop();
+        }
+    }
+    Ok(segments
+        .iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect())
+}
+
+#[tauri::command(async, rename_all = "camelCase")]
+fn extract_clip(
+    app: tauri::AppHandle,
+    input_path: String,
+    start: f64,
+    duration: f64,
+    accurate: Option<bool>,
+    session_id: String,
+) -> Result<String, String> {
+    validate_session_id(&session_id)?;
+    let input_path = PathBuf::from(input_path);
+    if !input_path.is_file() || !is_managed_input(&input_path)? {
+        return Err("Invalid input path".into());
+    }
+    check_input_size(&input_path, None)?;
+    if !start.is_finite() || !duration.is_finite() || duration <= 0.0 {
+        return Err("Invalid clip range".into());
+    }
+
+    let probe = probe_media(&app, &input_path)?;
+    let total = probe
+        .duration
+        .ok_or("Unable to determine input duration")?;
+    // A negative start seeks relative to the end of the input.
+    let start = if start < 0.0 { total + start } else { start };
+    if start < 0.0 || start + duration > total + 0.05 {
+        return Err(format!(
+            "Clip range {start:.2}s-{:.2}s is outside the input duration ({total:.2}s)",
+            start + duration
+        ));
+    }
+
+    // Stream copy cuts on the nearest preceding keyframe, which only matters
+    // for video; audio packets are short enough to cut anywhere.
+    let reencode = accurate.unwrap_or(false)
+        && probe.has_video
+        && !starts_on_keyframe(&app, &input_path, start)?;
+
+    let export_dir = dated_export_dir()?;
+
+    let stem = input_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("audioworkshop");
+    let ext = input_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("m4a");
+    let file_name = format!(
+        "{stem}__clip_{}ms_{}ms.{ext}",
+        (start * 1000.0).round() as u64,
+        (duration * 1000.0).round() as u64
+    );
+
+    let _ = append_video_trace_line(
+        &app,
+        &session_id,
+        &format!(
+            "{{\"stage\":\"backend_extract_clip_start\",\"start\":{start},\"duration\":{duration},\"mode\":\"{}\"}}",
+            if reencode { "reencode" } else { "copy" }
+        ),
+    );
+
+    let ffmpeg = ffmpeg_path(&app)?;
+    let mut args: Vec<String> = vec![
+        "-y".into(),
+        "-ss".into(),
+        format!("{start:.3}"),
+        "-i".into(),
+        path_arg(&input_path)?,
+        "-t".into(),
+        format!("{duration:.3}"),
+    ];
+    if !reencode {
+        args.extend(["-c".into(), "copy".into()]);
+        args.extend(["-avoid_negative_ts".into(), "make_zero".into()]);
+    }
+    let output_path =
+        run_ffmpeg_to_unique(&app, &ffmpeg, &session_id, args, &export_dir, &file_name)?;
+
+    Ok(output_path.to_string_lossy().to_string())
+}
+
+// Brings each input to one rate and layout before the concat filter, which
+// requires matching streams.
+const CONCAT_INPUT_FORMAT: &str =
+    "aresample=44100,aformat=sample_fmts=fltp:channel_layouts=stereo";
+
+fn concat_filter_graph(count: usize) -> String {
+    let mut graph: Vec<String> = (0..count)
+        .map(|i| format!("[{i}:a:0]{CONCAT_INPUT_FORMAT}[a{i}]"))
+        .collect();
+    let labels: String = (0..count).map(|i| format!("[a{i}]")).collect();
+    graph.push(format!("{labels}concat=n={count}:v=0:a=1[joined]"));
+    graph.join(";")
+}
+
+#[tauri::command(async, rename_all = "camelCase")]
+fn concat_audio(
+    app: tauri::AppHandle,
+    input_paths: Vec<String>,
+    session_id: String,
+) -> Result<ConcatResult, String> {
+    validate_session_id(&session_id)?;
+    if input_paths.len() < 2 {
+        return Err("Select at least two files to join".into());
+    }
+    let mut inputs: Vec<PathBuf> = Vec::new();
+    let mut streams: Vec<(Option<String>, Option<u32>, u32)> = Vec::new();
+    for raw in input_paths {
+        let path = PathBuf::from(raw);
+        if !path.is_file() || !is_managed_input(&path)? {
+            return Err(format!("Invalid input path: {}", path.to_string_lossy()));
+        }
+        let probe = probe_media(&app, &path)?;
+        if !probe.has_audio {
+            return Err(format!("No audio stream in {}", path.to_string_lossy()));
+        }
+        streams.push((probe.audio_codec, probe.sample_rate, probe.channels));
+        inputs.push(path);
+    }
+    // The concat demuxer decodes every file with the first file's decoder, so
+    // it only works when all inputs share a codec and stream layout; anything
+    // else is decoded per input and joined with the concat filter.
+    let uniform = streams.windows(2).all(|pair| pair[0] == pair[1]);
+
+    let list_path = if uniform {
+        let tmp = tmp_root()?;
+        validate_writable_dir(&tmp)?;
+        let list_path = tmp.join(format!("concat_{session_id}.txt"));
+        let list = inputs
+            .iter()
+            .map(|path| Ok(format!("file '{}'", path_arg(path)?.replace('\'', "'\\''"))))
+            .collect::<Result<Vec<String>, String>>()?
+            .join("\n");
+        std::fs::write(&list_path, list).map_err(|e| e.to_string())?;
+        Some(list_path)
+    } else {
+        None
+    };
+
+    let result = dated_export_dir().and_then(|output_dir| {
+        let ffmpeg = ffmpeg_path(&app)?;
+        let quality = effective_quality(None)?;
+        let mut args: Vec<String> = vec!["-y".into()];
+        match &list_path {
+            // The demuxer passes packets straight through, so rate drift
+            // between files is smoothed while re-encoding to one stream.
+            Some(list_path) => args.extend([
+                "-f".into(),
+                "concat".into(),
+                "-safe".into(),
+                "0".into(),
+                "-i".into(),
+                path_arg(list_path)?,
+                "-vn".into(),
+                "-af".into(),
+                "aresample=async=1".into(),
+            ]),
+            None => {
+                for path in &inputs {
+                    args.extend(["-i".into(), path_arg(path)?]);
+                }
+                args.extend([
+                    "-filter_complex".into(),
+                    concat_filter_graph(inputs.len()),
+                    "-map".into(),
+                    "[joined]".into(),
+                ]);
+            }
+        }
+        args.extend([
+            "-c:a".into(),
+            "aac".into(),
+            "-ar".into(),
+            "44100".into(),
+            "-ac".into(),
+            "2".into(),
+        ]);
+        args.extend(quality_or_default(&app, &session_id, "aac", quality, Some("192k")));
+        let file_name = format!("audioworkshop__{session_id}__concat.m4a");
+        run_ffmpeg_to_unique(&app, &ffmpeg, &session_id, args, &output_dir, &file_name)
+    });
+    if let Some(list_path) = &list_path {
+        let _ = std::fs::remove_file(list_path);
+    }
+    let output_path = result?;
+
+    let duration = probe_media(&app, &output_path)
+        .ok()
+        .and_then(|probe| probe.duration);
+    Ok(ConcatResult {
+        output_path: output_path.to_string_lossy().to_string(),
+        duration,
+    })
+}
+
+const CHAPTER_EXTENSIONS: [&str; 4] = ["m4a", "mp4", "m4v", "mov"];
+
+fn escape_ffmetadata(value: &str) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn chapters_metadata(chapters: &[Chapter], duration: f64) -> Result<String, String> {
+    let mut text = String::from(";FFMETADATA1\n");
+    for (index, chapter) in chapters.iter().enumerate() {
+        let end = chapters.get(index + 1).map(|next| next.start).unwrap_or(duration);
+        if !chapter.start.is_finite() || chapter.start < 0.0 || chapter.start >= duration {
+            return Err(format!("Chapter {} starts outside the file", index + 1));
+        }
+        if end <= chapter.start {
+            return Err(format!("Chapter {} must start after chapter {}", index + 2, index + 1));
+        }
+        if chapter.title.trim().is_empty() {
+            return Err(format!("Chapter {} has no title", index + 1));
+        }
+        text.push_str(&format!(
+            "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            (chapter.start * 1000.0).round() as u64,
+            (end * 1000.0).round() as u64,
+            escape_ffmetadata(chapter.title.trim())
+        ));
+    }
+    Ok(text)
+}
+
+fn probe_chapter_count(app: &tauri::AppHandle, path: &Path) -> Result<usize, String> {
+    let output = Command::new(ffprobe_path(app)?)
+        .args(["-v", "error", "-show_chapters", "-of", "json"])
+        .arg(path)
+        .output()
+        .map_err(|e| e.to_string())?;
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).map_err(|e| e.to_string())?;
+    Ok(json
+        .get("chapters")
+        .and_then(|c| c.as_array())
+        .map(|c| c.len())
+        .unwrap_or(0))
+}
+
+// Remuxes an m4a/mp4 with the given chapters into a new file in the export
+// folder; streams are copied and the input is left as it was.
+#[tauri::command(async, rename_all = "camelCase")]
+fn write_chapters(
+    app: tauri::AppHandle,
+    input_path: String,
+    chapters: Vec<Chapter>,
+    session_id: String,
+) -> Result<ChaptersResult, String> {
+    validate_session_id(&session_id)?;
+    let input_path = PathBuf::from(input_path);
+    if !input_path.is_file() || !is_managed_or_export(&input_path)? {
+        return Err("Invalid input path".into());
+    }
+    let ext = input_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+    if !CHAPTER_EXTENSIONS.contains(&ext.as_str()) {
+        return Err(format!("Chapters need one of: {}", CHAPTER_EXTENSIONS.join(", ")));
+    }
+    if chapters.is_empty() {
+        return Err("Add at least one chapter".into());
+    }
+    let duration = probe_media(&app, &input_path)?
+        .duration
+        .ok_or("Unable to read the file duration")?;
+    let metadata = chapters_metadata(&chapters, duration)?;
+
+    let tmp = tmp_root()?;
+    validate_writable_dir(&tmp)?;
+    let metadata_path = tmp.join(format!("chapters_{session_id}.txt"));
+    std::fs::write(&metadata_path, metadata).map_err(|e| e.to_string())?;
+    let stem = input_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("audioworkshop");
+    let file_name = format!("{stem}__chapters.{ext}");
+
+    let args: Vec<String> = vec![
+        "-y".into(),
+        "-i".into(),
+        path_arg(&input_path)?,
+        "-f".into(),
+        "ffmetadata".into(),
+        "-i".into(),
+        path_arg(&metadata_path)?,
+        "-map".into(),
+        "0".into(),
+        "-map_metadata".into(),
+        "0".into(),
+        "-map_chapters".into(),
+        "1".into(),
+        "-c".into(),
+        "copy".into(),
+    ];
+    let result = ffmpeg_path(&app).and_then(|ffmpeg| {
+        let output_dir = dated_export_dir()?;
+        run_ffmpeg_to_unique(&app, &ffmpeg, &session_id, args, &output_dir, &file_name)
+    });
+    let _ = std::fs::remove_file(&metadata_path);
+    let output_path = result?;
+
+    let written = probe_chapter_count(&app, &output_path)?;
+    if written != chapters.len() {
+        let _ = std::fs::remove_file(&output_path);
+        return Err(format!(
+            "Expected {} chapters but the output has {written}",
+            chapters.len()
+        ));
+    }
+    let _ = append_video_trace_line(
+        &app,
+        &session_id,
+        &format!("{{\"stage\":\"backend_chapters\",\"count\":{written}}}"),
+    );
+    Ok(ChaptersResult {
+        output_path: output_path.to_string_lossy().to_string(),
+        chapters: written,
+    })
+}
+
+#[tauri::command(async, rename_all = "camelCase")]
+fn mix_audio(
+    app: tauri::AppHandle,
+    inputs: Vec<MixInput>,
+    format: String,
+    session_id: String,
+    output_root: Option<String>,
+    duration: Option<String>,
+) -> Result<String, String> {
+    validate_session_id(&session_id)?;
+    if inputs.len() < 2 {
+        return Err("Select at least two files to mix".into());
+    }
+    let duration = duration.unwrap_or_else(|| "longest".into());
+    if duration != "longest" && duration != "shortest" {
+        return Err("Mix duration must be 'longest' or 'shortest'".into());
+    }
+    let format = format.trim().to_ascii_lowercase();
+    let spec = audio_format(&format).ok_or_else(|| {
+        format!(
+            "Unsupported export format '{format}'. Use one of: {}",
+            supported_format_names()
+        )
+    })?;
+
+    let mut paths: Vec<PathBuf> = Vec::new();
+    let mut source_rate: Option<u32> = None;
+    let mut channels = 1;
+    for input in &inputs {
+        let path = PathBuf::from(&input.path);
+        if !path.is_file() || !is_managed_input(&path)? {
+            return Err(format!("Invalid input path: {}", input.path));
+        }
+        if input.gain_db.is_some_and(|g| !g.is_finite() || g.abs() > 60.0) {
+            return Err(format!("Gain for {} must be between -60 and 60 dB", input.path));
+        }
+        let probe = probe_media(&app, &path)
+            .map_err(|e| format!("Could not decode {}: {e}", input.path))?;
+        if !probe.has_audio {
+            return Err(format!("No audio stream in {}", input.path));
+        }
+        source_rate = source_rate.max(probe.sample_rate);
+        channels = channels.max(probe.channels.clamp(1, 2));
+        paths.push(path);
+    }
+
+    // Every input is resampled to one rate before amix, which otherwise
+    // rejects inputs whose sample rates differ.
+    let mix_rate = output_sample_rate(spec.codec, source_rate)
+        .or(source_rate)
+        .unwrap_or(48000);
+    let mut graph: Vec<String> = Vec::new();
+    for (index, input) in inputs.iter().enumerate() {
+        graph.push(format!(
+            "[{index}:a]aresample={mix_rate},volume={:.2}dB[a{index}]",
+            input.gain_db.unwrap_or(0.0)
+        ));
+    }
+    let labels = (0..inputs.len())
+        .map(|index| format!("[a{index}]"))
+        .collect::<String>();
+    graph.push(format!(
+        "{labels}amix=inputs={}:duration={duration}:normalize=0[mix]",
+        inputs.len()
+    ));
+    let _ = append_video_trace_line(
+        &app,
+        &session_id,
+        &format!(
+            "{{\"stage\":\"backend_mix\",\"inputs\":{},\"sample_rate\":{mix_rate},\"duration\":\"{duration}\"}}",
+            inputs.len()
+        ),
+    );
+
+    let date_folder = app_now().format("%Y-%m-%d").to_string();
+    let file_name = apply_filename_template(
+        format!("audioworkshop__{session_id}__mix.{}", spec.extension),
+        &[
+            ("title", "mix"),
+            ("date", &date_folder),
+            ("stamp", &session_id),
+            ("resolution", ""),
+        ],
+        spec.extension,
+    )?;
+    let output_dir = resolve_output_dir(output_root, &date_folder)?;
+
+    let quality = effective_quality(None)?;
+    let bitrate_args = quality_or_default(&app, &session_id, spec.codec, quality, spec.bitrate);
+    let codec_args = audio_codec_args(spec, spec.codec, Some(mix_rate), channels, bitrate_args)?;
+    let ffmpeg = ffmpeg_path(&app)?;
+    let mut args: Vec<String> = vec!["-y".into()];
+    for path in &paths {
+        args.push("-i".into());
+        args.push(path_arg(path)?);
+    }
+    args.extend([
+        "-filter_complex".into(),
+        graph.join(";"),
+        "-map".into(),
+        "[mix]".into(),
+    ]);
+    args.extend(codec_args);
+    let output_path =
+        run_ffmpeg_to_unique(&app, &ffmpeg, &session_id, args, &output_dir, &file_name)?;
+
+    Ok(output_path.to_string_lossy().to_string())
+}
+
+fn parse_db_value(text: &str) -> Option<f64> {
+    let value = text.trim().trim_end_matches("dB").trim();
+    if value.eq_ignore_ascii_case("-inf") {
+        return Some(f64::NEG_INFINITY);
+    }
+    value.parse::<f64>().ok()
+}
+
+fn parse_level_stats(stderr: &str) -> (LevelAnalysis, String) {
+    let mut analysis = LevelAnalysis::default();
+    let mut astats_block: Vec<&str> = Vec::new();
+    let mut in_channel = false;
+    for line in stderr.lines() {
+        let Some((prefix, body)) = line.split_once("] ") else {
+            continue;
+        };
+        let body = body.trim();
+        if prefix.contains("Parsed_astats") {
+            astats_block.push(body);
+            if body.starts_with("Channel:") {
+                in_channel = true;
+            } else if body.starts_with("Overall") {
+                in_channel = false;
+            } else if let Some(peak) = body.strip_prefix("Peak level dB:") {
+                if in_channel {
+                    if let Some(peak) = parse_db_value(peak) {
+                        analysis.channel_peaks.push(peak);
+                    }
+                }
+            }
+        } else if prefix.contains("Parsed_volumedetect") {
+            if let Some(value) = body.strip_prefix("max_volume:") {
+                analysis.max_volume = parse_db_value(value);
+            } else if let Some(value) = body.strip_prefix("mean_volume:") {
+                analysis.mean_volume = parse_db_value(value);
+            }
+        }
+    }
+    analysis.clipping_risk = analysis.max_volume.map(|v| v >= -0.1).unwrap_or(false);
+    (analysis, astats_block.join("\n"))
+}
+
+#[tauri::command(async, rename_all = "camelCase")]
+fn analyze_levels(
+    app: tauri::AppHandle,
+    input_path: String,
+    session_id: String,
+) -> Result<LevelAnalysis, String> {
+    let input_path = PathBuf::from(input_path);
+    if !input_path.is_file() || !is_managed_input(&input_path)? {
+        return Err("Invalid input path".into());
+    }
+
+    let ffmpeg = ffmpeg_path(&app)?;
+    let output = std::process::Command::new(ffmpeg)
+        .args(["-hide_banner", "-nostats", "-i"])
+        .arg(&input_path)
+        .args(["-vn", "-af", "astats=metadata=0,volumedetect", "-f", "null", "-"])
+        .output()
+        .map_err(|e| e.to_string())?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        let _ = append_video_trace_line(&app, &session_id, &stderr);
+        return Err(format!("Level analysis failed: {}", last_error_line(&stderr)));
+    }
+
+    let (analysis, astats_block) = parse_level_stats(&stderr);
+    let _ = append_video_trace_line(
+        &app,
+        &session_id,
+        &format!(
+            "{{\"stage\":\"backend_analyze_levels\",\"astats\":{}}}",
+            serde_json::to_string(&astats_block).unwrap_or_default()
+        ),
+    );
+    Ok(analysis)
+}
+
+// Durations may differ by an encoder's padding; anything more means the files
+// aren't two versions of the same audio.
+const COMPARE_DURATION_TOLERANCE: f64 = 0.1;
+// volumedetect measures 16-bit samples, so a residual this far below the
+// signal is at the quantization floor.
+const IDENTICAL_SNR_DB: f64 = 90.0;
+
+fn mean_volume_db(
+    app: &tauri::AppHandle,
+    inputs: &[&Path],
+    graph: &str,
+) -> Result<Option<f64>, String> {
+    let mut command = Command::new(ffmpeg_path(app)?);
+    command.args(["-hide_banner", "-nostats"]);
+    for input in inputs {
+        command.arg("-i").arg(input);
+    }
+    let output = command
+        .args(["-filter_complex", &format!("{graph},volumedetect")])
+        .args(["-f", "null", "-"])
+        .output()
+        .map_err(|e| e.to_string())?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(format!("Comparison failed: {}", last_error_line(&stderr)));
+    }
+    Ok(stderr
+        .lines()
+        .filter_map(|line| line.split_once("] "))
+        .filter(|(prefix, _)| prefix.contains("Parsed_volumedetect"))
+        .find_map(|(_, body)| body.trim().strip_prefix("mean_volume:"))
+        .and_then(parse_db_value))
+}
+
+// Both files are brought to the first one's rate and layout, then the second
+// is subtracted from the first; the residual's RMS against the reference's
+// RMS gives the score.
+#[tauri::command(async, rename_all = "camelCase")]
+fn compare_audio(
+    app: tauri::AppHandle,
+    first_path: String,
+    second_path: String,
+    session_id: String,
+) -> Result<AudioComparison, String> {
+    let first = PathBuf::from(first_path);
+    let second = PathBuf::from(second_path);
+    let mut probes = Vec::new();
+    for path in [&first, &second] {
+        if !path.is_file() || !is_managed_input(path)? {
+            return Err(format!("Invalid input path: {}", path.to_string_lossy()));
+        }
+        check_input_size(path, None)?;
+        let probe = probe_media(&app, path)?;
+        if !probe.has_audio {
+            return Err(format!("No audio stream in {}", path.to_string_lossy()));
+        }
+        probes.push(probe);
+    }
+    let (first_duration, second_duration) = match (probes[0].duration, probes[1].duration) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return Err("Unable to read the file durations".into()),
+    };
+    if (first_duration - second_duration).abs() > COMPARE_DURATION_TOLERANCE {
+        return Err(format!(
+            "Durations differ ({first_duration:.2}s vs {second_duration:.2}s)"
+        ));
+    }
+    let sample_rate = probes[0].sample_rate.unwrap_or(48000);
+    let resampled = probes[1].sample_rate != Some(sample_rate);
+    let layout = if probes[0].channels == 1 { "mono" } else { "stereo" };
+    let format =
+        format!("aresample={sample_rate},aformat=sample_fmts=fltp:channel_layouts={layout}");
+
+    let reference_db = mean_volume_db(&app, &[&first], &format!("[0:a]{format}"))?;
+    let difference_db = mean_volume_db(
+        &app,
+        &[&first, &second],
+        &format!(
+            "[0:a]{format}[a];[1:a]{format},volume=-1[b];[a][b]amix=inputs=2:duration=shortest:normalize=0"
+        ),
+    )?;
+
+    let (similarity, snr_db) = match (reference_db, difference_db) {
+        (_, Some(diff)) if diff == f64::NEG_INFINITY => (1.0, None),
+        (Some(reference), _) if reference == f64::NEG_INFINITY => (0.0, None),
+        (Some(reference), Some(diff)) => {
+            let ratio = 10f64.powf((diff - reference) / 20.0);
+            ((1.0 - ratio).clamp(0.0, 1.0), Some(reference - diff))
+        }
+        _ => return Err("Comparison produced no level readings".into()),
+    };
+    let identical = difference_db == Some(f64::NEG_INFINITY)
+        || snr_db.is_some_and(|snr| snr >= IDENTICAL_SNR_DB);
+    let _ = append_video_trace_line(
+        &app,
+        &session_id,
+        &format!(
+            "{{\"stage\":\"backend_compare_audio\",\"similarity\":{similarity},\"identical\":{identical},\"resampled\":{resampled}}}"
+        ),
+    );
+    Ok(AudioComparison {
+        similarity,
+        identical,
+        reference_db: reference_db.filter(|db| db.is_finite()),
+        difference_db: difference_db.filter(|db| db.is_finite()),
+        snr_db,
+        sample_rate,
+        resampled,
+    })
+}
+
+// AAC priming and MP3 frame padding add a fraction of a second; a still-image
+// video can also round up to the next frame.
+const VERIFY_DURATION_TOLERANCE: f64 = 0.5;
+
+// ffmpeg exits 0 even when `-shortest` cut the output or an encode stopped
+// early, so a finished export is checked against the file it came from.
+#[tauri::command(async, rename_all = "camelCase")]
+fn verify_export(
+    app: tauri::AppHandle,
+    source_path: String,
+    output_path: String,
+    session_id: String,
+    tolerance: Option<f64>,
+) -> Result<ExportVerification, String> {
+    let tolerance = tolerance.unwrap_or(VERIFY_DURATION_TOLERANCE);
+    if !tolerance.is_finite() || tolerance < 0.0 {
+        return Err("Tolerance must be a non-negative number of seconds".into());
+    }
+    let mut durations = Vec::new();
+    for path in [PathBuf::from(source_path), PathBuf::from(output_path)] {
+        if !path.is_file() || !is_managed_or_export(&path)? {
+            return Err(format!("Invalid input path: {}", path.to_string_lossy()));
+        }
+        let duration = probe_media(&app, &path)?
+            .duration
+            .ok_or_else(|| format!("Unable to read the duration of {}", path.to_string_lossy()))?;
+        durations.push(duration);
+    }
+    let (source_duration, output_duration) = (durations[0], durations[1]);
+    let difference = output_duration - source_duration;
+    let warning = (difference.abs() > tolerance).then(|| {
+        format!(
+            "DurationMismatch: export is {output_duration:.2}s but the source is {source_duration:.2}s"
+        )
+    });
+    let _ = append_video_trace_line(
+        &app,
+        &session_id,
+        &format!(
+            "{{\"stage\":\"backend_verify_export\",\"source_duration\":{source_duration},\"output_duration\":{output_duration},\"mismatch\":{}}}",
+            warning.is_some()
+        ),
+    );
+    Ok(ExportVerification {
+        source_duration,
+        output_duration,
+        difference,
+        tolerance,
+        warning,
+    })
+}
+
+// A channel counts as clipped when its peak sits at full scale; astats'
+// "Peak count" is then the number of samples that reached it.
+const FULL_SCALE_DB: f64 = -0.01;
+
+fn parse_clipping_stats(stderr: &str) -> ClippingReport {
+    let mut report = ClippingReport::default();
+    let mut current: Option<ChannelClipping> = None;
+    for line in stderr.lines() {
+        let Some((prefix, body)) = line.split_once("] ") else {
+            continue;
+        };
+        if !prefix.contains("Parsed_astats") {
+            continue;
+        }
+        let body = body.trim();
+        if let Some(channel) = body.strip_prefix("Channel:") {
+            report.channels.extend(current.take());
+            current = Some(ChannelClipping {
+                channel: channel.trim().parse().unwrap_or(0),
+                ..ChannelClipping::default()
+            });
+        } else if body.starts_with("Overall") {
+            report.channels.extend(current.take());
+        } else if let Some(channel) = current.as_mut() {
+            if let Some(peak) = body.strip_prefix("Peak level dB:") {
+                channel.peak_db = parse_db_value(peak);
+            } else if let Some(count) = body.strip_prefix("Peak count:") {
+                channel.clip_count = count.trim().parse::<f64>().unwrap_or(0.0) as u64;
+            }
+        }
+    }
+    report.channels.extend(current.take());
+
+    for channel in &mut report.channels {
+        if !channel.peak_db.is_some_and(|peak| peak >= FULL_SCALE_DB) {
+            channel.clip_count = 0;
+        }
+    }
+    report.sample_peak_db = report
+        .channels
+        .iter()
+        .filter_map(|channel| channel.peak_db)
+        .reduce(f64::max);
+    report.clip_count = report.channels.iter().map(|channel| channel.clip_count).sum();
+    report.clipped = report.clip_count > 0;
+    report
+}
+
+fn measure_clipping(app: &tauri::AppHandle, input_path: &Path) -> Result<ClippingReport, String> {
+    let ffmpeg = ffmpeg_path(app)?;
+    let output = Command::new(ffmpeg)
+        .args(["-hide_banner", "-nostats", "-i"])
+        .arg(input_path)
+        .args(["-vn", "-af", "astats=metadata=0", "-f", "null", "-"])
+        .output()
+        .map_err(|e| e.to_string())?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(format!("Clipping analysis failed: {}", last_error_line(&stderr)));
+    }
+    Ok(parse_clipping_stats(&stderr))
+}
+
+#[tauri::command(async, rename_all = "camelCase")]
+fn detect_clipping(
+    app: tauri::AppHandle,
+    input_path: String,
+    session_id: String,
+) -> Result<ClippingReport, String> {
+    let input_path = PathBuf::from(input_path);
+    if !input_path.is_file() || !is_managed_input(&input_path)? {
+        return Err("Invalid input path".into());
+    }
+    let report = measure_clipping(&app, &input_path)?;
+    let _ = append_video_trace_line(
+        &app,
+        &session_id,
+        &format!(
+            "{{\"stage\":\"backend_detect_clipping\",\"clipped\":{},\"clip_count\":{}}}",
+            report.clipped, report.clip_count
+        ),
+    );
+    Ok(report)
+}
+
+const LOUDNESS_PRESETS: [LoudnessPreset; 6] = [
+    LoudnessPreset {
+        name: "spotify",
+        label: "Spotify",
+        integrated_lufs: -14.0,
+        true_peak_db: -1.0,
+        loudness_range: 9.0,
+    },
+    LoudnessPreset {
+        name: "youtube",
+        label: "YouTube",
+        integrated_lufs: -14.0,
+        true_peak_db: -1.0,
+        loudness_range: 9.0,
+    },
+    LoudnessPreset {
+        name: "apple_music",
+        label: "Apple Music",
+        integrated_lufs: -16.0,
+        true_peak_db: -1.0,
+        loudness_range: 9.0,
+    },
+    LoudnessPreset {
+        name: "podcast",
+        label: "Podcast",
+        integrated_lufs: -16.0,
+        true_peak_db: -1.5,
+        loudness_range: 11.0,
+    },
+    LoudnessPreset {
+        name: "broadcast",
+        label: "Broadcast (EBU R128)",
+        integrated_lufs: -23.0,
+        true_peak_db: -1.0,
+        loudness_range: 15.0,
+    },
+    LoudnessPreset {
+        name: "audiobook",
+        label: "Audiobook (ACX)",
+        integrated_lufs: -20.0,
+        true_peak_db: -3.0,
+        loudness_range: 7.0,
+    },
+];
+
+fn find_loudness_preset(name: &str) -> Result<&'static LoudnessPreset, String> {
+    let name = name.trim().to_ascii_lowercase();
+    LOUDNESS_PRESETS.iter().find(|p| p.name == name).ok_or_else(|| {
+        format!(
+            "Unknown loudness preset '{name}'. Use one of: {}",
+            LOUDNESS_PRESETS.map(|p| p.name).join(", ")
+        )
+    })
+}
+
+fn loudnorm_filter(preset: &LoudnessPreset) -> String {
+    format!(
+        "loudnorm=I={}:TP={}:LRA={}",
+        preset.integrated_lufs, preset.true_peak_db, preset.loudness_range
+    )
+}
+
+#[tauri::command]
+fn loudness_presets() -> Vec<&'static LoudnessPreset> {
+    LOUDNESS_PRESETS.iter().collect()
+}
+
+// Integrated loudness in LUFS from a loudnorm analysis pass. Silent input
+// reports -inf, which comes back as None.
+fn measure_loudness(app: &tauri::AppHandle, input_path: &Path) -> Result<Option<f64>, String> {
+    let output = Command::new(ffmpeg_path(app)?)
+        .args(["-hide_banner", "-nostats", "-i"])
+        .arg(input_path)
+        .args(["-vn", "-af", "loudnorm=print_format=json", "-f", "null", "-"])
+        .output()
+        .map_err(|e| e.to_string())?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(format!("Loudness analysis failed: {}", last_error_line(&stderr)));
+    }
+    let start = stderr.rfind('{').ok_or("Loudness analysis produced no report")?;
+    let end = stderr[start..]
+        .find('}')
+        .map(|i| start + i + 1)
+        .ok_or("Loudness analysis produced no report")?;
+    let report: serde_json::Value =
+        serde_json::from_str(&stderr[start..end]).map_err(|e| e.to_string())?;
+    Ok(report["input_i"]
+        .as_str()
+        .and_then(|v| v.trim().parse::<f64>().ok())
+        .filter(|v| v.is_finite()))
+}
+
+#[tauri::command(async, rename_all = "camelCase")]
+fn normalize_album(
+    app: tauri::AppHandle,
+    input_paths: Vec<String>,
+    session_id: String,
+    format: Option<String>,
+    target_lufs: Option<f64>,
+    loudness_preset: Option<String>,
+) -> Result<Vec<AlbumTrackGain>, String> {
+    if input_paths.is_empty() {
+        return Err("No tracks given".into());
+    }
+    let preset = loudness_preset.as_deref().map(find_loudness_preset).transpose()?;
+    let target = match (preset, target_lufs) {
+        (Some(preset), _) => preset.integrated_lufs,
+        (None, target) => target.unwrap_or(-18.0),
+    };
+    if !(-40.0..=0.0).contains(&target) {
+        return Err("Target loudness must be between -40 and 0 LUFS".into());
+    }
+
+    let mut tracks = Vec::new();
+    for raw in &input_paths {
+        let path = PathBuf::from(raw);
+        if !path.is_file() || !is_managed_input(&path)? {
+            return Err(format!("Invalid input path: {raw}"));
+        }
+        check_input_size(&path, None)?;
+        let loudness = measure_loudness(&app, &path)?;
+        let duration = probe_media(&app, &path)
+            .ok()
+            .and_then(|p| p.duration)
+            .unwrap_or(1.0);
+        tracks.push((path, loudness, duration));
+    }
+
+    // Album loudness is the duration-weighted mean of each track's energy,
+    // so one short loud track doesn't dominate.
+    let (energy, weight) = tracks
+        .iter()
+        .filter_map(|(_, loudness, duration)| loudness.map(|l| (l, *duration)))
+        .fold((0.0, 0.0), |(energy, weight), (l, d)| {
+            (energy + 10f64.powf(l / 10.0) * d, weight + d)
+        });
+    if weight <= 0.0 {
+        return Err("All tracks are silent".into());
+    }
+    let album_loudness = 10.0 * (energy / weight).log10();
+    let gain = target - album_loudness;
+    let _ = append_video_trace_line(
+        &app,
+        &session_id,
+        &format!(
+            "{{\"stage\":\"backend_album_gain\",\"album_lufs\":{album_loudness:.2},\"target_lufs\":{target:.2},\"gain_db\":{gain:.2}}}"
+        ),
+    );
+
+    let mut results = Vec::new();
+    for (path, loudness, _) in tracks {
+        let format = match &format {
+            Some(format) => format.clone(),
+            None => input_format(&path)?.to_string(),
+        };
+        let output_path = encode_audio_export(
+            &app,
+            &path,
+            &format,
+            &session_id,
+            None,
+            AudioOptions {
+                gain_db: Some(gain),
+                ..AudioOptions::default()
+            },
+            "_album",
+        )?;
+        results.push(AlbumTrackGain {
+            input_path: path.to_string_lossy().to_string(),
+            output_path,
+            loudness,
+            gain_db: gain,
+        });
+    }
+    Ok(results)
+}
+
+fn operations_running(app: &tauri::AppHandle) -> Result<bool, String> {
+    let running = !app
+        .state::<ProcessRegistry>()
+        .0
+        .lock()
+        .map_err(|e| e.to_string())?
+        .is_empty();
+    let queue_busy = app
+        .state::<DownloadQueue>()
+        .0
+        .lock()
+        .map_err(|e| e.to_string())?
+        .worker_running;
+    Ok(running || queue_busy)
+}
+
+// Cleans up after operations the journal shows as started but never
+// completed, then starts a fresh journal.
+#[tauri::command]
+fn recover_operations(app: tauri::AppHandle) -> Result<Vec<RecoveredOperation>, String> {
+    if operations_running(&app)? {
+        return Err("Operations are still running".into());
+    }
+    let path = journal_path()?;
+    let Ok(text) = std::fs::read_to_string(&path) else {
+        return Ok(Vec::new());
+    };
+    let mut open: Vec<JournalRecord> = Vec::new();
+    for record in text
+        .lines()
+        .filter_map(|line| serde_json::from_str::<JournalRecord>(line).ok())
+    {
+        if record.state == "completed" {
+            open.retain(|started| started.id != record.id);
+        } else {
+            open.push(record);
+        }
+    }
+
+    // Paused downloads and queue items waiting for a retry continue from
+    // their partial files, so nothing in a folder they may use is touched.
+    let paused: Vec<PathBuf> = app
+        .state::<DownloadRegistry>()
+        .0
+        .lock()
+        .map_err(|e| e.to_string())?
+        .values()
+        .flat_map(|paused| paused.part_files.clone())
+        .collect();
+    let queue_pending = app
+        .state::<DownloadQueue>()
+        .0
+        .lock()
+        .map_err(|e| e.to_string())?
+        .items
+        .iter()
+        .any(|item| matches!(item.state.as_str(), "queued" | "interrupted" | "downloading"));
+
+    let mut recovered = Vec::new();
+    for record in open {
+        let target = PathBuf::from(&record.target);
+        let mut kept = Vec::new();
+        let leftovers: Vec<PathBuf> = match record.kind.as_str() {
+            "export" if target.is_file() => vec![target.clone()],
+            "segments" if target.is_dir() => {
+                let mut files = Vec::new();
+                collect_files(&target, &mut files)?;
+                files
+            }
+            "download" => {
+                // Older records hold the folder, newer ones the output template.
+                let dir = if target.is_dir() {
+                    target.clone()
+                } else {
+                    target.parent().map(Path::to_path_buf).unwrap_or_default()
+                };
+                let (claimed, unclaimed): (Vec<PathBuf>, Vec<PathBuf>) = download_partials(&dir)
+                    .into_iter()
+                    .filter(|file| !record.existing.contains(&file.to_string_lossy().to_string()))
+                    .partition(|file| queue_pending || paused.contains(file));
+                kept.extend(claimed.iter().map(|file| file.to_string_lossy().to_string()));
+                unclaimed
+            }
+            _ => Vec::new(),
+        };
+        let removed = leftovers
+            .into_iter()
+            .filter(|file| is_managed_or_export(file).unwrap_or(false))
+            .filter(|file| std::fs::remove_file(file).is_ok())
+            .map(|file| file.to_string_lossy().to_string())
+            .collect();
+        if record.kind == "segments" {
+            let _ = std::fs::remove_dir(&target);
+        }
+        recovered.push(RecoveredOperation {
+            kind: record.kind,
+            target: record.target,
+            started_at: record.time,
+            removed,
+            kept,
+        });
+    }
+    atomic_write(&path, "")?;
+    Ok(recovered)
+}
+
+const DATA_ROOT_ENTRIES: [&str; 9] = [
+    "downloads",
+    "logs",
+    "tmp",
+    "hooks",
+    "settings.json",
+    "history.json",
+    "queue.json",
+    "operations.journal",
+    "yt_dlp_update.json",
+];
+
+// Maps a path under the old data folder to the same place under the new one.
+fn rebase_path(path: &Path, old_root: &Path, new_root: &Path) -> Option<PathBuf> {
+    let path = normalize_path(path).ok()?;
+    let relative = path.strip_prefix(normalize_path(old_root).ok()?).ok()?;
+    Some(new_root.join(relative))
+}
+
+fn rebase_string(path: &mut String, old_root: &Path, new_root: &Path) {
+    if let Some(rebased) = rebase_path(Path::new(path.as_str()), old_root, new_root) {
+        *path = rebased.to_string_lossy().to_string();
+    }
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    if path.is_dir() {
+        for entry in std::fs::read_dir(path).map_err(|e| e.to_string())? {
+            collect_files(&entry.map_err(|e| e.to_string())?.path(), files)?;
+        }
+    } else if path.is_file() {
+        files.push(path.to_path_buf());
+    }
+    Ok(())
+}
+
+#[tauri::command(async, rename_all = "camelCase")]
+fn migrate_data_root(
+    app: tauri::AppHandle,
+    new_root: String,
+    remove_old: Option<bool>,
+) -> Result<DataMigration, String> {
+    if operations_running(&app)? {
+        return Err("Wait for running downloads and exports to finish before moving data".into());
+    }
+
+    let old_root = app_root()?;
+    let new_root = PathBuf::from(new_root.trim());
+    if !new_root.is_absolute() {
+        return Err("Choose an absolute folder for the data".into());
+    }
+    validate_writable_dir(&new_root)?;
+    let (old_norm, new_norm) = (normalize_path(&old_root)?, normalize_path(&new_root)?);
+    if old_norm == new_norm {
+        return Err("Data is already in that folder".into());
+    }
+    if new_norm.starts_with(&old_norm) || old_norm.starts_with(&new_norm) {
+        return Err("The new folder can't be inside the current data folder or contain it".into());
+    }
+    if new_root.join("settings.json").exists() {
+        return Err("The new folder already contains AudioWorkshop data".into());
+    }
+
+    // Folders configured inside the old root move along with it, so the
+    // settings can keep pointing at them relative to the new root.
+    let mut entries: Vec<PathBuf> = DATA_ROOT_ENTRIES
+        .iter()
+        .map(|entry| old_root.join(entry))
+        .collect();
+    for root in [resolve_download_root(), resolve_export_root(), resolve_video_export_root()]
+        .into_iter()
+        .flatten()
+    {
+        let root = normalize_path(&root)?;
+        let Ok(relative) = root.strip_prefix(&old_norm) else {
+            continue;
+        };
+        let covered = entries
+            .iter()
+            .any(|entry| normalize_path(entry).is_ok_and(|entry| root.starts_with(entry)));
+        if !relative.as_os_str().is_empty() && !covered {
+            entries.push(old_root.join(relative));
+        }
+    }
+    let mut files: Vec<PathBuf> = Vec::new();
+    for entry in &entries {
+        collect_files(entry, &mut files)?;
+    }
+    let sizes = files
+        .iter()
+        .map(|file| std::fs::metadata(file).map(|m| m.len()).map_err(|e| e.to_string()))
+        .collect::<Result<Vec<u64>, String>>()?;
+    let mut progress = MigrationProgress {
+        files_copied: 0,
+        files_total: files.len(),
+        bytes_copied: 0,
+        bytes_total: sizes.iter().sum(),
+    };
+    let _ = app.emit("data-migration", progress.clone());
+
+    for (file, size) in files.iter().zip(&sizes) {
+        let relative = file.strip_prefix(&old_root).map_err(|e| e.to_string())?;
+        let dest = new_root.join(relative);
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let copied = std::fs::copy(file, &dest).map_err(|e| e.to_string())?;
+        if copied != *size || std::fs::metadata(&dest).map(|m| m.len()).ok() != Some(*size) {
+            return Err(format!(
+                "Copy of {} is incomplete; the old data was left untouched",
+                relative.to_string_lossy()
+            ));
+        }
+        progress.files_copied += 1;
+        progress.bytes_copied += size;
+        let _ = app.emit("data-migration", progress.clone());
+    }
+
+    // An absolute download or export folder inside the old root would
+    // otherwise keep pointing at the old drive.
+    let settings_file = new_root.join("settings.json");
+    if settings_file.exists() {
+        let contents = std::fs::read_to_string(&settings_file).map_err(|e| e.to_string())?;
+        let mut settings: Settings = serde_json::from_str(&contents).map_err(|e| e.to_string())?;
+        for root in [
+            &mut settings.download_root,
+            &mut settings.export_root,
+            &mut settings.video_export_root,
+        ] {
+            let Some(path) = root.as_deref().map(PathBuf::from) else {
+                continue;
+            };
+            if !path.is_absolute() {
+                continue;
+            }
+            if let Ok(relative) = normalize_path(&path)?.strip_prefix(&old_norm) {
+                *root = Some(relative.to_string_lossy().to_string());
+            }
+        }
+        let contents = serde_json::to_string_pretty(&settings).map_err(|e| e.to_string())?;
+        atomic_write(&settings_file, contents)?;
+    }
+
+    let pointer = data_root_pointer()?;
+    if normalize_path(&new_root)? == normalize_path(&base_app_root()?)? {
+        let _ = std::fs::remove_file(&pointer);
+    } else {
+        std::fs::create_dir_all(base_app_root()?).map_err(|e| e.to_string())?;
+        atomic_write(&pointer, new_root.to_string_lossy().as_bytes())?;
+    }
+
+    // History, the queue and paused downloads hold absolute paths into the
+    // old root; the app root now resolves to the new one.
+    if history_path()?.exists() {
+        let mut history = load_history()?;
+        for entry in history.iter_mut() {
+            rebase_string(&mut entry.output_path, &old_root, &new_root);
+        }
+        save_history(&history)?;
+    }
+    {
+        let queue = app.state::<DownloadQueue>();
+        let mut state = queue.0.lock().map_err(|e| e.to_string())?;
+        for item in state.items.iter_mut() {
+            if let Some(path) = item.output_path.as_mut() {
+                rebase_string(path, &old_root, &new_root);
+            }
+        }
+        save_queue(&state.items)?;
+    }
+    if let Ok(mut paused) = app.state::<DownloadRegistry>().0.lock() {
+        for download in paused.values_mut() {
+            let request = &mut download.request;
+            for path in [&mut request.download_dir, &mut request.log_path]
+                .into_iter()
+                .chain(download.part_files.iter_mut())
+            {
+                if let Some(rebased) = rebase_path(path, &old_root, &new_root) {
+                    *path = rebased;
+                }
+            }
+        }
+    }
+
+    let remove_old = remove_old.unwrap_or(false);
+    if remove_old {
+        for path in &entries {
+            let _ = if path.is_dir() {
+                std::fs::remove_dir_all(path)
+            } else {
+                std::fs::remove_file(path)
+            };
+        }
+    }
+
+    Ok(DataMigration {
+        old_root: old_root.to_string_lossy().to_string(),
+        new_root: new_root.to_string_lossy().to_string(),
+        files_copied: progress.files_copied,
+        bytes_copied: progress.bytes_copied,
+        removed_old: remove_old,
+    })
+}
+
+#[tauri::command]
+fn get_binaries_dir(app: tauri::AppHandle) -> Result<String, String> {
+    let dir = binaries_dir(&app)?;
+    Ok(dir.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+fn get_export_root() -> Result<String, String> {
+    let root = resolve_export_root()?;
+    validate_writable_dir(&root)?;
+    Ok(root.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+fn set_export_root(path: String) -> Result<String, String> {
+    let mut settings = load_settings()?;
+    if path.trim().is_empty() {
+        settings.export_root = None;
+        save_settings(&settings)?;
+        return get_export_root();
+    }
+
+    let candidate = {
+        let raw = PathBuf::from(path.trim());
+        if raw.is_absolute() {
+            raw
+        } else {
+            app_root()?.join(raw)
+        }
+    };
+
+    validate_writable_dir(&candidate)?;
+    settings.export_root = Some(candidate.to_string_lossy().to_string());
+    save_settings(&settings)?;
+    get_export_root()
+}
+
+#[tauri::command]
+fn get_video_export_root() -> Result<String, String> {
+    let root = resolve_video_export_root()?;
+    validate_writable_dir(&root)?;
+    Ok(root.to_string_lossy().to_string())
+}
+
+// An empty path clears the setting so video exports follow export_root again.
+#[tauri::command]
+fn set_video_export_root(path: String) -> Result<String, String> {
+    let mut settings = load_settings()?;
+    if path.trim().is_empty() {
+        settings.video_export_root = None;
+        save_settings(&settings)?;
+        return get_video_export_root();
+    }
+
+    let candidate = {
+        let raw = PathBuf::from(path.trim());
+        if raw.is_absolute() {
+            raw
+        } else {
+            app_root()?.join(raw)
+        }
+    };
+
+    validate_writable_dir(&candidate)?;
+    settings.video_export_root = Some(candidate.to_string_lossy().to_string());
+    save_settings(&settings)?;
+    get_video_export_root()
+}
+
+#[tauri::command]
+fn prepare_download(date_folder: String, log_stamp: String) -> Result<DownloadPaths, String> {
+    validate_log_stamp(&log_stamp)?;
+
+    let root = resolve_download_root()?;
+    validate_writable_dir(&root)?;
+
+    let download_dir = scheme_dir(&root, &date_folder)?;
+    std::fs::create_dir_all(&download_dir).map_err(|e| e.to_string())?;
+
+    let logs = logs_root()?;
+    std::fs::create_dir_all(&logs).map_err(|e| e.to_string())?;
+    let log_path = logs.join(format!("download_{}.log", log_stamp));
+
+    Ok(DownloadPaths {
+        download_root: root.to_string_lossy().to_string(),
+        download_dir: download_dir.to_string_lossy().to_string(),
+        log_path: log_path.to_string_lossy().to_string(),
+    })
+}
+
+// Patterns yt-dlp prints for failures that usually succeed on a second try.
+const TRANSIENT_DOWNLOAD_ERRORS: [&str; 9] = [
+    "HTTP Error 403",
+    "HTTP Error 429",
+    "HTTP Error 5",
+    "fragment",
+    "Connection reset",
+    "timed out",
+    "Temporary failure in name resolution",
+    "IncompleteRead",
+    "Unable to download webpage",
+];
+
+const FATAL_DOWNLOAD_ERRORS: [&str; 5] = [
+    "Video unavailable",
+    "Private video",
+    "This video is not available",
+    "Sign in to confirm your age",
+    "members-only",
+];
+
+const MAX_DOWNLOAD_BACKOFF_SECS: u64 = 30;
+
+fn is_transient_download_error(output: &str) -> bool {
+    if FATAL_DOWNLOAD_ERRORS.iter().any(|p| output.contains(p)) {
+        return false;
+    }
+    TRANSIENT_DOWNLOAD_ERRORS.iter().any(|p| output.contains(p))
+}
+
+fn download_backoff_secs(attempt: u32) -> u64 {
+    (2u64 << attempt.saturating_sub(1).min(8)).min(MAX_DOWNLOAD_BACKOFF_SECS)
+}
+
+fn validate_download_url(url: &str) -> Result<(), String> {
+    let url = url.trim();
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .ok_or("URL must start with http:// or https://")?;
+    let host = rest.split(['/', '?', '#']).next().unwrap_or("");
+    if host.is_empty() || url.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err("Invalid URL".into());
+    }
+    Ok(())
+}
+
+const PROXY_SCHEMES: [&str; 6] = ["http", "https", "socks4", "socks4a", "socks5", "socks5h"];
+
+fn validate_proxy_url(url: &str) -> Result<(), String> {
+    let (scheme, rest) = url
+        .split_once("://")
+        .ok_or("Proxy URL must look like scheme://host:port")?;
+    if !PROXY_SCHEMES.contains(&scheme.to_ascii_lowercase().as_str()) {
+        return Err(format!(
+            "Unsupported proxy scheme '{scheme}'. Use one of: {}",
+            PROXY_SCHEMES.join(", ")
+        ));
+    }
+    let authority = rest.trim_end_matches('/');
+    let host_port = authority.rsplit('@').next().unwrap_or("");
+    let (host, port) = host_port
+        .rsplit_once(':')
+        .ok_or("Proxy URL must include a port")?;
+    if host.is_empty()
+        || authority.contains('/')
+        || url.chars().any(|c| c.is_whitespace() || c.is_control())
+    {
+        return Err("Invalid proxy host".into());
+    }
+    port.parse::<u16>()
+        .map_err(|_| "Invalid proxy port".to_string())?;
+    Ok(())
+}
+
+fn redact_proxy_url(url: &str) -> String {
+    match url.split_once("://") {
+        Some((scheme, rest)) => {
+            let host_port = rest.rsplit('@').next().unwrap_or("");
+            match host_port.rsplit_once(':') {
+                Some((_, port)) => format!("{scheme}://<redacted>:{port}"),
+                None => format!("{scheme}://<redacted>"),
+            }
+        }
+        None => "<redacted>".into(),
+    }
+}
+
+fn validate_rate_limit(rate: &str) -> Result<(), String> {
+    let digits = rate.trim_end_matches(['K', 'k', 'M', 'm', 'G', 'g']);
+    let suffix_len = rate.len() - digits.len();
+    let valid = suffix_len <= 1
+        && !digits.is_empty()
+        && digits.parse::<f64>().map(|v| v > 0.0).unwrap_or(false);
+    if !valid {
+        return Err("Rate limit must look like 500K, 2M, or a byte count".into());
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn get_network_settings() -> Result<NetworkSettings, String> {
+    let settings = load_settings()?;
+    Ok(NetworkSettings {
+        proxy_url: settings.proxy_url,
+        rate_limit: settings.rate_limit,
+    })
+}
+
+const DEFAULT_VIDEO_BITS_PER_PIXEL: f64 = 0.01;
+
+#[tauri::command]
+fn get_effective_config() -> Result<EffectiveConfig, String> {
+    let settings = load_settings()?;
+    let folder_scheme = folder_scheme();
+    let organize_by = organize_by();
+    let process_priority = process_priority();
+    let ffmpeg_log_level = settings
+        .ffmpeg_log_level
+        .clone()
+        .filter(|level| FFMPEG_LOG_LEVELS.contains(&level.as_str()));
+    let ffmpeg_threads = settings.ffmpeg_threads.filter(|t| *t > 0);
+    let quality = settings.quality.filter(|q| *q <= 100);
+    let download_container = download_container();
+    let user_set = [
+        ("download_root", settings.download_root.is_some()),
+        ("export_root", settings.export_root.is_some()),
+        ("video_export_root", settings.video_export_root.is_some()),
+        ("filename_template", settings.filename_template.is_some()),
+        ("proxy_url", settings.proxy_url.is_some()),
+        ("rate_limit", settings.rate_limit.is_some()),
+        ("session_log_max_kb", settings.session_log_max_kb.is_some()),
+        ("quality", quality.is_some()),
+        ("output_root_allowlist", settings.output_root_allowlist.is_some()),
+        ("export_presets", settings.export_presets.is_some()),
+        ("folder_scheme", settings.folder_scheme.as_ref() == Some(&folder_scheme)),
+        ("organize_by", settings.organize_by.as_ref() == Some(&organize_by)),
+        ("video_bits_per_pixel", settings.video_bits_per_pixel.is_some()),
+        ("ffmpeg_log_level", ffmpeg_log_level.is_some()),
+        ("ffmpeg_threads", ffmpeg_threads.is_some()),
+        (
+            "ffmpeg_timeout_factor",
+            settings
+                .ffmpeg_timeout_factor
+                .is_some_and(|f| validate_timeout_factor(f).is_ok()),
+        ),
+        ("use_utc", settings.use_utc.is_some()),
+        (
+            "max_input_mb",
+            settings
+                .max_input_mb
+                .is_some_and(|limit| validate_max_input_mb(limit).is_ok()),
+        ),
+        (
+            "max_parallel_jobs",
+            settings
+                .max_parallel_jobs
+                .is_some_and(|jobs| validate_max_parallel_jobs(jobs).is_ok()),
+        ),
+        ("download_container", download_container.is_some()),
+        ("process_priority", settings.process_priority.as_ref() == Some(&process_priority)),
+        ("post_export_command", settings.post_export_command.is_some()),
+    ];
+    let defaults = user_set
+        .iter()
+        .filter(|(_, set)| !set)
+        .map(|(key, _)| key.to_string())
+        .collect();
+    Ok(EffectiveConfig {
+        download_root: resolve_download_root()?.to_string_lossy().to_string(),
+        export_root: resolve_export_root()?.to_string_lossy().to_string(),
+        video_export_root: resolve_video_export_root()?.to_string_lossy().to_string(),
+        filename_template: settings.filename_template,
+        proxy_url: settings.proxy_url,
+        rate_limit: settings.rate_limit,
+        session_log_max_kb: session_log_max_kb(),
+        quality,
+        output_root_allowlist: settings.output_root_allowlist.unwrap_or_default(),
+        export_preset_count: settings.export_presets.map(|p| p.len()).unwrap_or(0),
+        folder_scheme,
+        organize_by,
+        video_bits_per_pixel: settings
+            .video_bits_per_pixel
+            .unwrap_or(DEFAULT_VIDEO_BITS_PER_PIXEL),
+        ffmpeg_log_level,
+        ffmpeg_threads: ffmpeg_threads.map(|t| t.min(available_threads())),
+        ffmpeg_timeout_factor: ffmpeg_timeout_factor(),
+        use_utc: use_utc(),
+        max_input_mb: max_input_mb(),
+        max_parallel_jobs: max_parallel_jobs() as u32,
+        download_container,
+        process_priority,
+        post_export_command: settings.post_export_command,
+        defaults,
+    })
+}
+
+#[tauri::command]
+fn validate_settings() -> Result<SettingsValidation, String> {
+    let mut settings = load_settings()?;
+    let mut report = SettingsValidation {
+        reset: Vec::new(),
+        warnings: Vec::new(),
+    };
+
+    if settings.download_root.is_some() {
+        let root = resolve_download_root()?;
+        if let Err(err) = validate_writable_dir(&root) {
+            settings.download_root = None;
+            report.reset.push("download_root".into());
+            report.warnings.push(format!(
+                "Download folder {} is not writable ({err}); using the default",
+                root.to_string_lossy()
+            ));
+        }
+    }
+    if settings.export_root.is_some() {
+        let root = resolve_export_root()?;
+        if let Err(err) = validate_writable_dir(&root) {
+            settings.export_root = None;
+            report.reset.push("export_root".into());
+            report.warnings.push(format!(
+                "Export folder {} is not writable ({err}); using the default",
+                root.to_string_lossy()
+            ));
+        }
+    }
+    if settings.video_export_root.is_some() {
+        let root = resolve_video_export_root()?;
+        if let Err(err) = validate_writable_dir(&root) {
+            settings.video_export_root = None;
+            report.reset.push("video_export_root".into());
+            report.warnings.push(format!(
+                "Video export folder {} is not writable ({err}); using the export folder",
+                root.to_string_lossy()
+            ));
+        }
+    }
+    if let Err(err) = validate_ffmpeg_settings(
+        settings.ffmpeg_log_level.as_deref(),
+        settings.ffmpeg_threads,
+    ) {
+        settings.ffmpeg_log_level = None;
+        settings.ffmpeg_threads = None;
+        report.reset.push("ffmpeg".into());
+        report.warnings.push(format!("{err}; using ffmpeg defaults"));
+    }
+    if let Some(Err(err)) = settings.ffmpeg_timeout_factor.map(validate_timeout_factor) {
+        settings.ffmpeg_timeout_factor = None;
+        report.reset.push("ffmpeg_timeout_factor".into());
+        report.warnings.push(format!("{err}; using the default"));
+    }
+    if let Some(Err(err)) = settings.max_parallel_jobs.map(validate_max_parallel_jobs) {
+        settings.max_parallel_jobs = None;
+        report.reset.push("max_parallel_jobs".into());
+        report.warnings.push(format!("{err}; using the default"));
+    }
+    if let Some(Err(err)) = settings.max_input_mb.map(validate_max_input_mb) {
+        settings.max_input_mb = None;
+        report.reset.push("max_input_mb".into());
+        report.warnings.push(format!("{err}; using the default"));
+    }
+    let priority_valid = settings
+        .process_priority
+        .as_deref()
+        .is_none_or(|p| PROCESS_PRIORITIES.contains(&p));
+    if !priority_valid {
+        settings.process_priority = None;
+        report.reset.push("process_priority".into());
+        report.warnings.push(format!(
+            "Unknown process priority; using {DEFAULT_PROCESS_PRIORITY}"
+        ));
+    }
+    let hook_check = settings
+        .post_export_command
+        .as_deref()
+        .map(validate_post_export_command);
+    if let Some(Err(err)) = hook_check {
+        settings.post_export_command = None;
+        report.reset.push("post_export_command".into());
+        report.warnings.push(format!("{err}; the post-export hook is disabled"));
+    }
+    let organize_valid = settings
+        .organize_by
+        .as_deref()
+        .is_none_or(|mode| ORGANIZE_MODES.contains(&mode));
+    if !organize_valid {
+        settings.organize_by = None;
+        report.reset.push("organize_by".into());
+        report.warnings.push("Unknown organize mode; using date folders".into());
+    }
+    let container_check = settings
+        .download_container
+        .as_deref()
+        .map(validate_download_container);
+    if let Some(Err(err)) = container_check {
+        settings.download_container = None;
+        report.reset.push("download_container".into());
+        report.warnings.push(format!("{err}; downloads are converted to m4a"));
+    }
+    // The temp folder isn't configurable, so it can only be reported.
+    let tmp = tmp_root()?;
+    if let Err(err) = validate_writable_dir(&tmp) {
+        report.warnings.push(format!(
+            "Temp folder {} is not writable ({err})",
+            tmp.to_string_lossy()
+        ));
+    }
+
+    if !report.reset.is_empty() {
+        save_settings(&settings)?;
+    }
+    Ok(report)
+}
+
+#[tauri::command]
+fn get_session_log_limit() -> Result<u64, String> {
+    Ok(session_log_max_kb())
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn set_session_log_limit(max_kb: Option<u64>) -> Result<u64, String> {
+    if let Some(kb) = max_kb {
+        if kb < MIN_SESSION_LOG_MAX_KB {
+            return Err(format!(
+                "Session log limit must be at least {MIN_SESSION_LOG_MAX_KB} KB"
+            ));
+        }
+    }
+    let mut settings = load_settings()?;
+    settings.session_log_max_kb = max_kb;
+    save_settings(&settings)?;
+    Ok(session_log_max_kb())
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn set_network_settings(
+    proxy_url: Option<String>,
+    rate_limit: Option<String>,
+) -> Result<NetworkSettings, String> {
+    let mut settings = load_settings()?;
+
+    let proxy_url = proxy_url
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty());
+    if let Some(proxy) = &proxy_url {
+        validate_proxy_url(proxy)?;
+    }
+    let rate_limit = rate_limit
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty());
+    if let Some(rate) = &rate_limit {
+        validate_rate_limit(rate)?;
+    }
+
+    settings.proxy_url = proxy_url;
+    settings.rate_limit = rate_limit;
+    save_settings(&settings)?;
+    get_network_settings()
+}
+
+fn last_error_line(text: &str) -> String {
+    text.lines()
+        .rev()
+        .find(|line| line.contains("ERROR"))
+        .or_else(|| text.lines().rev().find(|line| !line.trim().is_empty()))
+        .unwrap_or("unknown error")
+        .trim()
+        .to_string()
+}
+
+fn tail_lines_of(text: &str, max_lines: usize) -> String {
+    let lines: Vec<&str> = text.lines().rev().take(max_lines).collect();
+    lines.into_iter().rev().collect::<Vec<&str>>().join("\n")
+}
+
+// Percent complete from a progress line: yt-dlp prints it directly, ffmpeg
+// prints the position ("time=00:01:02.50") which needs the input duration.
+fn parse_progress(line: &str, duration: Option<f64>) -> Option<f64> {
+    if let Some(rest) = line.trim_start().strip_prefix("[download]") {
+        let percent = rest.trim_start().split('%').next()?;
+        return percent.trim().parse::<f64>().ok();
+    }
+    let duration = duration?;
+    let time = line.split("time=").nth(1)?.split_whitespace().next()?;
+    let mut secs = 0.0;
+    for part in time.split(':') {
+        secs = secs * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some((secs / duration * 100.0).clamp(0.0, 100.0))
+}
+
+const PROCESS_PRIORITIES: [&str; 3] = ["normal", "below-normal", "idle"];
+// Long jobs run below normal unless told otherwise so the UI stays responsive.
+const DEFAULT_PROCESS_PRIORITY: &str = "below-normal";
+
+fn process_priority() -> String {
+    load_settings()
+        .ok()
+        .and_then(|s| s.process_priority)
+        .filter(|p| PROCESS_PRIORITIES.contains(&p.as_str()))
+        .unwrap_or_else(|| DEFAULT_PROCESS_PRIORITY.into())
+}
+
+// Windows takes the priority class as a creation flag; on Unix the child is
+// reniced right after it starts, since std has no way to set it up front.
+fn spawn_with_priority(command: &mut Command) -> std::io::Result<Child> {
+    let priority = process_priority();
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x0000_4000;
+        const IDLE_PRIORITY_CLASS: u32 = 0x0000_0040;
+        match priority.as_str() {
+            "below-normal" => {
+                command.creation_flags(BELOW_NORMAL_PRIORITY_CLASS);
+            }
+            "idle" => {
+                command.creation_flags(IDLE_PRIORITY_CLASS);
+            }
+            _ => {}
+        }
+    }
+    let child = command.spawn()?;
+    #[cfg(unix)]
+    {
+        let niceness = match priority.as_str() {
+            "below-normal" => Some("10"),
+            "idle" => Some("19"),
+            _ => None,
+        };
+        if let Some(niceness) = niceness {
+            let _ = Command::new("renice")
+                .args(["-n", niceness, "-p", &child.id().to_string()])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status();
+        }
+    }
+    Ok(child)
+}
+
+// yt-dlp runs ffmpeg as its own child for merging and conversion; killing
+// only yt-dlp would leave that ffmpeg writing into the download folder. The
+// children go first, while they are still findable through their parent.
+fn kill_process_tree(child: &mut Child) -> std::io::Result<()> {
+    let pid = child.id().to_string();
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        let _ = Command::new("taskkill")
+            .args(["/T", "/F", "/PID", &pid])
+            .creation_flags(CREATE_NO_WINDOW)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+    #[cfg(unix)]
+    {
+        let _ = Command::new("pkill")
+            .args(["-KILL", "-P", &pid])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+    child.kill()
+}
+
+#[tauri::command]
+fn get_process_priority() -> String {
+    process_priority()
+}
+
+#[tauri::command]
+fn set_process_priority(priority: String) -> Result<String, String> {
+    let priority = priority.trim().to_ascii_lowercase();
+    if !PROCESS_PRIORITIES.contains(&priority.as_str()) {
+        return Err(format!(
+            "Process priority must be one of: {}",
+            PROCESS_PRIORITIES.join(", ")
+        ));
+    }
+    let mut settings = load_settings()?;
+    settings.process_priority = Some(priority);
+    save_settings(&settings)?;
+    Ok(process_priority())
+}
+
+// Speed samples closer together than this are too noisy to average in.
+const PROGRESS_SAMPLE_INTERVAL: f64 = 0.5;
+// Weight of the newest sample in the speed average; low so a slow start or a
+// burst doesn't swing the ETA.
+const PROGRESS_SPEED_WEIGHT: f64 = 0.2;
+const PROGRESS_MIN_SAMPLES: u32 = 3;
+
+fn record_progress(state: &mut ProgressState, percent: f64) {
+    let now = std::time::Instant::now();
+    state.percent = Some(percent);
+    let Some((at, previous)) = state.last_sample else {
+        state.last_sample = Some((now, percent));
+        return;
+    };
+    let elapsed = now.duration_since(at).as_secs_f64();
+    if elapsed < PROGRESS_SAMPLE_INTERVAL {
+        return;
+    }
+    let speed = (percent - previous).max(0.0) / elapsed;
+    state.speed = Some(match state.speed {
+        Some(average) => average + PROGRESS_SPEED_WEIGHT * (speed - average),
+        None => speed,
+    });
+    state.samples += 1;
+    state.last_sample = Some((now, percent));
+}
+
+fn progress_eta(state: &ProgressState) -> Option<f64> {
+    if state.samples < PROGRESS_MIN_SAMPLES {
+        return None;
+    }
+    let speed = state.speed.filter(|s| *s > 0.0)?;
+    Some((100.0 - state.percent?).max(0.0) / speed)
+}
+
+const DEFAULT_MAX_PARALLEL_JOBS: u32 = 2;
+
+fn validate_max_parallel_jobs(jobs: u32) -> Result<(), String> {
+    if !(1..=16).contains(&jobs) {
+        return Err("Parallel job limit must be between 1 and 16".into());
+    }
+    Ok(())
+}
+
+fn max_parallel_jobs() -> usize {
+    load_settings()
+        .ok()
+        .and_then(|s| s.max_parallel_jobs)
+        .filter(|jobs| validate_max_parallel_jobs(*jobs).is_ok())
+        .unwrap_or(DEFAULT_MAX_PARALLEL_JOBS) as usize
+}
+
+#[tauri::command]
+fn get_max_parallel_jobs() -> usize {
+    max_parallel_jobs()
+}
+
+#[tauri::command]
+fn set_max_parallel_jobs(app: tauri::AppHandle, jobs: u32) -> Result<usize, String> {
+    validate_max_parallel_jobs(jobs)?;
+    let mut settings = load_settings()?;
+    settings.max_parallel_jobs = Some(jobs);
+    save_settings(&settings)?;
+    // A raised limit lets waiting jobs start right away.
+    app.state::<JobSlots>().freed.notify_all();
+    Ok(max_parallel_jobs())
+}
+
+// Runs `work` once one of the shared slots is free. The slot is handed back
+// however `work` ends; a cancel_all while waiting returns without running it.
+fn with_job_slot<T>(
+    app: &tauri::AppHandle,
+    work: impl FnOnce() -> Result<T, String>,
+) -> Result<T, String> {
+    let slots = app.state::<JobSlots>();
+    {
+        let mut state = slots.state.lock().map_err(|e| e.to_string())?;
+        let generation = state.generation;
+        state.waiting += 1;
+        while state.running >= max_parallel_jobs() && state.generation == generation {
+            state = slots.freed.wait(state).map_err(|e| e.to_string())?;
+        }
+        state.waiting -= 1;
+        if state.generation != generation {
+            return Err("Operation cancelled".into());
+        }
+        state.running += 1;
+    }
+    let result = work();
+    if let Ok(mut state) = slots.state.lock() {
+        state.running = state.running.saturating_sub(1);
+    }
+    slots.freed.notify_all();
+    result
+}
+
+// Turns away every job still waiting for a slot and reports how many there
+// were; running jobs give their slots back as they are killed.
+fn release_waiting_jobs(app: &tauri::AppHandle) -> usize {
+    let slots = app.state::<JobSlots>();
+    let Ok(mut state) = slots.state.lock() else {
+        return 0;
+    };
+    state.generation += 1;
+    let waiting = state.waiting;
+    drop(state);
+    slots.freed.notify_all();
+    waiting
+}
+
+// Exports and downloads take a shared slot first; probes and hooks don't.
+// Downloads and exports the user started, as opposed to helper processes
+// such as post-export hooks and encoder probes.
+fn is_user_operation(id: &str) -> bool {
+    id.starts_with("export:") || id.starts_with("download:")
+}
+
+fn run_tracked(
+    app: &tauri::AppHandle,
+    id: &str,
+    command: &mut Command,
+    timeout: Option<std::time::Duration>,
+    expected_duration: Option<f64>,
+) -> Result<TrackedOutput, String> {
+    if is_user_operation(id) {
+        with_job_slot(app, || run_tracked_now(app, id, command, timeout, expected_duration))
+    } else {
+        run_tracked_now(app, id, command, timeout, expected_duration)
+    }
+}
+
+// A process still running at the deadline is killed and reported as timed out.
+fn run_tracked_now(
+    app: &tauri::AppHandle,
+    id: &str,
+    command: &mut Command,
+    timeout: Option<std::time::Duration>,
+    expected_duration: Option<f64>,
+) -> Result<TrackedOutput, String> {
+    let args: Vec<String> = command
+        .get_args()
+        .map(|arg| arg.to_string_lossy().to_string())
+        .collect();
+    let input = args
+        .windows(2)
+        .find(|pair| pair[0] == "-i" && Path::new(&pair[1]).is_file())
+        .map(|pair| pair[1].clone())
+        .or_else(|| args.last().cloned());
+    let mut child = spawn_with_priority(command.stdout(Stdio::piped()).stderr(Stdio::piped()))
+        .map_err(|e| e.to_string())?;
+
+    // Drain both pipes on their own threads so a chatty child can't block on
+    // a full pipe while we poll for its exit. Progress lines end in \r as
+    // well as \n, so each chunk is scanned for them as it arrives.
+    let progress = Arc::new(Mutex::new(ProgressState::default()));
+    // Exports report each update as a video-export-progress event.
+    let listener = id
+        .strip_prefix("export:")
+        .map(|session_id| (app.clone(), session_id.to_string()));
+    fn read_all<R: std::io::Read + Send + 'static>(
+        pipe: Option<R>,
+        progress: Arc<Mutex<ProgressState>>,
+        duration: Option<f64>,
+        listener: Option<(tauri::AppHandle, String)>,
+    ) -> std::thread::JoinHandle<String> {
+        std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            let mut chunk = [0u8; 8192];
+            let mut line_start = 0;
+            if let Some(mut pipe) = pipe {
+                while let Ok(read) = pipe.read(&mut chunk) {
+                    if read == 0 {
+                        break;
+                    }
+                    buf.extend_from_slice(&chunk[..read]);
+                    while let Some(end) = buf[line_start..]
+                        .iter()
+                        .position(|b| *b == b'\n' || *b == b'\r')
+                    {
+                        let line = String::from_utf8_lossy(&buf[line_start..line_start + end]);
+                        if let Some(percent) = parse_progress(&line, duration) {
+                            let eta_secs = progress.lock().ok().and_then(|mut state| {
+                                record_progress(&mut state, percent);
+                                progress_eta(&state)
+                            });
+                            if let Some((app, session_id)) = &listener {
+                                let _ = app.emit(
+                                    "video-export-progress",
+                                    ExportProgress {
+                                        session_id: session_id.clone(),
+                                        percent,
+                                        eta_secs,
+                                    },
+                                );
+                            }
+                        }
+                        line_start += end + 1;
+                    }
+                }
+            }
+            String::from_utf8_lossy(&buf).to_string()
+        })
+    }
+    let stdout_reader = read_all(
+        child.stdout.take(),
+        progress.clone(),
+        expected_duration,
+        listener.clone(),
+    );
+    let stderr_reader = read_all(
+        child.stderr.take(),
+        progress.clone(),
+        expected_duration,
+        listener,
+    );
+
+    let child = Arc::new(Mutex::new(child));
+    let registry = app.state::<ProcessRegistry>();
+    registry.0.lock().map_err(|e| e.to_string())?.insert(
+        id.to_string(),
+        RunningProcess {
+            child: child.clone(),
+            stopped: false,
+            input,
+            started_at: app_now().to_rfc3339(),
+            progress,
+        },
+    );
+    if is_user_operation(id) {
+        if let Ok(mut last) = app.state::<LastOperation>().0.lock() {
+            *last = Some(id.to_string());
+        }
+    }
+
+    let deadline = timeout.map(|timeout| std::time::Instant::now() + timeout);
+    let mut timed_out = false;
+    let status = loop {
+        let mut guard = child.lock().map_err(|e| e.to_string())?;
+        match guard.try_wait() {
+            Ok(Some(status)) => break Ok(status),
+            Ok(None) if !timed_out && deadline.is_some_and(|d| std::time::Instant::now() >= d) => {
+                timed_out = true;
+                let _ = kill_process_tree(&mut guard);
+            }
+            Ok(None) => {
+                drop(guard);
+                std::thread::sleep(std::time::Duration::from_millis(100));
+            }
+            Err(err) => break Err(err.to_string()),
+        }
+    };
+    let stopped = registry
+        .0
+        .lock()
+        .map_err(|e| e.to_string())?
+        .remove(id)
+        .map(|entry| entry.stopped)
+        .unwrap_or(false);
+    if let Ok(mut last) = app.state::<LastOperation>().0.lock() {
+        if last.as_deref() == Some(id) {
+            *last = None;
+        }
+    }
+
+    Ok(TrackedOutput {
+        status: status?,
+        stdout: stdout_reader.join().unwrap_or_default(),
+        stderr: stderr_reader.join().unwrap_or_default(),
+        stopped,
+        timed_out,
+    })
+}
+
+fn stop_all_tracked(app: &tauri::AppHandle) -> usize {
+    let registry = app.state::<ProcessRegistry>();
+    let Ok(mut running) = registry.0.lock() else {
+        return 0;
+    };
+    let mut stopped = 0;
+    for entry in running.values_mut().filter(|entry| !entry.stopped) {
+        entry.stopped = true;
+        if let Ok(mut child) = entry.child.lock() {
+            if kill_process_tree(&mut child).is_ok() {
+                stopped += 1;
+            }
+        }
+    }
+    stopped
+}
+
+// Stops every running download and export, turns away jobs still waiting
+// for a slot and holds back queued downloads so the worker doesn't start the
+// next one. Safe to call when idle.
+#[tauri::command]
+fn cancel_all(app: tauri::AppHandle) -> Result<usize, String> {
+    {
+        let queue = app.state::<DownloadQueue>();
+        let mut state = queue.0.lock().map_err(|e| e.to_string())?;
+        let mut changed = Vec::new();
+        for item in state
+            .items
+            .iter_mut()
+            .filter(|item| item.state == "queued" || item.state == "interrupted")
+        {
+            item.state = "cancelled".into();
+            changed.push(item.clone());
+        }
+        if !changed.is_empty() {
+            save_queue(&state.items)?;
+        }
+        for item in changed {
+            let _ = app.emit("download-queue", item);
+        }
+    }
+    let released = release_waiting_jobs(&app);
+    Ok(stop_all_tracked(&app) + released)
+}
+
+// Downloads and exports share the registry, and their ids come from the
+// same stamp format, so keys are namespaced by kind.
+fn download_process_id(id: &str) -> String {
+    format!("download:{id}")
+}
+
+fn export_process_id(session_id: &str) -> String {
+    format!("export:{session_id}")
+}
+
+#[tauri::command]
+fn get_active_operations(app: tauri::AppHandle) -> Result<Vec<ActiveOperation>, String> {
+    let registry = app.state::<ProcessRegistry>();
+    let running = registry.0.lock().map_err(|e| e.to_string())?;
+    let mut operations: Vec<ActiveOperation> = running
+        .iter()
+        .filter(|(_, entry)| !entry.stopped)
+        .map(|(key, entry)| {
+            let (kind, id) = key.split_once(':').unwrap_or(("other", key));
+            let state = entry.progress.lock().ok();
+            ActiveOperation {
+                id: id.to_string(),
+                kind: kind.to_string(),
+                input: entry.input.clone(),
+                started_at: entry.started_at.clone(),
+                progress: state.as_ref().and_then(|s| s.percent),
+                eta_secs: state.as_ref().and_then(|s| progress_eta(s)),
+            }
+        })
+        .collect();
+    operations.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+    Ok(operations)
+}
+
+// None when the export isn't running or hasn't reported any progress yet.
+#[tauri::command(rename_all = "camelCase")]
+fn get_export_eta(
+    app: tauri::AppHandle,
+    session_id: String,
+) -> Result<Option<ExportProgress>, String> {
+    let registry = app.state::<ProcessRegistry>();
+    let running = registry.0.lock().map_err(|e| e.to_string())?;
+    let Some(entry) = running.get(&export_process_id(&session_id)) else {
+        return Ok(None);
+    };
+    let state = entry.progress.lock().map_err(|e| e.to_string())?;
+    Ok(state.percent.map(|percent| ExportProgress {
+        session_id: session_id.clone(),
+        percent,
+        eta_secs: progress_eta(&state),
+    }))
+}
+
+// Returns the id that was stopped; downloads stop the same way pause_download
+// does, keeping the partial file.
+#[tauri::command]
+fn cancel_last(app: tauri::AppHandle) -> Result<Option<String>, String> {
+    let last = app
+        .state::<LastOperation>()
+        .0
+        .lock()
+        .map_err(|e| e.to_string())?
+        .take();
+    let Some(id) = last else {
+        return Ok(None);
+    };
+    Ok(stop_tracked(&app, &id)?.then_some(id))
+}
+
+fn stop_tracked(app: &tauri::AppHandle, id: &str) -> Result<bool, String> {
+    let registry = app.state::<ProcessRegistry>();
+    let mut running = registry.0.lock().map_err(|e| e.to_string())?;
+    let Some(entry) = running.get_mut(id) else {
+        return Ok(false);
+    };
+    entry.stopped = true;
+    let mut child = entry.child.lock().map_err(|e| e.to_string())?;
+    kill_process_tree(&mut child).map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+// yt-dlp's .part downloads plus the .ytdl state files it keeps beside them.
+fn download_partials(dir: &Path) -> Vec<PathBuf> {
+    let mut files = part_files(dir);
+    files.extend(
+        std::fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("ytdl")),
+    );
+    files
+}
+
+fn part_files(dir: &Path) -> Vec<PathBuf> {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().and_then(|e| e.to_str()) == Some("part"))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[tauri::command(async, rename_all = "camelCase")]
+fn download_audio(
+    app: tauri::AppHandle,
+    url: String,
+    date_folder: String,
+    log_stamp: String,
+    playlist: Option<bool>,
+    max_attempts: Option<u32>,
+    format_id: Option<String>,
+) -> Result<String, String> {
+    validate_download_url(&url)?;
+    require_tools(&app, &["yt-dlp", "ffmpeg"])?;
+    let playlist = playlist.unwrap_or(false);
+    let format_id = format_id
+        .map(|f| f.trim().to_string())
+        .filter(|f| !f.is_empty());
+    if let Some(format_id) = &format_id {
+        if !format_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err("Invalid format id".into());
+        }
+        // Playlist entries each advertise their own formats, so only a single
+        // video can be checked up front.
+        if !playlist {
+            let media = fetch_remote_media(&app, &url)?;
+            if !media.audio_formats.iter().any(|f| &f.format_id == format_id) {
+                return Err(format!("Format '{format_id}' is not offered for this video"));
+            }
+        }
+    }
+    let paths = prepare_download(date_folder, log_stamp.clone())?;
+    let request = DownloadRequest {
+        url: url.trim().to_string(),
+        download_dir: PathBuf::from(&paths.download_dir),
+        log_path: PathBuf::from(&paths.log_path),
+        playlist,
+        max_attempts: max_attempts.unwrap_or(3).clamp(1, 10),
+        format_id,
+    };
+    run_download(&app, &log_stamp, request, false)
+}
+
+fn parse_remote_media(json: &serde_json::Value) -> RemoteMedia {
+    let text = |value: &serde_json::Value, key: &str| {
+        value.get(key).and_then(|v| v.as_str()).map(|s| s.to_string())
+    };
+    let audio_formats = json
+        .get("formats")
+        .and_then(|f| f.as_array())
+        .map(|formats| {
+            formats
+                .iter()
+                .filter(|f| {
+                    f.get("acodec").and_then(|c| c.as_str()).is_some_and(|c| c != "none")
+                        && f.get("vcodec").and_then(|c| c.as_str()) == Some("none")
+                })
+                .map(|f| RemoteAudioFormat {
+                    format_id: text(f, "format_id").unwrap_or_default(),
+                    ext: text(f, "ext"),
+                    codec: text(f, "acodec"),
+                    bitrate_kbps: f.get("abr").and_then(|v| v.as_f64()),
+                    sample_rate: f.get("asr").and_then(|v| v.as_u64()),
+                    filesize: f
+                        .get("filesize")
+                        .or_else(|| f.get("filesize_approx"))
+                        .and_then(|v| v.as_u64()),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    RemoteMedia {
+        title: text(json, "title"),
+        uploader: text(json, "uploader"),
+        duration: json.get("duration").and_then(|v| v.as_f64()),
+        audio_formats,
+    }
+}
+
+#[tauri::command(async)]
+fn probe_url(app: tauri::AppHandle, url: String) -> Result<RemoteMedia, String> {
+    fetch_remote_media(&app, &url)
+}
+
+// Piped yt-dlp output on Windows uses the console code page, which turns CJK
+// and emoji titles in the printed file path into '?'; UTF-8 is forced so the
+// reported path matches the file on disk.
+fn yt_dlp_command(yt_dlp: &Path) -> Command {
+    let mut command = Command::new(yt_dlp);
+    command
+        .env("PYTHONIOENCODING", "utf-8")
+        .env("PYTHONUTF8", "1")
+        .args(["--encoding", "utf-8"]);
+    command
+}
+
+fn fetch_remote_media(app: &tauri::AppHandle, url: &str) -> Result<RemoteMedia, String> {
+    validate_download_url(url)?;
+    let yt_dlp = tool_path(app, "yt-dlp")?;
+    let mut args: Vec<String> = vec![
+        "--dump-json".into(),
+        "--no-download".into(),
+        "--no-playlist".into(),
+    ];
+    if let Some(proxy) = load_settings()?.proxy_url {
+        validate_proxy_url(&proxy)?;
+        args.push("--proxy".into());
+        args.push(proxy);
+    }
+    args.push("--".into());
+    args.push(url.trim().to_string());
+
+    let output = yt_dlp_command(&yt_dlp)
+        .args(&args)
+        .output()
+        .map_err(|e| e.to_string())?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        if stderr.contains("Sign in to confirm your age") {
+            return Err("This video is age-restricted and needs a signed-in account".into());
+        }
+        if let Some(reason) = FATAL_DOWNLOAD_ERRORS.iter().find(|e| stderr.contains(*e)) {
+            return Err(format!("Video can't be downloaded: {reason}"));
+        }
+        return Err(format!("Probe failed: {}", last_error_line(&stderr)));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stdout.lines().find(|l| !l.trim().is_empty()).unwrap_or("");
+    let json: serde_json::Value = serde_json::from_str(line).map_err(|e| e.to_string())?;
+    Ok(parse_remote_media(&json))
+}
+
+// The log stamp passed to download_audio doubles as the download id.
+#[tauri::command(rename_all = "camelCase")]
+fn pause_download(app: tauri::AppHandle, download_id: String) -> Result<bool, String> {
+    stop_tracked(&app, &download_process_id(&download_id))
+}
+
+#[tauri::command(async, rename_all = "camelCase")]
+fn resume_download(
+    app: tauri::AppHandle,
+    download_id: String,
+    url: String,
+) -> Result<String, String> {
+    let downloads = app.state::<DownloadRegistry>();
+    let paused = {
+        let mut paused = downloads.0.lock().map_err(|e| e.to_string())?;
+        let matches = paused
+            .get(&download_id)
+            .map(|paused| paused.request.url == url.trim());
+        if matches == Some(false) {
+            return Err("URL does not match the paused download".into());
+        }
+        paused
+            .remove(&download_id)
+            .ok_or("No paused download with that id")?
+    };
+    if !paused.part_files.iter().any(|path| path.is_file()) {
+        let _ = append_log_line(
+            &paused.request.log_path,
+            "[resume] partial file is gone, starting over",
+        );
+    }
+    run_download(&app, &download_id, paused.request, true)
+}
+
+// yt-dlp occasionally exits 0 after writing a truncated file, so the result
+// is probed before it is reported as a success.
+fn verify_download(app: &tauri::AppHandle, path: &Path) -> Result<(), String> {
+    let size = std::fs::metadata(path).map_err(|e| e.to_string())?.len();
+    if size == 0 {
+        return Err("file is empty".into());
+    }
+    let probe = probe_media(app, path)?;
+    if !probe.has_audio {
+        return Err("no audio stream".into());
+    }
+    if !probe.duration.is_some_and(|d| d > 0.0) {
+        return Err("no valid duration".into());
+    }
+    Ok(())
+}
+
+const DOWNLOAD_CONTAINER_KEEP: &str = "original";
+
+fn validate_download_container(container: &str) -> Result<(), String> {
+    if container == DOWNLOAD_CONTAINER_KEEP {
+        return Ok(());
+    }
+    remux_target(container).map(|_| ())
+}
+
+// None keeps yt-dlp's m4a conversion; "original" keeps whatever the site
+// serves; anything else is a remux target every download is brought into.
+fn download_container() -> Option<String> {
+    load_settings()
+        .ok()
+        .and_then(|s| s.download_container)
+        .filter(|c| validate_download_container(c).is_ok())
+}
+
+fn remux_download(
+    app: &tauri::AppHandle,
+    id: &str,
+    file: &Path,
+    container: &str,
+    log_path: &Path,
+) -> Result<PathBuf, String> {
+    let target = remux_target(container)?;
+    let ext = file
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+    if ext == target.extension {
+        return Ok(file.to_path_buf());
+    }
+    let plan = plan_remux(app, file, target)?;
+    if !plan.copied {
+        let _ = append_log_line(
+            log_path,
+            &format!(
+                "[remux] warning: {} can't be stored in .{}; re-encoding with {}",
+                plan.source_codec, target.extension, plan.output_codec
+            ),
+        );
+    }
+    let dir = file.parent().ok_or("Invalid download path")?;
+    let stem = file
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or("Invalid download path")?;
+    let output_path = unique_path(dir, &format!("{stem}.{}", target.extension));
+    let mut args = plan.args;
+    args.push(path_arg(&output_path)?);
+    let output = run_tracked(
+        app,
+        &download_process_id(id),
+        Command::new(ffmpeg_path(app)?).args(&args),
+        None,
+        None,
+    )?;
+    if !output.status.success() || output.stopped {
+        let _ = std::fs::remove_file(&output_path);
+        let _ = append_log_line(log_path, &output.stderr);
+        return Err(format!(
+            "Remux to .{} failed: {}",
+            target.extension,
+            last_error_line(&output.stderr)
+        ));
+    }
+    let _ = std::fs::remove_file(file);
+    let _ = append_log_line(
+        log_path,
+        &format!("[remux] {} -> {}", file.to_string_lossy(), output_path.to_string_lossy()),
+    );
+    Ok(output_path)
+}
+
+#[tauri::command]
+fn get_download_container() -> Option<String> {
+    download_container()
+}
+
+// An empty value goes back to the built-in m4a conversion.
+#[tauri::command]
+fn set_download_container(container: String) -> Result<Option<String>, String> {
+    let container = container.trim().trim_start_matches('.').to_ascii_lowercase();
+    let mut settings = load_settings()?;
+    if container.is_empty() {
+        settings.download_container = None;
+    } else {
+        validate_download_container(&container)?;
+        settings.download_container = Some(container);
+    }
+    save_settings(&settings)?;
+    Ok(download_container())
+}
+
+fn download_template(request: &DownloadRequest) -> PathBuf {
+    request.download_dir.join("%(title)s [%(id)s].%(ext)s")
+}
+
+fn run_download(
+    app: &tauri::AppHandle,
+    id: &str,
+    request: DownloadRequest,
+    resume: bool,
+) -> Result<String, String> {
+    let existing = download_partials(&request.download_dir)
+        .iter()
+        .map(|file| file.to_string_lossy().to_string())
+        .collect();
+    let journal_id = journal_begin_with("download", &download_template(&request), existing);
+    let result = run_download_attempts(app, id, request, resume);
+    journal_end(journal_id);
+    result
+}
+
+fn run_download_attempts(
+    app: &tauri::AppHandle,
+    id: &str,
+    request: DownloadRequest,
+    resume: bool,
+) -> Result<String, String> {
+    let yt_dlp = tool_path(app, "yt-dlp")?;
+    let bin_dir = binaries_dir(app)?;
+    let format = request.format_id.as_deref().unwrap_or("bestaudio/best");
+    let _ = append_log_line(&request.log_path, &format!("[format] {format}"));
+    let container = download_container();
+    // Without a container setting yt-dlp converts to m4a itself; with one it
+    // keeps the source stream and remux_download takes over afterwards.
+    let audio_format = if container.is_some() { "best" } else { "m4a" };
+    let mut args: Vec<String> = vec![
+        "-f".into(),
+        format.into(),
+        "-x".into(),
+        "--audio-format".into(),
+        audio_format.into(),
+        "--ffmpeg-location".into(),
+        path_arg(&bin_dir)?,
+        "-o".into(),
+        path_arg(&download_template(&request))?,
+        "--print".into(),
+        "after_move:filepath".into(),
+        // --print implies --quiet; keep the progress lines get_active_operations reads.
+        "--progress".into(),
+    ];
+    if resume {
+        args.push("--continue".into());
+    }
+    if !request.playlist {
+        args.push("--no-playlist".into());
+    }
+    let settings = load_settings()?;
+    if let Some(proxy) = settings.proxy_url {
+        validate_proxy_url(&proxy)?;
+        args.push("--proxy".into());
+        args.push(proxy);
+    }
+    if let Some(rate) = settings.rate_limit {
+        validate_rate_limit(&rate)?;
+        args.push("--limit-rate".into());
+        args.push(rate);
+    }
+    args.push("--".into());
+    args.push(request.url.clone());
+
+    let log_path = &request.log_path;
+    let max_attempts = request.max_attempts;
+    let mut attempt = 1;
+    loop {
+        let _ = append_log_line(
+            log_path,
+            &format!("[attempt {attempt}/{max_attempts}] yt-dlp {}", args.join(" ")),
+        );
+        let output = run_tracked(
+            app,
+            &download_process_id(id),
+            yt_dlp_command(&yt_dlp).args(&args),
+            None,
+            None,
+        )?;
+        let _ = append_log_line(log_path, &output.stdout);
+        let _ = append_log_line(log_path, &output.stderr);
+        let _ = append_log_line(
+            log_path,
+            &format!(
+                "[attempt {attempt}/{max_attempts}] exit code {}",
+                output.status.code().unwrap_or(-1)
+            ),
+        );
+
+        if output.stopped {
+            let _ = append_log_line(log_path, "[paused] partial file kept for resume");
+            if let Ok(mut paused) = app.state::<DownloadRegistry>().0.lock() {
+                paused.insert(
+                    id.to_string(),
+                    PausedDownload {
+                        request: request.clone(),
+                        part_files: part_files(&request.download_dir),
+                    },
+                );
+            }
+            return Err("Download paused".into());
+        }
+
+        if output.status.success() {
+            let reported = output
+                .stdout
+                .lines()
+                .rev()
+                .map(|line| PathBuf::from(line.trim()))
+                .find(|path| path.is_file());
+            let file = match reported {
+                Some(path) => path.to_string_lossy().to_string(),
+                None => find_latest_download(request.download_dir.to_string_lossy().to_string())?,
+            };
+            if let Err(reason) = verify_download(app, Path::new(&file)) {
+                let corrupt = format!("{file}.corrupt");
+                let kept_as = if std::fs::rename(&file, &corrupt).is_ok() { corrupt } else { file };
+                let _ = append_log_line(log_path, &format!("[verify] {reason}; kept as {kept_as}"));
+                return Err(format!("Downloaded file is corrupt: {reason}"));
+            }
+            let file = match container.as_deref().filter(|c| *c != "original") {
+                Some(container) => remux_download(app, id, Path::new(&file), container, log_path)?
+                    .to_string_lossy()
+                    .to_string(),
+                None => file,
+            };
+            let _ = atomic_write(&request.download_dir.join("last_download.txt"), &file);
+            return Ok(file);
+        }
+
+        if attempt >= max_attempts || !is_transient_download_error(&output.stderr) {
+            return Err(format!(
+                "Download failed after {attempt} attempt(s): {}",
+                last_error_line(&output.stderr)
+            ));
+        }
+        let delay = download_backoff_secs(attempt);
+        let _ = append_log_line(
+            log_path,
+            &format!("[attempt {attempt}/{max_attempts}] transient error, retrying in {delay}s"),
+        );
+        std::thread::sleep(std::time::Duration::from_secs(delay));
+        attempt += 1;
+    }
+}
+
+// Applies a state change to one queue item, persists the queue and tells
+// the frontend about it.
+fn update_queue_item(
+    app: &tauri::AppHandle,
+    id: &str,
+    update: impl FnOnce(&mut QueueItem),
+) -> Result<(), String> {
+    let queue = app.state::<DownloadQueue>();
+    let mut state = queue.0.lock().map_err(|e| e.to_string())?;
+    let Some(item) = state.items.iter_mut().find(|item| item.id == id) else {
+        return Ok(());
+    };
+    update(item);
+    let _ = app.emit("download-queue", item.clone());
+    save_queue(&state.items)
+}
+
+fn next_queued_item(app: &tauri::AppHandle) -> Option<QueueItem> {
+    let queue = app.state::<DownloadQueue>();
+    let mut state = queue.0.lock().ok()?;
+    let next = state
+        .items
+        .iter()
+        .find(|item| item.state == "queued" || item.state == "interrupted")
+        .cloned();
+    if next.is_none() {
+        state.worker_running = false;
+    }
+    next
+}
+
+// Items are processed one at a time on a single worker thread.
+fn ensure_queue_worker(app: &tauri::AppHandle) -> Result<(), String> {
+    {
+        let queue = app.state::<DownloadQueue>();
+        let mut state = queue.0.lock().map_err(|e| e.to_string())?;
+        if state.worker_running {
+            return Ok(());
+        }
+        state.worker_running = true;
+    }
+
+    let app = app.clone();
+    std::thread::spawn(move || {
+        while let Some(item) = next_queued_item(&app) {
+            let _ = update_queue_item(&app, &item.id, |item| {
+                item.state = "downloading".into();
+                item.error = None;
+            });
+            let date_folder = app_now().format("%Y-%m-%d").to_string();
+            let result = prepare_download(date_folder, item.id.clone()).and_then(|paths| {
+                let request = DownloadRequest {
+                    url: item.url.clone(),
+                    download_dir: PathBuf::from(&paths.download_dir),
+                    log_path: PathBuf::from(&paths.log_path),
+                    playlist: item.playlist,
+                    max_attempts: 3,
+                    format_id: None,
+                };
+                run_download(&app, &item.id, request, false)
+            });
+            let _ = update_queue_item(&app, &item.id, |item| match result {
+                Ok(path) => {
+                    item.state = "done".into();
+                    item.output_path = Some(path);
+                }
+                Err(err) => {
+                    item.state = "error".into();
+                    item.error = Some(err);
+                }
+            });
+            // remove_from_queue stops a running download the way a pause does;
+            // it can't be resumed, so it mustn't stay registered as paused.
+            let removed = app
+                .state::<DownloadQueue>()
+                .0
+                .lock()
+                .map(|state| !state.items.iter().any(|queued| queued.id == item.id))
+                .unwrap_or(false);
+            if removed {
+                if let Ok(mut paused) = app.state::<DownloadRegistry>().0.lock() {
+                    paused.remove(&item.id);
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+// Called on launch: anything that was mid-download when the app closed is
+// marked for retry and the worker picks pending items back up.
+fn restore_download_queue(app: &tauri::AppHandle) -> Result<(), String> {
+    let mut items = load_queue()?;
+    for item in items.iter_mut().filter(|item| item.state == "downloading") {
+        item.state = "interrupted".into();
+    }
+    save_queue(&items)?;
+    let pending = items
+        .iter()
+        .any(|item| item.state == "queued" || item.state == "interrupted");
+    app.state::<DownloadQueue>()
+        .0
+        .lock()
+        .map_err(|e| e.to_string())?
+        .items = items;
+    if pending {
+        ensure_queue_worker(app)?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn enqueue_download(
+    app: tauri::AppHandle,
+    url: String,
+    playlist: Option<bool>,
+) -> Result<QueueItem, String> {
+    validate_download_url(&url)?;
+    require_tools(&app, &["yt-dlp", "ffmpeg"])?;
+    let item = {
+        let queue = app.state::<DownloadQueue>();
+        let mut state = queue.0.lock().map_err(|e| e.to_string())?;
+        let base = app_now().format("%Y%m%d_%H%M%S_%3f").to_string();
+        let mut id = base.clone();
+        let mut suffix = 1;
+        while state.items.iter().any(|item| item.id == id) {
+            id = format!("{base}_{suffix}");
+            suffix += 1;
+        }
+        let item = QueueItem {
+            id,
+            url: url.trim().to_string(),
+            playlist: playlist.unwrap_or(false),
+            state: "queued".into(),
+            added_at: app_now().to_rfc3339(),
+            output_path: None,
+            error: None,
+        };
+        state.items.push(item.clone());
+        save_queue(&state.items)?;
+        item
+    };
+    let _ = app.emit("download-queue", item.clone());
+    ensure_queue_worker(&app)?;
+    Ok(item)
+}
+
+#[tauri::command]
+fn get_queue(app: tauri::AppHandle) -> Result<Vec<QueueItem>, String> {
+    let queue = app.state::<DownloadQueue>();
+    let state = queue.0.lock().map_err(|e| e.to_string())?;
+    Ok(state.items.clone())
+}
+
+#[tauri::command]
+fn remove_from_queue(app: tauri::AppHandle, id: String) -> Result<(), String> {
+    let queue = app.state::<DownloadQueue>();
+    let mut state = queue.0.lock().map_err(|e| e.to_string())?;
+    let before = state.items.len();
+    state.items.retain(|item| item.id != id);
+    if state.items.len() == before {
+        return Err("No queued download with that id".into());
+    }
+    save_queue(&state.items)?;
+    drop(state);
+    // Stops the download if the worker is on this item right now; the worker
+    // then drops the paused entry the stop leaves behind.
+    stop_tracked(&app, &download_process_id(&id))?;
+    if let Ok(mut paused) = app.state::<DownloadRegistry>().0.lock() {
+        paused.remove(&id);
+    }
+    Ok(())
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn record_download(
+    url: String,
+    title: String,
+    output_path: String,
+    format: String,
+    format_id: Option<String>,
+) -> Result<(), String> {
+    let root = resolve_download_root()?;
+    let path = PathBuf::from(&output_path);
+    if !path.is_file() || !is_within(&root, &path)? {
+        return Err("Invalid download path".into());
+    }
+
+    let mut entries = load_history()?;
+    entries.push(HistoryEntry {
+        url,
+        title,
+        timestamp: app_now().to_rfc3339(),
+        output_path,
+        format,
+        format_id,
+    });
+    save_history(&entries)
+}
+
+#[derive(Debug, Serialize)]
+struct HistoryRebuild {
+    recovered: usize,
+    backup_path: Option<String>,
+}
+
+// Sidecars are looked up as "<file>.meta" and "<stem>.meta"; JSON contents
+// may carry url/title fields, anything else is ignored.
+fn read_meta_sidecar(file: &Path) -> Option<serde_json::Value> {
+    let with_ext = PathBuf::from(format!("{}.meta", file.to_string_lossy()));
+    [with_ext, file.with_extension("meta")]
+        .iter()
+        .find_map(|path| std::fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+}
+
+// yt-dlp names downloads "<title> [<id>].<ext>".
+fn split_download_name(stem: &str) -> (String, Option<String>) {
+    match stem.rsplit_once(" [") {
+        Some((title, id)) if id.ends_with(']') => {
+            (title.to_string(), Some(id.trim_end_matches(']').to_string()))
+        }
+        _ => (stem.to_string(), None),
+    }
+}
+
+#[tauri::command]
+fn rebuild_history() -> Result<HistoryRebuild, String> {
+    let path = history_path()?;
+    let backup_path = if path.exists() {
+        let backup = path.with_file_name(format!(
+            "history.backup_{}.json",
+            app_now().format("%Y%m%d_%H%M%S")
+        ));
+        std::fs::copy(&path, &backup).map_err(|e| e.to_string())?;
+        Some(backup.to_string_lossy().to_string())
+    } else {
+        None
+    };
+    // Whatever still parses from the old index is kept for files that exist.
+    let previous: Vec<HistoryEntry> = std::fs::read_to_string(&path)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default();
+
+    let mut files = Vec::new();
+    collect_files(&resolve_download_root()?, &mut files)?;
+    let mut entries: Vec<(std::time::SystemTime, HistoryEntry)> = Vec::new();
+    for file in files {
+        let Some(format) = file
+            .extension()
+            .and_then(|e| e.to_str())
+            .and_then(|e| extension_format(&e.to_ascii_lowercase()))
+        else {
+            continue;
+        };
+        let output_path = file.to_string_lossy().to_string();
+        let modified = std::fs::metadata(&file)
+            .and_then(|m| m.modified())
+            .unwrap_or(std::time::SystemTime::UNIX_EPOCH);
+        if let Some(entry) = previous.iter().find(|e| e.output_path == output_path) {
+            entries.push((modified, entry.clone()));
+            continue;
+        }
+        let stem = file.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+        let (title, video_id) = split_download_name(stem);
+        let meta = read_meta_sidecar(&file);
+        let meta_text = |key: &str| {
+            meta.as_ref()
+                .and_then(|m| m.get(key))
+                .and_then(|v| v.as_str())
+                .map(|v| v.to_string())
+        };
+        let url = meta_text("url")
+            .or_else(|| meta_text("webpage_url"))
+            .or_else(|| video_id.map(|id| format!("https://www.youtube.com/watch?v={id}")))
+            .unwrap_or_default();
+        entries.push((
+            modified,
+            HistoryEntry {
+                url,
+                title: meta_text("title").unwrap_or(title),
+                timestamp: app_time(modified).to_rfc3339(),
+                output_path,
+                format: format.to_string(),
+                format_id: meta_text("format_id"),
+            },
+        ));
+    }
+    entries.sort_by_key(|(modified, _)| *modified);
+    let entries: Vec<HistoryEntry> = entries.into_iter().map(|(_, entry)| entry).collect();
+    save_history(&entries)?;
+    Ok(HistoryRebuild {
+        recovered: entries.len(),
+        backup_path,
+    })
+}
+
+#[tauri::command]
+fn get_history(limit: Option<usize>) -> Result<Vec<HistoryEntry>, String> {
+    let entries = load_history()?;
+    let before = entries.len();
+    let entries: Vec<HistoryEntry> = entries
+        .into_iter()
+        .filter(|entry| Path::new(&entry.output_path).is_file())
+        .collect();
+    if entries.len() != before {
+        save_history(&entries)?;
+    }
+
+    let limit = limit.unwrap_or(50);
+    Ok(entries.into_iter().rev().take(limit).collect())
+}
+
+#[tauri::command]
+fn write_download_log(path: String, contents: String) -> Result<(), String> {
+    let root = resolve_download_root()?;
+    let path = PathBuf::from(path);
+    if !is_within(&root, &path)? {
+        return Err("Invalid log path".into());
+    }
+    atomic_write(&path, contents)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn write_video_log(log_stamp: String, contents: String) -> Result<(), String> {
+    validate_log_stamp(&log_stamp)?;
+    let logs = logs_root()?;
+    std::fs::create_dir_all(&logs).map_err(|e| e.to_string())?;
+    let log_path = logs.join(format!("video_export_{}.log", log_stamp));
+    atomic_write(&log_path, contents)
+}
+
+#[tauri::command]
+fn get_logs_dir() -> Result<String, String> {
+    let logs = logs_root()?;
+    std::fs::create_dir_all(&logs).map_err(|e| e.to_string())?;
+    Ok(logs.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+fn list_logs() -> Result<Vec<LogFileInfo>, String> {
+    let logs = logs_root()?;
+    std::fs::create_dir_all(&logs).map_err(|e| e.to_string())?;
+
+    let entries = std::fs::read_dir(&logs).map_err(|e| e.to_string())?;
+    let mut files: Vec<(std::time::SystemTime, LogFileInfo)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let meta = entry.metadata().ok()?;
+            if !meta.is_file() {
+                return None;
+            }
+            let modified = meta.modified().ok()?;
+            let info = LogFileInfo {
+                name: entry.file_name().to_string_lossy().to_string(),
+                path: entry.path().to_string_lossy().to_string(),
+                size: meta.len(),
+                modified: app_time(modified).format("%Y-%m-%d %H:%M:%S").to_string(),
+            };
+            Some((modified, info))
+        })
+        .collect();
+
+    files.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    Ok(files.into_iter().map(|(_, info)| info).collect())
+}
+
+const MAX_LOG_TAIL_LINES: usize = 2000;
+
+#[tauri::command(rename_all = "camelCase")]
+fn read_log_tail(name: String, max_lines: Option<usize>) -> Result<String, String> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.contains("..") {
+        return Err("Invalid log name".into());
+    }
+    let logs = logs_root()?;
+    let path = logs.join(&name);
+    if !path.is_file() || !is_within(&logs, &path)? {
+        return Err(format!("Log not found: {name}"));
+    }
+    let max_lines = max_lines.unwrap_or(200).clamp(1, MAX_LOG_TAIL_LINES);
+    Ok(tail_lines(&path, max_lines))
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn append_video_trace(
+    app: tauri::AppHandle,
+    session_id: String,
+    line: String,
+) -> Result<(), String> {
+    append_video_trace_line(&app, &session_id, &line)
+}
+
+#[tauri::command]
+fn write_meta_file(path: String, contents: String) -> Result<(), String> {
+    let root = resolve_download_root()?;
+    let path = PathBuf::from(path);
+    if !is_within(&root, &path)? {
+        return Err("Invalid metadata path".into());
+    }
+    atomic_write(&path, contents)
+}
+
+#[tauri::command]
+fn read_downloaded_file(path: String) -> Result<Vec<u8>, String> {
+    let root = resolve_download_root()?;
+    let path = PathBuf::from(path);
+    if !is_within(&root, &path)? {
+        return Err("Invalid download path".into());
+    }
+    std::fs::read(path).map_err(|e| e.to_string())
+}
+
+const MAX_CHUNK_BYTES: u64 = 8 * 1024 * 1024;
+
+#[derive(Debug, Serialize)]
+struct FileChunk {
+    bytes: Vec<u8>,
+    offset: u64,
+    file_size: u64,
+    eof: bool,
+}
+
+#[tauri::command]
+fn read_file_chunk(path: String, offset: u64, length: u64) -> Result<FileChunk, String> {
+    use std::io::{Read, Seek, SeekFrom};
+    let root = resolve_download_root()?;
+    let path = PathBuf::from(path);
+    if !is_within(&root, &path)? {
+        return Err("Invalid download path".into());
+    }
+    if length == 0 || length > MAX_CHUNK_BYTES {
+        return Err(format!("Chunk length must be 1-{MAX_CHUNK_BYTES} bytes"));
+    }
+
+    let mut file = std::fs::File::open(&path).map_err(|e| e.to_string())?;
+    let file_size = file.metadata().map_err(|e| e.to_string())?.len();
+    if offset > file_size {
+        return Err(format!("Offset {offset} is past the end of the file ({file_size} bytes)"));
+    }
+    let length = length.min(file_size - offset);
+    file.seek(SeekFrom::Start(offset)).map_err(|e| e.to_string())?;
+    let mut bytes = vec![0u8; length as usize];
+    file.read_exact(&mut bytes).map_err(|e| e.to_string())?;
+    Ok(FileChunk {
+        bytes,
+        offset,
+        file_size,
+        eof: offset + length >= file_size,
+    })
+}
+
+#[tauri::command(rename_all = "camelCase")]
+fn find_latest_download(download_dir: String) -> Result<String, String> {
+    let root = resolve_download_root()?;
+    let dir = PathBuf::from(download_dir);
+    if !is_within(&root, &dir.join("probe.txt"))? {
+        return Err("Invalid download directory".into());
+    }
+
+    let entries = std::fs::read_dir(&dir).map_err(|e| e.to_string())?;
+    let mut candidates: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .and_then(|ext| extension_format(&ext.to_ascii_lowercase()))
+                .is_some()
+        })
+        .collect();
+
+    candidates.sort_by_key(|path| {
+        std::fs::metadata(path)
+            .and_then(|meta| meta.modified())
+            .ok()
+    });
+
+    let latest = candidates.pop().ok_or("No downloaded file found")?;
+    let canonical = canonical_path(&latest)?;
+    Ok(canonical.to_string_lossy().to_string())
+}
+
+fn cached_duration(
+    app: &tauri::AppHandle,
+    path: &Path,
+    size: u64,
+    modified: std::time::SystemTime,
+) -> Option<f64> {
+    let cache = app.state::<DurationCache>();
+    if let Some(cached) = cache.0.lock().ok()?.get(path).copied() {
+        if cached.size == size && cached.modified == modified {
+            return cached.duration;
+        }
+    }
+    // Probed without holding the lock so other listings aren't held up.
+    let duration = probe_media(app, path).ok().and_then(|p| p.duration);
+    if let Ok(mut cached) = cache.0.lock() {
+        cached.insert(
+            path.to_path_buf(),
+            CachedDuration {
+                size,
+                modified,
+                duration,
+            },
+        );
+    }
+    duration
+}
+
+// With the flat and by-month schemes a folder holds more than one day, so
+// files are also matched on their modified date there.
+#[tauri::command(async, rename_all = "camelCase")]
+fn list_downloads(
+    app: tauri::AppHandle,
+    date_folder: String,
+) -> Result<Vec<DownloadListing>, String> {
+    let dir = scheme_dir(&resolve_download_root()?, &date_folder)?;
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let same_day = folder_scheme() == "by-date";
+
+    let mut files: Vec<(std::time::SystemTime, PathBuf, u64)> = Vec::new();
+    for entry in std::fs::read_dir(&dir).map_err(|e| e.to_string())?.flatten() {
+        let path = entry.path();
+        let is_audio = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .and_then(|e| extension_format(&e.to_ascii_lowercase()))
+            .is_some();
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if !is_audio || !metadata.is_file() {
+            continue;
+        }
+        let modified = metadata.modified().unwrap_or(std::time::SystemTime::UNIX_EPOCH);
+        if !same_day
+            && !app_time(modified)
+                .format("%Y-%m-%d")
+                .to_string()
+                .starts_with(&date_folder)
+        {
+            continue;
+        }
+        files.push((modified, path, metadata.len()));
+    }
+    files.sort_by_key(|(modified, _, _)| std::cmp::Reverse(*modified));
+
+    Ok(files
+        .into_iter()
+        .map(|(modified, path, size)| DownloadListing {
+            name: path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            duration: cached_duration(&app, &path, size, modified),
+            path: path.to_string_lossy().to_string(),
+            size,
+            modified: app_time(modified).to_rfc3339(),
+        })
+        .collect())
+}
+
+const ILLEGAL_FILENAME_CHARS: &str = "<>:\"/\\|?*";
+
+fn validate_file_stem(name: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("Filename template produced an empty name".into());
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| c.is_control() || ILLEGAL_FILENAME_CHARS.contains(*c))
+    {
+        return Err(format!("Filename contains illegal character {c:?}"));
+    }
+    if name.ends_with('.') || name.ends_with(' ') {
+        return Err("Filename must not end with a dot or space".into());
+    }
+    Ok(())
+}
+
+fn render_filename_template(template: &str, values: &[(&str, &str)]) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        let close = after
+            .find('}')
+            .ok_or("Unclosed token in filename template")?;
+        let token = &after[..close];
+        let value = values
+            .iter()
+            .find(|(name, _)| *name == token)
+            .map(|(_, value)| *value)
+            .ok_or_else(|| format!("Unknown filename token {{{token}}}"))?;
+        // Titles come from YouTube and routinely contain `:` or `/`, so token
+        // values are cleaned; only the template's own literals can be rejected.
+        out.extend(value.chars().map(|c| {
+            if c.is_control() || ILLEGAL_FILENAME_CHARS.contains(c) {
+                '_'
+            } else {
+                c
+            }
+        }));
+        rest = &after[close + 1..];
+    }
+    out.push_str(rest);
+    validate_file_stem(&out)?;
+    Ok(out)
+}
+
+#[tauri::command]
+fn get_filename_template() -> Result<Option<String>, String> {
+    Ok(load_settings()?.filename_template)
+}
+
+#[tauri::command]
+fn set_filename_template(template: String) -> Result<Option<String>, String> {
+    let mut settings = load_settings()?;
+    if template.trim().is_empty() {
+        settings.filename_template = None;
+        save_settings(&settings)?;
+        return get_filename_template();
+    }
+
+    render_filename_template(
+        template.trim(),
+        &[
+            ("title", "title"),
+            ("date", "2000-01-01"),
+            ("stamp", "20000101_000000"),
+            ("resolution", "1080x1920_30fps"),
+        ],
+    )?;
+    settings.filename_template = Some(template.trim().to_string());
+    save_settings(&settings)?;
+    get_filename_template()
+}
+
+struct AudioFormatSpec {
+    name: &'static str,
+    extension: &'static str,
+    container: &'static str,
+    codec: &'static str,
+    bitrate: Option<&'static str>,
+    tags: bool,
+    cover_art: bool,
+}
+
+// The first entry for an extension is the format assumed when only a
+// filename is given.
+const AUDIO_FORMATS: [AudioFormatSpec; 7] = [
+    AudioFormatSpec {
+        name: "mp3",
+        extension: "mp3",
+        container: "mp3",
+        codec: "libmp3lame",
+        bitrate: Some("192k"),
+        tags: true,
+        cover_art: true,
+    },
+    AudioFormatSpec {
+        name: "m4a",
+        extension: "m4a",
+        container: "ipod",
+        codec: "aac",
+        bitrate: Some("192k"),
+        tags: true,
+        cover_art: true,
+    },
+    AudioFormatSpec {
+        name: "aac",
+        extension: "m4a",
+        container: "ipod",
+        codec: "aac",
+        bitrate: Some("192k"),
+        tags: true,
+        cover_art: true,
+    },
+    AudioFormatSpec {
+        name: "wav",
+        extension: "wav",
+        container: "wav",
+        codec: "pcm_s16le",
+        bitrate: None,
+        tags: true,
+        cover_art: false,
+    },
+    AudioFormatSpec {
+        name: "flac",
+        extension: "flac",
+        container: "flac",
+        codec: "flac",
+        bitrate: None,
+        tags: true,
+        cover_art: true,
+    },
+    AudioFormatSpec {
+        name: "opus",
+        extension: "opus",
+        container: "ogg",
+        codec: "libopus",
+        bitrate: Some("128k"),
+        tags: true,
+        cover_art: false,
+    },
+    AudioFormatSpec {
+        name: "webm",
+        extension: "webm",
+        container: "webm",
+        codec: "libopus",
+        bitrate: Some("128k"),
+        tags: true,
+        cover_art: false,
+    },
+];
+
+#[derive(Debug, Serialize)]
+struct FormatInfo {
+    name: String,
+    extension: String,
+    container: String,
+    codec: String,
+    tags: bool,
+    cover_art: bool,
+}
+
+#[tauri::command]
+fn supported_formats() -> Vec<FormatInfo> {
+    AUDIO_FORMATS
+        .iter()
+        .map(|spec| FormatInfo {
+            name: spec.name.into(),
+            extension: spec.extension.into(),
+            container: spec.container.into(),
+            codec: spec.codec.into(),
+            tags: spec.tags,
+            cover_art: spec.cover_art,
+        })
+        .collect()
+}
+
+fn audio_format(name: &str) -> Option<&'static AudioFormatSpec> {
+    AUDIO_FORMATS.iter().find(|spec| spec.name == name)
+}
+
+fn format_extension(format: &str) -> Option<&'static str> {
+    audio_format(format).map(|spec| spec.extension)
+}
+
+fn extension_format(ext: &str) -> Option<&'static str> {
+    AUDIO_FORMATS
+        .iter()
+        .find(|spec| spec.extension == ext)
+        .map(|spec| spec.name)
+}
+
+// Export format matching an input file's extension, for operations that
+// keep the source format.
+fn input_format(path: &Path) -> Result<&'static str, String> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+    extension_format(&ext).ok_or_else(|| format!("Unsupported input format '.{ext}'"))
+}
+
+fn supported_format_names() -> String {
+    AUDIO_FORMATS
+        .iter()
+        .map(|spec| spec.name)
+        .collect::<Vec<&str>>()
+        .join(", ")
+}
+
+fn codec_fits_container(container: &str, codec: &str) -> bool {
+    match container {
+        "mp3" => codec == "libmp3lame",
+        "ipod" => matches!(codec, "aac" | "alac" | "libfdk_aac"),
+        "mp4" => matches!(
+            codec,
+            "aac" | "alac" | "libfdk_aac" | "libmp3lame" | "libopus"
+        ),
+        "wav" => codec.starts_with("pcm_"),
+        "flac" => codec == "flac",
+        "ogg" => matches!(codec, "libopus" | "libvorbis" | "flac"),
+        "webm" => matches!(codec, "libopus" | "libvorbis"),
+        _ => false,
+    }
+}
+
+fn parse_encoders(text: &str, kind: char) -> Vec<String> {
+    // Rows look like " A....D aac   AAC (Advanced Audio Coding)" and follow a
+    // "------" separator line; the first flag is A, V or S.
+    text.lines()
+        .skip_while(|line| !line.trim_start().starts_with("---"))
+        .skip(1)
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let flags = parts.next()?;
+            let name = parts.next()?;
+            flags.starts_with(kind).then(|| name.to_string())
+        })
+        .collect()
+}
+
+fn audio_encoders(app: &tauri::AppHandle) -> Result<Vec<String>, String> {
+    let cache = app.state::<EncoderCache>();
+    let mut cached = cache.0.lock().map_err(|e| e.to_string())?;
+    if let Some(encoders) = cached.as_ref() {
+        return Ok(encoders.clone());
+    }
+    let output = Command::new(ffmpeg_path(app)?)
+        .args(["-hide_banner", "-encoders"])
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err("Unable to list ffmpeg encoders".into());
+    }
+    let encoders = parse_encoders(&String::from_utf8_lossy(&output.stdout), 'A');
+    *cached = Some(encoders.clone());
+    Ok(encoders)
+}
+
+fn wav_bit_depth_codec(
+    bit_depth: Option<&str>,
+    container: &str,
+) -> Result<Option<&'static str>, String> {
+    let Some(bit_depth) = bit_depth.map(str::trim).filter(|b| !b.is_empty()) else {
+        return Ok(None);
+    };
+    let codec = match bit_depth {
+        "16" => "pcm_s16le",
+        "24" => "pcm_s24le",
+        "32f" => "pcm_f32le",
+        other => return Err(format!("Bit depth must be 16, 24 or 32f, not '{other}'")),
+    };
+    if container != "wav" {
+        return Err("Bit depth can only be chosen for WAV exports".into());
+    }
+    Ok(Some(codec))
+}
+
+fn select_audio_encoder(
+    app: &tauri::AppHandle,
+    requested: Option<&str>,
+    default: &str,
+    container: &str,
+) -> Result<String, String> {
+    let Some(requested) = requested.map(str::trim).filter(|e| !e.is_empty()) else {
+        return Ok(default.to_string());
+    };
+    let available = audio_encoders(app)?;
+    if !available.iter().any(|name| name == requested) {
+        return Err(format!(
+            "Audio encoder '{requested}' isn't available in the bundled ffmpeg"
+        ));
+    }
+    if !codec_fits_container(container, requested) {
+        return Err(format!(
+            "Audio encoder '{requested}' can't be used with the {container} container"
+        ));
+    }
+    Ok(requested.to_string())
+}
+
+#[tauri::command(async)]
+fn list_audio_encoders(app: tauri::AppHandle) -> Result<Vec<String>, String> {
+    audio_encoders(&app)
+}
+
+struct HardwareEncoderSpec {
+    name: &'static str,
+    encoder: &'static str,
+    pix_fmt: &'static str,
+}
+
+// Tried in this order when the export asks for "auto".
+const HARDWARE_H264_ENCODERS: [HardwareEncoderSpec; 3] = [
+    HardwareEncoderSpec {
+        name: "nvenc",
+        encoder: "h264_nvenc",
+        pix_fmt: "yuv420p",
+    },
+    HardwareEncoderSpec {
+        name: "qsv",
+        encoder: "h264_qsv",
+        pix_fmt: "nv12",
+    },
+    HardwareEncoderSpec {
+        name: "amf",
+        encoder: "h264_amf",
+        pix_fmt: "yuv420p",
+    },
+];
+const HARDWARE_TEST_TIMEOUT_SECS: u64 = 20;
+
+fn parse_hwaccels(text: &str) -> Vec<String> {
+    text.lines()
+        .skip_while(|line| !line.starts_with("Hardware acceleration methods"))
+        .skip(1)
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_string())
+        .collect()
+}
+
+// An encoder compiled into ffmpeg still fails without the matching GPU and
+// driver, so each one has to encode a single test frame to count.
+fn hardware_encoder_works(
+    app: &tauri::AppHandle,
+    ffmpeg: &Path,
+    spec: &HardwareEncoderSpec,
+) -> bool {
+    let output = run_tracked(
+        app,
+        &format!("probe:{}", spec.encoder),
+        Command::new(ffmpeg).args([
+            "-hide_banner",
+            "-f",
+            "lavfi",
+            "-i",
+            "color=black:s=256x256:r=1",
+            "-frames:v",
+            "1",
+            "-c:v",
+            spec.encoder,
+            "-pix_fmt",
+            spec.pix_fmt,
+            "-f",
+            "null",
+            "-",
+        ]),
+        Some(std::time::Duration::from_secs(HARDWARE_TEST_TIMEOUT_SECS)),
+        None,
+    );
+    output.is_ok_and(|output| output.status.success() && !output.timed_out)
+}
+
+fn ffmpeg_capabilities(app: &tauri::AppHandle) -> Result<FfmpegCapabilities, String> {
+    let cache = app.state::<CapabilityCache>();
+    let mut cached = cache.0.lock().map_err(|e| e.to_string())?;
+    if let Some(capabilities) = cached.as_ref() {
+        return Ok(capabilities.clone());
+    }
+    let ffmpeg = ffmpeg_path(app)?;
+    let listing = |flag: &str| -> Result<String, String> {
+        let output = Command::new(&ffmpeg)
+            .args(["-hide_banner", flag])
+            .output()
+            .map_err(|e| e.to_string())?;
+        if !output.status.success() {
+            return Err(format!("Unable to run ffmpeg {flag}"));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    };
+    let hwaccels = parse_hwaccels(&listing("-hwaccels")?);
+    let video_encoders = parse_encoders(&listing("-encoders")?, 'V');
+    let hardware_h264 = HARDWARE_H264_ENCODERS
+        .iter()
+        .filter(|spec| video_encoders.iter().any(|name| name == spec.encoder))
+        .filter(|spec| hardware_encoder_works(app, &ffmpeg, spec))
+        .map(|spec| spec.name.to_string())
+        .collect();
+    let capabilities = FfmpegCapabilities {
+        hwaccels,
+        video_encoders,
+        hardware_h264,
+        max_threads: available_threads(),
+    };
+    *cached = Some(capabilities.clone());
+    Ok(capabilities)
+}
+
+#[tauri::command(async)]
+fn get_ffmpeg_capabilities(app: tauri::AppHandle) -> Result<FfmpegCapabilities, String> {
+    ffmpeg_capabilities(&app)
+}
+
+// Returns the encoder args and pixel format for a hardware H.264 export, or
+// None when software x264 should be used. "auto" quietly falls back when no
+// hardware encoder was detected; naming one that wasn't is an error.
+fn hardware_video_args(
+    app: &tauri::AppHandle,
+    options: &VideoOptions,
+) -> Result<Option<(Vec<String>, &'static str)>, String> {
+    let Some(requested) = options.hwaccel.as_deref().map(str::trim).filter(|h| !h.is_empty())
+    else {
+        return Ok(None);
+    };
+    if options.video_codec.as_deref().is_some_and(|codec| codec != "x264") {
+        return Err("Hardware encoding is only available for H.264 (x264) exports".into());
+    }
+    let detected = ffmpeg_capabilities(app)?.hardware_h264;
+    let spec = if requested == "auto" {
+        match HARDWARE_H264_ENCODERS
+            .iter()
+            .find(|spec| detected.iter().any(|name| name == spec.name))
+        {
+            Some(spec) => spec,
+            None => return Ok(None),
+        }
+    } else {
+        let spec = HARDWARE_H264_ENCODERS
+            .iter()
+            .find(|spec| spec.name == requested)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown hardware encoder '{requested}'. Use auto or one of: {}",
+                    HARDWARE_H264_ENCODERS.map(|spec| spec.name).join(", ")
+                )
+            })?;
+        if !detected.iter().any(|name| name == spec.name) {
+            return Err(format!("Hardware encoder '{requested}' wasn't detected on this machine"));
+        }
+        spec
+    };
+    // x264 presets don't exist on these encoders, so only the CRF carries
+    // over, as each encoder's constant-quality setting.
+    let crf = options.crf.unwrap_or(23);
+    if crf > 51 {
+        return Err("CRF for x264 must be between 0 and 51".into());
+    }
+    let quality: Vec<String> = match spec.name {
+        "nvenc" => vec!["-rc".into(), "vbr".into(), "-cq".into(), crf.to_string()],
+        "qsv" => vec!["-global_quality".into(), crf.to_string()],
+        _ => vec![
+            "-rc".into(),
+            "cqp".into(),
+            "-qp_i".into(),
+            crf.to_string(),
+            "-qp_p".into(),
+            crf.to_string(),
+        ],
+    };
+    let mut args: Vec<String> = vec!["-c:v".into(), spec.encoder.into()];
+    args.extend(quality);
+    Ok(Some((args, spec.pix_fmt)))
+}
+
+fn output_sample_rate(codec: &str, source: Option<u32>) -> Option<u32> {
+    const OPUS_RATES: [u32; 5] = [8000, 12000, 16000, 24000, 48000];
+    const MP3_RATES: [u32; 9] = [8000, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000];
+    match codec {
+        "aac" | "libfdk_aac" => Some(aac_sample_rate(source)),
+        "libopus" => Some(source.filter(|r| OPUS_RATES.contains(r)).unwrap_or(48000)),
+        "libmp3lame" => Some(source.filter(|r| MP3_RATES.contains(r)).unwrap_or(44100)),
+        // Lossless codecs keep whatever rate the source has.
+        _ => None,
+    }
+}
+
+// How the 0-100 quality setting maps onto each encoder; written to the trace
+// log whenever it is applied.
+const QUALITY_MAPPING: &str = "libmp3lame: -q:a 9..0 (VBR); aac/libfdk_aac: -b:a 64k..320k; libopus: -b:a 32k..256k; libvorbis: -q:a 0..10; lossless codecs: unaffected";
+
+fn quality_args(codec: &str, quality: u8) -> Option<Vec<String>> {
+    let q = f64::from(quality.min(100)) / 100.0;
+    let bitrate = |low: f64, high: f64| {
+        let kbps = ((low + (high - low) * q) / 8.0).round() * 8.0;
+        vec!["-b:a".to_string(), format!("{kbps}k")]
+    };
+    match codec {
+        "libmp3lame" => Some(vec!["-q:a".into(), format!("{}", (9.0 - 9.0 * q).round())]),
+        "aac" | "libfdk_aac" => Some(bitrate(64.0, 320.0)),
+        "libopus" => Some(bitrate(32.0, 256.0)),
+        "libvorbis" => Some(vec!["-q:a".into(), format!("{:.1}", 10.0 * q)]),
+        _ => None,
+    }
+}
+
+fn effective_quality(requested: Option<u8>) -> Result<Option<u8>, String> {
+    let quality = match requested {
+        Some(quality) => Some(quality),
+        None => load_settings()?.quality,
+    };
+    if quality.is_some_and(|q| q > 100) {
+        return Err("Quality must be between 0 and 100".into());
+    }
+    Ok(quality)
+}
+
+// Bitrate/VBR args for an encoder: the quality mapping when one is set,
+// else the given default.
+fn quality_or_default(
+    app: &tauri::AppHandle,
+    session_id: &str,
+    codec: &str,
+    quality: Option<u8>,
+    default_bitrate: Option<&str>,
+) -> Vec<String> {
+    if let Some(args) = quality.and_then(|q| quality_args(codec, q)) {
+        let _ = append_video_trace_line(
+            app,
+            session_id,
+            &format!(
+                "{{\"stage\":\"backend_quality\",\"quality\":{},\"codec\":\"{codec}\",\"args\":{},\"mapping\":{}}}",
+                quality.unwrap_or_default(),
+                serde_json::to_string(&args.join(" ")).unwrap_or_default(),
+                serde_json::to_string(QUALITY_MAPPING).unwrap_or_default()
+            ),
+        );
+        return args;
+    }
+    default_bitrate
+        .map(|bitrate| vec!["-b:a".to_string(), bitrate.to_string()])
+        .unwrap_or_default()
+}
+
+// Rough LAME VBR averages for -q:a 0..9.
+const MP3_VBR_KBPS: [f64; 10] = [245.0, 225.0, 190.0, 175.0, 165.0, 130.0, 115.0, 100.0, 85.0, 65.0];
+
+// Expected audio bitrate and whether it is a guess (VBR/lossless) rather
+// than a fixed CBR figure.
+fn estimated_audio_kbps(
+    codec: &str,
+    bitrate_args: &[String],
+    sample_rate: u32,
+    channels: u32,
+) -> (f64, bool) {
+    let value = bitrate_args.get(1).map(String::as_str).unwrap_or_default();
+    match bitrate_args.first().map(String::as_str) {
+        Some("-b:a") => (value.trim_end_matches('k').parse().unwrap_or(192.0), false),
+        Some("-q:a") if codec == "libmp3lame" => {
+            let q = value.parse::<usize>().unwrap_or(4).min(9);
+            (MP3_VBR_KBPS[q], true)
+        }
+        Some("-q:a") => (64.0 + value.parse::<f64>().unwrap_or(5.0) * 32.0, true),
+        _ => {
+            let pcm = f64::from(sample_rate) * f64::from(channels) * 16.0 / 1000.0;
+            if codec == "flac" {
+                (pcm * 0.6, true)
+            } else {
+                (pcm, false)
+            }
+        }
+    }
+}
+
+#[tauri::command(async, rename_all = "camelCase")]
+fn estimate_output_size(
+    app: tauri::AppHandle,
+    input_path: String,
+    format: Option<String>,
+    video: Option<bool>,
+    audio_options: Option<AudioOptions>,
+) -> Result<SizeEstimate, String> {
+    let input_path = PathBuf::from(input_path);
+    if !input_path.is_file() || !is_managed_input(&input_path)? {
+        return Err("Invalid input path".into());
+    }
+    let options = audio_options.unwrap_or_default();
+    let video = video.unwrap_or(false);
+    let probe = probe_media(&app, &input_path)?;
+    let mut duration = probe.duration.ok_or("Unable to read input duration")?;
+    if let Some(tempo) = options.tempo {
+        atempo_chain(tempo)?;
+        duration /= tempo;
+    }
+
+    let (codec, container_bitrate) = if video {
+        ("aac".to_string(), Some("192k"))
+    } else {
+        let format = format.unwrap_or_else(|| "mp3".into());
+        let spec = audio_format(&format)
+            .ok_or_else(|| format!("Unsupported export format '{format}'"))?;
+        (
+            options
+                .audio_encoder
+                .clone()
+                .unwrap_or_else(|| spec.codec.to_string()),
+            spec.bitrate,
+        )
+    };
+    let channels = options.channels.unwrap_or_else(|| probe.channels.clamp(1, 2));
+    let sample_rate = output_sample_rate(&codec, probe.sample_rate)
+        .or(probe.sample_rate)
+        .unwrap_or(44100);
+    let quality = effective_quality(options.quality)?;
+    let bitrate_args = quality
+        .and_then(|q| quality_args(&codec, q))
+        .unwrap_or_else(|| {
+            container_bitrate
+                .map(|b| vec!["-b:a".to_string(), b.to_string()])
+                .unwrap_or_default()
+        });
+    let (audio_kbps, approximate) = estimated_audio_kbps(&codec, &bitrate_args, sample_rate, channels);
+
+    // Video size depends entirely on content; the black frame export packs
+    // far below typical camera footage, so this is a tunable guess.
+    let video_kbps = if video {
+        let bits_per_pixel = load_settings()?
+            .video_bits_per_pixel
+            .unwrap_or(DEFAULT_VIDEO_BITS_PER_PIXEL);
+        1080.0 * 1920.0 * 30.0 * bits_per_pixel / 1000.0
+    } else {
+        0.0
+    };
+
+    let estimated_bytes = ((audio_kbps + video_kbps) * 1000.0 / 8.0 * duration) as u64;
+    Ok(SizeEstimate {
+        duration,
+        audio_kbps,
+        video_kbps,
+        estimated_bytes,
+        approximate: approximate || video,
+    })
+}
+
+#[tauri::command]
+fn get_quality() -> Result<Option<u8>, String> {
+    Ok(load_settings()?.quality)
+}
+
+#[tauri::command]
+fn set_quality(quality: Option<u8>) -> Result<Option<u8>, String> {
+    if quality.is_some_and(|q| q > 100) {
+        return Err("Quality must be between 0 and 100".into());
+    }
+    let mut settings = load_settings()?;
+    settings.quality = quality;
+    save_settings(&settings)?;
+    Ok(quality)
+}
+
+fn audio_codec_args(
+    spec: &AudioFormatSpec,
+    codec: &str,
+    source_rate: Option<u32>,
+    channels: u32,
+    bitrate_args: Vec<String>,
+) -> Result<Vec<String>, String> {
+    if !codec_fits_container(spec.container, codec) {
+        return Err(format!(
+            "Codec '{codec}' can't be stored in a {} ({}) container",
+            spec.name, spec.container
+        ));
+    }
+    let mut args: Vec<String> = vec!["-c:a".into(), codec.into()];
+    if let Some(rate) = output_sample_rate(codec, source_rate) {
+        args.extend(["-ar".into(), rate.to_string()]);
+    }
+    args.extend(["-ac".into(), channels.to_string()]);
+    args.extend(bitrate_args);
+    args.extend(["-f".into(), spec.container.into()]);
+    Ok(args)
+}
+
+// Moving the index to the front lets players start before the whole file has
+// loaded, but costs a second pass over the output; local-only exports can
+// skip it.
+fn streaming_args(
+    app: &tauri::AppHandle,
+    session_id: &str,
+    container: &str,
+    web_optimized: Option<bool>,
+) -> Vec<String> {
+    let web_optimized = web_optimized.unwrap_or(true);
+    let args: Vec<String> = match container {
+        "mp4" | "ipod" if web_optimized => vec!["-movflags".into(), "+faststart".into()],
+        "webm" | "matroska" if web_optimized => vec!["-cues_to_front".into(), "1".into()],
+        _ => Vec::new(),
+    };
+    let note = match (web_optimized, args.is_empty()) {
+        (true, false) => "index moved to front for streaming; adds a rewrite pass",
+        (false, _) => "streaming layout skipped; faster for local-only files",
+        (true, true) => "container has no streaming layout option",
+    };
+    let _ = append_video_trace_line(
+        app,
+        session_id,
+        &format!(
+            "{{\"stage\":\"backend_streaming\",\"container\":\"{container}\",\"web_optimized\":{web_optimized},\"note\":\"{note}\"}}"
+        ),
+    );
+    args
+}
+
+fn reconcile_export_extension(file_name: &str, format: &str) -> Result<(String, String), String> {
+    let path = Path::new(file_name);
+    // Only a known audio extension counts; "my.song" keeps its dot.
+    let known_ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .filter(|e| extension_format(e).is_some());
+    let stem = match known_ext {
+        Some(_) => path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or(file_name),
+        None => file_name,
+    };
+
+    // An explicit format wins over a conflicting extension so the payload and
+    // the name always agree.
+    let format = format.trim().trim_start_matches('.').to_ascii_lowercase();
+    let format = if format.is_empty() {
+        known_ext
+            .as_deref()
+            .and_then(extension_format)
+            .unwrap_or("mp3")
+            .to_string()
+    } else {
+        format
+    };
+    let ext = format_extension(&format).ok_or_else(|| {
+        format!(
+            "Unsupported export format '{format}'. Use one of: {}",
+            supported_format_names()
+        )
+    })?;
+    Ok((format!("{stem}.{ext}"), format))
+}
+
+fn apply_filename_template(
+    default_name: String,
+    tokens: &[(&str, &str)],
+    ext: &str,
+) -> Result<String, String> {
+    match load_settings()?.filename_template {
+        Some(template) => Ok(format!(
+            "{}.{ext}",
+            render_filename_template(&template, tokens)?
+        )),
+        None => Ok(default_name),
+    }
+}
+
+// ffmpeg arguments are built as Strings; a path that isn't valid Unicode
+// would come out of a lossy conversion with replacement characters and point
+// somewhere else, so it is refused instead. Valid Unicode (CJK, emoji)
+// passes through unchanged.
+fn path_arg(path: &Path) -> Result<String, String> {
+    path.to_str()
+        .map(|p| p.to_string())
+        .ok_or_else(|| format!("Path isn't valid Unicode: {}", path.to_string_lossy()))
+}
+
+fn sanitized_file_name(name: &str, fallback_ext: &str) -> String {
+    let candidate = Path::new(name)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("audioworkshop-output");
+    if candidate.trim().is_empty() {
+        return format!("audioworkshop-output.{fallback_ext}");
+    }
+    candidate.to_string()
+}
+
+fn harden_file_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| {
+            if c.is_control() || ILLEGAL_FILENAME_CHARS.contains(c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    cleaned.trim_end_matches(['.', ' ']).to_string()
+}
+
+fn unique_path(dir: &Path, file_name: &str) -> PathBuf {
+    let candidate = dir.join(file_name);
+    if !candidate.exists() {
+        return candidate;
+    }
+    let name = Path::new(file_name);
+    let stem = name.file_stem().and_then(|s| s.to_str()).unwrap_or(file_name);
+    let ext = name.extension().and_then(|e| e.to_str());
+    (1..)
+        .map(|n| match ext {
+            Some(ext) => dir.join(format!("{stem} ({n}).{ext}")),
+            None => dir.join(format!("{stem} ({n})")),
+        })
+        .find(|path| !path.exists())
+        .unwrap_or(candidate)
+}
+
+// unique_path alone races when two exports pick a name at the same moment, so
+// the chosen name is claimed by creating it; the export then overwrites it.
+fn reserve_output_path(dir: &Path, file_name: &str) -> Result<PathBuf, String> {
+    loop {
+        let candidate = unique_path(dir, file_name);
+        match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&candidate)
+        {
+            Ok(_) => return Ok(candidate),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err.to_string()),
+        }
     }
+}
 
-    let candidate = {
-        let raw = PathBuf::from(path.trim());
-        if raw.is_absolute() {
-            raw
-        } else {
-            app_root()?.join(raw)
+// The folder counterpart of reserve_output_path, for outputs that are a set
+// of files.
+fn reserve_output_dir(parent: &Path, name: &str) -> Result<PathBuf, String> {
+    loop {
+        let candidate = unique_path(parent, name);
+        match std::fs::create_dir(&candidate) {
+            Ok(()) => return Ok(candidate),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err.to_string()),
         }
-    };
+    }
+}
 
-    validate_writable_dir(&candidate)?;
-    settings.export_root = Some(candidate.to_string_lossy().to_string());
-    save_settings(&settings)?;
-    get_export_root()
+fn run_ffmpeg_to_unique(
+    app: &tauri::AppHandle,
+    ffmpeg: &Path,
+    session_id: &str,
+    args: Vec<String>,
+    dir: &Path,
+    file_name: &str,
+) -> Result<PathBuf, String> {
+    let (output_path, exit) =
+        run_ffmpeg_to_unique_traced(app, ffmpeg, session_id, args, dir, file_name)?;
+    if !exit.success {
+        return Err("Export failed. See logs.".into());
+    }
+    Ok(output_path)
 }
 
-#[tauri::command]
-fn prepare_download(date_folder: String, log_stamp: String) -> Result<DownloadPaths, String> {
-    if !date_folder
-        .chars()
-        .all(|c| c.is_ascii_digit() || c == '-')
-    {
-        return Err("Invalid date folder".into());
+// run_ffmpeg_to_unique for callers that report ffmpeg's exit themselves; a
+// failed run still removes its reserved file.
+fn run_ffmpeg_to_unique_traced(
+    app: &tauri::AppHandle,
+    ffmpeg: &Path,
+    session_id: &str,
+    mut args: Vec<String>,
+    dir: &Path,
+    file_name: &str,
+) -> Result<(PathBuf, FfmpegExit), String> {
+    let output_path = reserve_output_path(dir, file_name)?;
+    let journal_id = journal_begin("export", &output_path);
+    args.push(path_arg(&output_path)?);
+    let result = run_ffmpeg_traced(app, ffmpeg, session_id, &args);
+    let succeeded = result.as_ref().is_ok_and(|exit| exit.success);
+    if !succeeded {
+        let _ = std::fs::remove_file(&output_path);
     }
-    if !log_stamp
-        .chars()
-        .all(|c| c.is_ascii_digit() || c == '_')
-    {
-        return Err("Invalid log stamp".into());
+    journal_end(journal_id);
+    let exit = result?;
+    if succeeded {
+        run_post_export_hook(app, session_id, &output_path);
     }
+    Ok((output_path, exit))
+}
 
-    let root = resolve_download_root()?;
-    validate_writable_dir(&root)?;
-
-    let download_dir = root.join(date_folder);
-    std::fs::create_dir_all(&download_dir).map_err(|e| e.to_string())?;
+const POST_EXPORT_TIMEOUT_SECS: u64 = 120;
 
-    let logs = logs_root()?;
-    std::fs::create_dir_all(&logs).map_err(|e| e.to_string())?;
-    let log_path = logs.join(format!("download_{}.log", log_stamp));
+// Hooks are programs the user copied into this folder themselves; nothing
+// the app writes can land there (validate_writable_dir refuses it), so the
+// frontend can pick a hook but never supply one.
+fn hooks_dir() -> Result<PathBuf, String> {
+    Ok(app_root()?.join("hooks"))
+}
 
-    Ok(DownloadPaths {
-        download_root: root.to_string_lossy().to_string(),
-        download_dir: download_dir.to_string_lossy().to_string(),
-        log_path: log_path.to_string_lossy().to_string(),
-    })
+fn is_in_hooks_dir(path: &Path) -> bool {
+    let Ok(hooks) = hooks_dir().and_then(|dir| normalize_path(&dir)) else {
+        return false;
+    };
+    let lexical = normalize_path(path).is_ok_and(|path| path.starts_with(&hooks));
+    let real = match (path.canonicalize(), hooks.canonicalize()) {
+        (Ok(path), Ok(hooks)) => path.starts_with(hooks),
+        _ => false,
+    };
+    lexical || real
 }
 
-#[tauri::command]
-fn write_download_log(path: String, contents: String) -> Result<(), String> {
-    let root = resolve_download_root()?;
-    let path = PathBuf::from(path);
-    if !is_within(&root, &path)? {
-        return Err("Invalid log path".into());
+// Only a plain file name directly inside the hooks folder is accepted.
+fn validate_post_export_command(command: &str) -> Result<PathBuf, String> {
+    if Path::new(command).file_name().and_then(|n| n.to_str()) != Some(command) {
+        return Err("Post-export command must be a file name in the hooks folder".into());
     }
-    std::fs::write(path, contents).map_err(|e| e.to_string())
+    let dir = hooks_dir()?;
+    let program = dir.join(command);
+    if !program.is_file() {
+        return Err(format!("Post-export command not found in the hooks folder: {command}"));
+    }
+    let parent = program
+        .canonicalize()
+        .ok()
+        .and_then(|p| p.parent().map(Path::to_path_buf));
+    if parent != dir.canonicalize().ok() {
+        return Err(format!("Post-export command {command} isn't inside the hooks folder"));
+    }
+    Ok(program)
 }
 
-#[tauri::command(rename_all = "camelCase")]
-fn write_video_log(log_stamp: String, contents: String) -> Result<(), String> {
-    if !log_stamp
-        .chars()
-        .all(|c| c.is_ascii_digit() || c == '_')
-    {
-        return Err("Invalid log stamp".into());
+// Runs on its own thread so the export returns straight away; the export has
+// already succeeded, so a failing hook is only logged.
+fn run_post_export_hook(app: &tauri::AppHandle, session_id: &str, output_path: &Path) {
+    let app = app.clone();
+    let session_id = session_id.to_string();
+    let output_path = output_path.to_path_buf();
+    std::thread::spawn(move || run_hook_now(&app, &session_id, &output_path));
+}
+
+fn run_hook_now(app: &tauri::AppHandle, session_id: &str, output_path: &Path) {
+    let Ok(settings) = load_settings() else {
+        return;
+    };
+    let Some(command) = settings.post_export_command else {
+        return;
+    };
+    let preview = pending_exports_root()
+        .and_then(|root| is_within(&root, output_path))
+        .unwrap_or(false);
+    if preview {
+        return;
     }
-    let logs = logs_root()?;
-    std::fs::create_dir_all(&logs).map_err(|e| e.to_string())?;
-    let log_path = logs.join(format!("video_export_{}.log", log_stamp));
-    std::fs::write(log_path, contents).map_err(|e| e.to_string())
+    let program = match validate_post_export_command(&command) {
+        Ok(program) => program,
+        Err(err) => {
+            let _ = append_video_trace_line(
+                app,
+                session_id,
+                &format!(
+                    "{{\"stage\":\"backend_post_export\",\"error\":{}}}",
+                    serde_json::to_string(&err).unwrap_or_default()
+                ),
+            );
+            return;
+        }
+    };
+    let output = run_tracked(
+        app,
+        &format!("hook:{session_id}"),
+        Command::new(&program).arg(output_path),
+        Some(std::time::Duration::from_secs(POST_EXPORT_TIMEOUT_SECS)),
+        None,
+    );
+    let line = match output {
+        Ok(output) => format!(
+            "{{\"stage\":\"backend_post_export\",\"code\":{},\"timed_out\":{},\"stdout\":{},\"stderr\":{}}}",
+            output.status.code().unwrap_or(-1),
+            output.timed_out,
+            serde_json::to_string(&tail_lines_of(&output.stdout, 50)).unwrap_or_default(),
+            serde_json::to_string(&tail_lines_of(&output.stderr, 50)).unwrap_or_default()
+        ),
+        Err(err) => format!(
+            "{{\"stage\":\"backend_post_export\",\"error\":{}}}",
+            serde_json::to_string(&err).unwrap_or_default()
+        ),
+    };
+    let _ = append_video_trace_line(app, session_id, &line);
 }
 
-#[tauri::command(rename_all = "camelCase")]
-fn append_video_trace(session_id: String, line: String) -> Result<(), String> {
-    append_video_trace_line(&session_id, &line)
+#[tauri::command]
+fn get_post_export_hook() -> Result<PostExportHook, String> {
+    Ok(PostExportHook {
+        command: load_settings()?.post_export_command,
+        hooks_dir: hooks_dir()?.to_string_lossy().to_string(),
+    })
 }
 
 #[tauri::command]
-fn write_meta_file(path: String, contents: String) -> Result<(), String> {
-    let root = resolve_download_root()?;
-    let path = PathBuf::from(path);
-    if !is_within(&root, &path)? {
-        return Err("Invalid metadata path".into());
+fn set_post_export_hook(command: Option<String>) -> Result<PostExportHook, String> {
+    let command = command
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty());
+    if let Some(command) = &command {
+        validate_post_export_command(command)?;
     }
-    std::fs::write(path, contents).map_err(|e| e.to_string())
+    let mut settings = load_settings()?;
+    settings.post_export_command = command.clone();
+    save_settings(&settings)?;
+    std::fs::create_dir_all(hooks_dir()?).map_err(|e| e.to_string())?;
+    get_post_export_hook()
+}
+
+static STAMP_COUNTER: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+
+// Time-based stamps collide when two sessions start within the same
+// millisecond; the per-process counter keeps them apart.
+fn new_stamp() -> String {
+    let count = STAMP_COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed) % 1000;
+    format!("{}_{count:03}", app_now().format("%Y%m%d_%H%M%S_%3f"))
 }
 
 #[tauri::command]
-fn read_downloaded_file(path: String) -> Result<Vec<u8>, String> {
-    let root = resolve_download_root()?;
-    let path = PathBuf::from(path);
-    if !is_within(&root, &path)? {
-        return Err("Invalid download path".into());
-    }
-    std::fs::read(path).map_err(|e| e.to_string())
+fn new_session_id() -> String {
+    new_stamp()
 }
 
-#[tauri::command(rename_all = "camelCase")]
-fn find_latest_download(download_dir: String) -> Result<String, String> {
-    let root = resolve_download_root()?;
-    let dir = PathBuf::from(download_dir);
-    if !is_within(&root, &dir.join("probe.txt"))? {
-        return Err("Invalid download directory".into());
+#[tauri::command(async, rename_all = "camelCase")]
+fn import_file(source_path: String, destination: Option<String>) -> Result<String, String> {
+    let source = PathBuf::from(source_path);
+    if !source.is_file() {
+        return Err("Source file not found".into());
+    }
+    if is_managed_input(&source)? {
+        return Ok(source.to_string_lossy().to_string());
     }
 
-    let entries = std::fs::read_dir(&dir).map_err(|e| e.to_string())?;
-    let mut candidates: Vec<PathBuf> = entries
-        .flatten()
-        .map(|entry| entry.path())
-        .filter(|path| path.is_file())
-        .filter(|path| {
-            path.extension()
-                .and_then(|ext| ext.to_str())
-                .map(|ext| ext.eq_ignore_ascii_case("m4a"))
-                .unwrap_or(false)
-        })
-        .collect();
-
-    candidates.sort_by_key(|path| {
-        std::fs::metadata(path)
-            .and_then(|meta| meta.modified())
-            .ok()
-    });
+    let root = match destination.as_deref().unwrap_or("tmp") {
+        "tmp" => tmp_root()?,
+        "downloads" => resolve_download_root()?,
+        other => return Err(format!("Unknown import destination '{other}'")),
+    };
+    let dest_dir = scheme_dir(&root, &app_now().format("%Y-%m-%d").to_string())?;
+    validate_writable_dir(&dest_dir)?;
 
-    let latest = candidates.pop().ok_or("No downloaded file found")?;
-    let canonical = latest.canonicalize().map_err(|e| e.to_string())?;
-    Ok(canonical.to_string_lossy().to_string())
-}
+    let raw_name = sanitized_file_name(&source.to_string_lossy(), "bin");
+    let mut file_name = harden_file_name(&raw_name);
+    if file_name.is_empty() {
+        file_name = "imported-file".into();
+    }
 
-fn sanitized_file_name(name: &str, fallback_ext: &str) -> String {
-    let candidate = Path::new(name)
-        .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("audioworkshop-output");
-    if candidate.trim().is_empty() {
-        return format!("audioworkshop-output.{fallback_ext}");
+    let dest = unique_path(&dest_dir, &file_name);
+    if !is_within(&root, &dest)? {
+        return Err("Invalid import destination".into());
     }
-    candidate.to_string()
+    std::fs::copy(&source, &dest).map_err(|e| e.to_string())?;
+    Ok(dest.to_string_lossy().to_string())
 }
 
-#[tauri::command(rename_all = "camelCase")]
+#[tauri::command(async, rename_all = "camelCase")]
 fn export_audio_file(
+    app: tauri::AppHandle,
     file_name: String,
     format: String,
     bytes: Vec<u8>,
     output_root: Option<String>,
 ) -> Result<String, String> {
-    let now = Local::now();
+    let now = app_now();
     let date_folder = now.format("%Y-%m-%d").to_string();
 
-    let fallback_ext = if format.trim().is_empty() {
-        "mp3"
-    } else {
-        format.trim()
-    };
-    let file_name = sanitized_file_name(&file_name, fallback_ext);
+    let file_name = sanitized_file_name(&file_name, "mp3");
+    let (file_name, format) = reconcile_export_extension(&file_name, &format)?;
+    let ext = format_extension(&format).unwrap_or("mp3");
+    let title = Path::new(&file_name)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("audioworkshop-output")
+        .to_string();
+    let stamp = new_stamp();
+    let file_name = apply_filename_template(
+        file_name,
+        &[
+            ("title", &title),
+            ("date", &date_folder),
+            ("stamp", &stamp),
+            ("resolution", ""),
+        ],
+        ext,
+    )?;
 
-    let custom_root = output_root.is_some();
-    let output_root = if let Some(root) = output_root {
-        let raw = PathBuf::from(root);
-        if raw.is_absolute() {
-            raw
-        } else {
-            app_root()?.join(raw)
-        }
-    } else {
-        resolve_export_root()?
-    };
-    validate_writable_dir(&output_root)?;
+    let output_dir = resolve_titled_output_dir(output_root, &date_folder, Some(&title))?;
+    let output_path = reserve_output_path(&output_dir, &file_name)?;
+    if let Err(err) = atomic_write(&output_path, bytes) {
+        let _ = std::fs::remove_file(&output_path);
+        return Err(err);
+    }
+    run_post_export_hook(&app, &stamp, &output_path);
+    Ok(output_path.to_string_lossy().to_string())
+}
 
-    let export_dir = if custom_root {
-        output_root.clone()
-    } else {
-        output_root.join(date_folder)
+#[tauri::command]
+fn reveal_in_explorer(path: String) -> Result<(), String> {
+    let path = PathBuf::from(path);
+    if !path.is_file() {
+        return Err("File not found".into());
+    }
+    let roots = [
+        resolve_export_root()?,
+        resolve_video_export_root()?,
+        resolve_download_root()?,
+    ];
+    if !roots
+        .iter()
+        .any(|root| is_within(root, &path).unwrap_or(false))
+    {
+        return Err("Invalid reveal path".into());
+    }
+
+    // Explorer exits non-zero even when it opens the window, so only spawn
+    // failures are treated as errors.
+    #[cfg(target_os = "windows")]
+    let spawned = {
+        use std::os::windows::process::CommandExt;
+        let mut select = std::ffi::OsString::from("/select,\"");
+        select.push(path.as_os_str());
+        select.push("\"");
+        std::process::Command::new("explorer").raw_arg(select).spawn()
     };
-    std::fs::create_dir_all(&export_dir).map_err(|e| e.to_string())?;
+    #[cfg(target_os = "macos")]
+    let spawned = std::process::Command::new("open").arg("-R").arg(&path).spawn();
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let spawned = std::process::Command::new("xdg-open")
+        .arg(path.parent().ok_or("Invalid path")?)
+        .spawn();
 
-    let output_path = export_dir.join(file_name);
-    std::fs::write(&output_path, bytes).map_err(|e| e.to_string())?;
-    Ok(output_path.to_string_lossy().to_string())
+    spawned.map(|_| ()).map_err(|e| e.to_string())
+}
+
+// Only media the app itself produces can be opened; the extension check keeps
+// a script dropped into the export folder from being launched this way.
+#[tauri::command]
+fn open_file(path: String) -> Result<(), String> {
+    let path = PathBuf::from(path);
+    if !path.is_file() {
+        return Err("File not found".into());
+    }
+    let roots = [resolve_export_root()?, resolve_video_export_root()?];
+    if !roots
+        .iter()
+        .any(|root| is_within(root, &path).unwrap_or(false))
+    {
+        return Err("Invalid open path".into());
+    }
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+    if extension_format(&ext).is_none()
+        && !VIDEO_INPUT_EXTENSIONS.contains(&ext.as_str())
+        && ext != "png"
+    {
+        return Err("Only exported media files can be opened".into());
+    }
+
+    #[cfg(target_os = "windows")]
+    let spawned = std::process::Command::new("explorer").arg(&path).spawn();
+    #[cfg(target_os = "macos")]
+    let spawned = std::process::Command::new("open").arg(&path).spawn();
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let spawned = std::process::Command::new("xdg-open").arg(&path).spawn();
+
+    spawned.map(|_| ()).map_err(|e| e.to_string())
 }
 
-fn collect_files_recursively(root: &Path, out: &mut Vec<PathBuf>) {
-    let entries = match std::fs::read_dir(root) {
+// Support bundles must stay responsive even with years of dated download
+// folders, so the walk is bounded in depth and wall-clock time.
+const SCAN_MAX_DEPTH: usize = 4;
+const SCAN_TIME_LIMIT: std::time::Duration = std::time::Duration::from_secs(5);
+
+fn scan_newest_with_prefix(
+    dir: &Path,
+    prefix: &str,
+    depth: usize,
+    deadline: std::time::Instant,
+    newest: &mut Option<(std::time::SystemTime, PathBuf)>,
+) {
+    if depth > SCAN_MAX_DEPTH {
+        return;
+    }
+    let entries = match std::fs::read_dir(dir) {
         Ok(entries) => entries,
         Err(_) => return,
     };
     for entry in entries.flatten() {
-        let path = entry.path();
-        if path.is_dir() {
-            collect_files_recursively(&path, out);
-        } else {
-            out.push(path);
+        if std::time::Instant::now() > deadline {
+            return;
+        }
+        let file_type = match entry.file_type() {
+            Ok(file_type) => file_type,
+            Err(_) => continue,
+        };
+        if file_type.is_dir() {
+            scan_newest_with_prefix(&entry.path(), prefix, depth + 1, deadline, newest);
+            continue;
+        }
+        let matches = entry
+            .file_name()
+            .to_str()
+            .map(|n| n.starts_with(prefix))
+            .unwrap_or(false);
+        if !matches {
+            continue;
+        }
+        let modified = match entry.metadata().and_then(|m| m.modified()) {
+            Ok(modified) => modified,
+            Err(_) => continue,
+        };
+        if newest.as_ref().map(|(t, _)| modified > *t).unwrap_or(true) {
+            *newest = Some((modified, entry.path()));
         }
     }
 }
 
 fn latest_file_with_prefix(root: &Path, prefix: &str) -> Option<PathBuf> {
-    let mut files: Vec<PathBuf> = Vec::new();
-    collect_files_recursively(root, &mut files);
-    let mut candidates: Vec<PathBuf> = files
-        .into_iter()
-        .filter(|p| {
-            p.file_name()
-                .and_then(|n| n.to_str())
-                .map(|n| n.starts_with(prefix))
-                .unwrap_or(false)
-        })
-        .collect();
-    candidates.sort_by_key(|p| {
-        std::fs::metadata(p)
-            .and_then(|m| m.modified())
-            .ok()
-    });
-    candidates.pop()
+    let deadline = std::time::Instant::now() + SCAN_TIME_LIMIT;
+    let mut newest = None;
+    scan_newest_with_prefix(root, prefix, 0, deadline, &mut newest);
+    newest.map(|(_, path)| path)
 }
 
 fn tail_lines(path: &Path, max_lines: usize) -> String {
@@ -838,16 +9842,43 @@ fn tail_lines(path: &Path, max_lines: usize) -> String {
     lines.into_iter().rev().collect::<Vec<&str>>().join("\n")
 }
 
-#[tauri::command]
-fn write_support_bundle(app: tauri::AppHandle) -> Result<String, String> {
+#[derive(Debug, Serialize)]
+struct SupportBundleSections {
+    paths: bool,
+    download_settings: bool,
+    latest_download_log: bool,
+    latest_video_log: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct SupportBundle {
+    path: String,
+    binaries_found: bool,
+    missing_binaries: Vec<String>,
+    app_root: Option<String>,
+    download_root: String,
+    export_root: Option<String>,
+    video_export_root: Option<String>,
+    sections: SupportBundleSections,
+}
+
+#[tauri::command(async)]
+fn write_support_bundle(app: tauri::AppHandle) -> Result<SupportBundle, String> {
     let logs = logs_root()?;
     std::fs::create_dir_all(&logs).map_err(|e| e.to_string())?;
 
-    let stamp = Local::now().format("%Y%m%d_%H%M%S").to_string();
+    let now = app_now();
+    let stamp = now.format("%Y%m%d_%H%M%S").to_string();
     let bundle_path = logs.join(format!("support_bundle_{stamp}.txt"));
+    let timezone = if use_utc() {
+        "UTC".to_string()
+    } else {
+        format!("local (UTC{})", now.format("%:z"))
+    };
 
-    let app_root_text = app_root()
-        .map(|p| p.to_string_lossy().to_string())
+    let app_root_path = app_root().map(|p| p.to_string_lossy().to_string());
+    let app_root_text = app_root_path
+        .clone()
         .unwrap_or_else(|e| format!("(error: {e})"));
     let resource_dir_text = app
         .path()
@@ -861,12 +9892,16 @@ fn write_support_bundle(app: tauri::AppHandle) -> Result<String, String> {
         .map(|p| p.to_string_lossy().to_string())
         .unwrap_or_else(|e| format!("(error: {e})"));
 
-    let binaries_result = binaries_dir(&app)
-        .map(|p| p.to_string_lossy().to_string())
-        .unwrap_or_else(|e| format!("(error: {e})"));
+    let binaries = binaries_dir(&app).map(|p| p.to_string_lossy().to_string());
+    let binaries_found = binaries.is_ok();
+    let binaries_result = binaries.unwrap_or_else(|e| format!("(error: {e})"));
 
     let download_root = resolve_download_root()?;
-    let latest_download = latest_file_with_prefix(&download_root, "download_");
+    let (latest_download, latest_video) = std::thread::scope(|scope| {
+        let downloads = scope.spawn(|| latest_file_with_prefix(&download_root, "download_"));
+        let video = latest_file_with_prefix(&logs, "video_export_");
+        (downloads.join().unwrap_or(None), video)
+    });
     let latest_download_text = latest_download
         .as_ref()
         .map(|p| p.to_string_lossy().to_string())
@@ -876,7 +9911,6 @@ fn write_support_bundle(app: tauri::AppHandle) -> Result<String, String> {
         .map(|p| tail_lines(p, 120))
         .unwrap_or_else(|| "(no download log tail)".into());
 
-    let latest_video = latest_file_with_prefix(&logs, "video_export_");
     let latest_video_text = latest_video
         .as_ref()
         .map(|p| p.to_string_lossy().to_string())
@@ -886,15 +9920,29 @@ fn write_support_bundle(app: tauri::AppHandle) -> Result<String, String> {
         .map(|p| tail_lines(p, 120))
         .unwrap_or_else(|| "(no video log tail)".into());
 
+    let loaded_settings = load_settings();
+    let settings_collected = loaded_settings.is_ok();
+    let settings = loaded_settings.unwrap_or_default();
+    let proxy_text = settings
+        .proxy_url
+        .as_deref()
+        .map(redact_proxy_url)
+        .unwrap_or_else(|| "(none)".into());
+    let rate_limit_text = settings.rate_limit.unwrap_or_else(|| "(none)".into());
+
     let contents = format!(
         "Audio Workshop Support Bundle\n\
-generated_at={stamp}\n\n\
+generated_at={stamp}\n\
+timezone={timezone}\n\n\
 [paths]\n\
 app_root={app_root_text}\n\
 resource_dir={resource_dir_text}\n\
 current_exe={current_exe_text}\n\
 current_dir={current_dir_text}\n\
 binaries_dir={binaries_result}\n\n\
+[download_settings]\n\
+proxy_url={proxy_text}\n\
+rate_limit={rate_limit_text}\n\n\
 [latest_download_log]\n\
 path={latest_download_text}\n\
 {latest_download_tail}\n\n\
@@ -907,18 +9955,60 @@ path={latest_video_text}\n\
         current_exe_text = current_exe_text,
         current_dir_text = current_dir_text,
         binaries_result = binaries_result,
+        proxy_text = proxy_text,
+        rate_limit_text = rate_limit_text,
         latest_download_text = latest_download_text,
         latest_download_tail = latest_download_tail,
         latest_video_text = latest_video_text,
         latest_video_tail = latest_video_tail
     );
 
-    std::fs::write(&bundle_path, contents).map_err(|e| e.to_string())?;
-    Ok(bundle_path.to_string_lossy().to_string())
+    atomic_write(&bundle_path, contents)?;
+    let root_text =
+        |root: Result<PathBuf, String>| root.ok().map(|p| p.to_string_lossy().to_string());
+    Ok(SupportBundle {
+        path: bundle_path.to_string_lossy().to_string(),
+        binaries_found,
+        missing_binaries: required_binaries_status(&app).missing,
+        app_root: app_root_path.clone().ok(),
+        download_root: download_root.to_string_lossy().to_string(),
+        export_root: root_text(resolve_export_root()),
+        video_export_root: root_text(resolve_video_export_root()),
+        sections: SupportBundleSections {
+            paths: app_root_path.is_ok(),
+            download_settings: settings_collected,
+            latest_download_log: latest_download.is_some(),
+            latest_video_log: latest_video.is_some(),
+        },
+    })
 }
 
 fn main() {
     tauri::Builder::default()
+        .manage(ProcessRegistry::default())
+        .manage(LastOperation::default())
+        .manage(JobSlots::default())
+        .manage(DownloadRegistry::default())
+        .manage(EncoderCache::default())
+        .manage(CapabilityCache::default())
+        .manage(DurationCache::default())
+        .manage(SessionLogLocks::default())
+        .manage(DownloadQueue::default())
+        .setup(|app| {
+            if let Err(err) = restore_download_queue(app.handle()) {
+                let line = format!(
+                    "{} Failed to restore download queue: {err}",
+                    app_now().to_rfc3339()
+                );
+                let _ = logs_root().and_then(|logs| {
+                    std::fs::create_dir_all(&logs).map_err(|e| e.to_string())?;
+                    append_log_line(&logs.join("app.log"), &line)
+                });
+            }
+            let _ = cleanup_pending_exports();
+            let _ = cleanup_write_probes();
+            Ok(())
+        })
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_shell::init())
         .invoke_handler(tauri::generate_handler![
@@ -939,8 +10029,406 @@ fn main() {
             export_audio_file,
             write_meta_file,
             read_downloaded_file,
-            find_latest_download
+            find_latest_download,
+            reveal_in_explorer,
+            run_diagnostics,
+            extract_clip,
+            get_logs_dir,
+            list_logs,
+            get_filename_template,
+            set_filename_template,
+            validate_filter,
+            download_audio,
+            get_network_settings,
+            set_network_settings,
+            record_download,
+            get_history,
+            concat_audio,
+            analyze_levels,
+            check_yt_dlp_update,
+            update_yt_dlp,
+            preflight_input,
+            transcode_audio,
+            get_session_log_limit,
+            set_session_log_limit,
+            run_ffmpeg,
+            validate_settings,
+            extract_audio_from_video,
+            pause_download,
+            resume_download,
+            hash_file,
+            list_audio_encoders,
+            enqueue_download,
+            get_queue,
+            remove_from_queue,
+            import_file,
+            to_mono,
+            to_stereo,
+            normalize_album,
+            supported_formats,
+            get_quality,
+            set_quality,
+            generate_waveform_image,
+            split_audio,
+            get_output_allowlist,
+            set_output_allowlist,
+            read_metadata,
+            list_export_presets,
+            save_export_preset,
+            delete_export_preset,
+            apply_export_preset,
+            cancel_all,
+            get_folder_scheme,
+            set_folder_scheme,
+            read_file_chunk,
+            validate_date_stamp,
+            estimate_output_size,
+            preview_export_dir,
+            commit_export,
+            discard_export,
+            mix_audio,
+            detect_clipping,
+            read_log_tail,
+            get_ffmpeg_settings,
+            set_ffmpeg_settings,
+            path_is_managed,
+            resample_audio,
+            write_chapters,
+            generate_spectrogram,
+            migrate_data_root,
+            loudness_presets,
+            probe_url,
+            diagnose_path,
+            new_session_id,
+            export_waveform_data,
+            reorder_export_presets,
+            cancel_last,
+            recover_operations,
+            get_active_operations,
+            export_kenburns_video,
+            get_use_utc,
+            set_use_utc,
+            rebuild_history,
+            get_max_input_mb,
+            set_max_input_mb,
+            remux,
+            open_file,
+            get_binaries_status,
+            get_video_export_root,
+            set_video_export_root,
+            preview_command,
+            compare_audio,
+            clear_readonly,
+            get_export_eta,
+            normalize_color,
+            list_downloads,
+            get_download_container,
+            set_download_container,
+            get_process_priority,
+            set_process_priority,
+            get_organize_by,
+            set_organize_by,
+            verify_export,
+            get_post_export_hook,
+            set_post_export_hook,
+            get_effective_config,
+            export_multi_format,
+            get_ffmpeg_capabilities,
+            get_max_parallel_jobs,
+            set_max_parallel_jobs
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            // Closing the window would otherwise orphan ffmpeg/yt-dlp.
+            if let tauri::RunEvent::Exit = event {
+                stop_all_tracked(app);
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ffmpeg can't run in unit tests, so this covers the part that decides
+    // whether the mp4 is valid: what the probe reports for a non-WAV input
+    // and the AAC rate and layout derived from it.
+    #[test]
+    fn non_wav_inputs_get_mp4_compatible_audio() {
+        for (codec, rate, channels, expected_rate) in [
+            ("mp3", "22050", 1, 22050),
+            ("aac", "48000", 2, 48000),
+            ("opus", "48000", 6, 48000),
+            ("vorbis", "37800", 2, 44100),
+        ] {
+            let probe = parse_probe(&serde_json::json!({
+                "format": {"duration": "3.5"},
+                "streams": [
+                    {"codec_type": "video", "codec_name": "mjpeg",
+                     "disposition": {"attached_pic": 1}},
+                    {"codec_type": "audio", "codec_name": codec,
+                     "sample_rate": rate, "channels": channels},
+                ],
+            }));
+            assert!(probe.has_audio);
+            assert_eq!(probe.audio_codec.as_deref(), Some(codec));
+            assert_eq!(probe.video_codec, None);
+            assert_eq!(output_sample_rate("aac", probe.sample_rate), Some(expected_rate));
+        }
+        assert!(codec_fits_container("mp4", "aac"));
+        assert!(!codec_fits_container("mp4", "pcm_s16le"));
+    }
+
+    #[test]
+    fn export_extension_follows_the_payload_format() {
+        let reconcile = |name, format| reconcile_export_extension(name, format).unwrap();
+        assert_eq!(reconcile("song.wav", "mp3"), ("song.mp3".into(), "mp3".into()));
+        assert_eq!(reconcile("SONG.WAV", ""), ("SONG.wav".into(), "wav".into()));
+        assert_eq!(reconcile("Take.FLAC", "MP3"), ("Take.mp3".into(), "mp3".into()));
+        assert_eq!(reconcile("Mix.M4A", ".aac"), ("Mix.m4a".into(), "aac".into()));
+        assert_eq!(reconcile("my.song", ""), ("my.song.mp3".into(), "mp3".into()));
+        assert!(reconcile_export_extension("song.wav", "xyz").is_err());
+    }
+
+    #[test]
+    fn normalize_path_resolves_missing_paths_lexically() {
+        let base = std::env::temp_dir().join(format!("aw_missing_{}", new_stamp()));
+        let messy = base.join("a").join(".").join("b").join("..").join("c.wav");
+        assert_eq!(normalize_path(&messy).unwrap(), base.join("a").join("c.wav"));
+        let root = normalize_path(&base).unwrap();
+        let root = root.ancestors().last().unwrap();
+        assert_eq!(normalize_path(&root.join("..").join("x")).unwrap(), root.join("x"));
+    }
+
+    #[test]
+    fn is_within_refuses_missing_folders_and_traversal() {
+        let dir = std::env::temp_dir().join(format!("aw_test_within_{}", new_stamp()));
+        std::fs::create_dir_all(dir.join("inner")).unwrap();
+        assert_eq!(is_within(&dir, &dir.join("inner").join("new.wav")), Ok(true));
+        assert_eq!(is_within(&dir.join("inner"), &dir.join("new.wav")), Ok(false));
+        assert_eq!(
+            is_within(&dir.join("inner"), &dir.join("inner").join("..").join("new.wav")),
+            Ok(false)
+        );
+        assert!(is_within(&dir, &dir.join("missing").join("new.wav")).is_err());
+        assert!(is_within(&dir.join("missing"), &dir.join("new.wav")).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn normalize_path_keeps_unc_shares() {
+        let path = Path::new(r"\\server\share\music\..\mix.wav");
+        assert_eq!(normalize_path(path).unwrap(), Path::new(r"\\server\share\mix.wav"));
+        let escape = Path::new(r"\\server\share\..\..\mix.wav");
+        assert_eq!(normalize_path(escape).unwrap(), Path::new(r"\\server\share\mix.wav"));
+    }
+
+    #[test]
+    fn failed_atomic_write_keeps_the_target_and_removes_the_temp_file() {
+        let dir = std::env::temp_dir().join(format!("aw_test_atomic_{}", new_stamp()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let leftovers = |dir: &Path| {
+            std::fs::read_dir(dir)
+                .unwrap()
+                .flatten()
+                .filter(|entry| entry.file_name().to_string_lossy().ends_with(".tmp"))
+                .count()
+        };
+
+        let file = dir.join("settings.json");
+        atomic_write(&file, "old").unwrap();
+        atomic_write(&file, "new").unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "new");
+        assert_eq!(leftovers(&dir), 0);
+
+        // A non-empty folder in the target's place makes the final rename fail.
+        let blocked = dir.join("history.json");
+        std::fs::create_dir_all(&blocked).unwrap();
+        std::fs::write(blocked.join("keep.txt"), "kept").unwrap();
+        assert!(atomic_write(&blocked, "replacement").is_err());
+        assert_eq!(std::fs::read_to_string(blocked.join("keep.txt")).unwrap(), "kept");
+        assert_eq!(leftovers(&dir), 0);
+
+        assert!(atomic_write(&dir.join("missing").join("queue.json"), "x").is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stamps_are_digit_groups_only() {
+        assert!(is_valid_stamp(&new_stamp()));
+        assert!(is_valid_stamp("20250101_120000_123_007"));
+        assert!(is_valid_stamp("42"));
+        for bad in ["", "_", "2025__01", "2025_", "_2025", "2025-01-01", "../2025", "2025_0a"] {
+            assert!(!is_valid_stamp(bad), "{bad}");
+        }
+        assert!(!is_valid_stamp(&"1".repeat(65)));
+        assert!(validate_log_stamp("2025_01").is_ok());
+        assert!(validate_log_stamp("..").is_err());
+        assert!(validate_session_id("x").is_err());
+    }
+
+    #[test]
+    fn date_folders_must_be_real_calendar_dates() {
+        for good in ["2025-01-31", "2024-02-29"] {
+            assert!(is_full_date(good) && validate_date_folder(good).is_ok(), "{good}");
+        }
+        let bad_folders = [
+            "2025-02-29",
+            "2025-13-01",
+            "2025-1-01",
+            "2025/01/01",
+            "20250101",
+            "",
+            "2025-01-01/..",
+        ];
+        for bad in bad_folders {
+            assert!(!is_full_date(bad), "{bad}");
+            assert!(validate_date_folder(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn parse_color_accepts_hex_forms_and_names() {
+        let parsed = |input| parse_color(input).unwrap();
+        assert_eq!(parsed("#fa0"), "0xFFAA00");
+        assert_eq!(parsed("#1e90ff"), "0x1E90FF");
+        assert_eq!(parsed("#1E90FFFF"), "0x1E90FF");
+        assert_eq!(parsed("#00000080"), "0x000000@0.502");
+        assert_eq!(parsed("#FFFFFF00"), "0xFFFFFF@0.000");
+        assert_eq!(parsed(" White "), "0xFFFFFF");
+        assert_eq!(parsed("lime"), "0x00FF00");
+    }
+
+    #[test]
+    fn parse_color_rejects_everything_else() {
+        for bad in ["", "#", "#12", "#1234", "#12345", "#1234567", "#GGGGGG"] {
+            assert!(parse_color(bad).is_err(), "{bad}");
+        }
+        for bad in ["123456", "0xFFFFFF", "white:t=fill", "red@0.5", "#FFF,drawbox", "notacolor"] {
+            assert!(parse_color(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn stale_write_probes_are_swept() {
+        let dir = std::env::temp_dir().join(format!("aw_test_probe_{}", new_stamp()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let backdated = |name: &str| {
+            let path = dir.join(name);
+            let file = std::fs::File::create(&path).unwrap();
+            let age = std::time::Duration::from_secs(STALE_PROBE_SECS + 60);
+            file.set_modified(std::time::SystemTime::now() - age).unwrap();
+            path
+        };
+        let stale = backdated(".aw_write_test_1_20250101_000000_000_000");
+        let unrelated = backdated("song.wav");
+        let fresh = dir.join(".aw_write_test_2_20250101_000000_000_001");
+        std::fs::write(&fresh, "test").unwrap();
+
+        assert_eq!(remove_stale_probes(&dir), 1);
+        assert!(!stale.exists());
+        assert!(fresh.exists() && unrelated.exists());
+
+        // Checking the folder again sweeps a newly stale probe and leaves
+        // nothing of its own behind.
+        let stale = backdated(".aw_write_test");
+        validate_writable_dir(&dir).unwrap();
+        assert!(!stale.exists());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn concat_graph_normalizes_every_input() {
+        assert_eq!(
+            concat_filter_graph(2),
+            "[0:a:0]aresample=44100,aformat=sample_fmts=fltp:channel_layouts=stereo[a0];\
+             [1:a:0]aresample=44100,aformat=sample_fmts=fltp:channel_layouts=stereo[a1];\
+             [a0][a1]concat=n=2:v=0:a=1[joined]"
+        );
+    }
+
+    #[test]
+    fn dry_run_flag_is_cleared_after_a_panic() {
+        assert!(dry_run(is_dry_run));
+        assert!(!is_dry_run());
+        let panicked = std::panic::catch_unwind(|| dry_run(|| panic!("planning failed")));
+        assert!(panicked.is_err());
+        assert!(!is_dry_run());
+    }
+
+    #[test]
+    fn path_arg_keeps_cjk_and_emoji_names() {
+        for name in ["音楽/曲 🎵.wav", "록/노래.mp3", "😀😀.flac"] {
+            assert_eq!(path_arg(Path::new(name)).as_deref(), Ok(name));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn path_arg_rejects_non_unicode_paths() {
+        use std::os::unix::ffi::OsStrExt;
+        let path = Path::new(std::ffi::OsStr::from_bytes(b"bad\xff.wav"));
+        assert!(path_arg(path).is_err());
+    }
+
+    #[test]
+    fn rapid_exports_get_distinct_names() {
+        let stamps: Vec<String> = (0..50).map(|_| new_stamp()).collect();
+        let unique: std::collections::HashSet<&String> = stamps.iter().collect();
+        assert_eq!(unique.len(), stamps.len());
+
+        let dir = std::env::temp_dir().join(format!("aw_test_reserve_{}", new_stamp()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let first = reserve_output_path(&dir, "mix.mp3").unwrap();
+        let second = reserve_output_path(&dir, "mix.mp3").unwrap();
+        let folder = reserve_output_dir(&dir, "mix__segments").unwrap();
+        let other_folder = reserve_output_dir(&dir, "mix__segments").unwrap();
+        assert_ne!(first, second);
+        assert_ne!(folder, other_folder);
+        assert!(first.is_file() && second.is_file());
+        assert!(folder.is_dir() && other_folder.is_dir());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn concurrent_session_appends_keep_whole_lines() {
+        let dir = std::env::temp_dir().join(format!("aw_test_log_{}", new_stamp()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let log_path = dir.join("session.log");
+        let locks = Arc::new(SessionLogLocks::default());
+        let line = "x".repeat(4000);
+        let workers: Vec<_> = (0..8)
+            .map(|worker| {
+                let (locks, log_path) = (locks.clone(), log_path.clone());
+                let line = format!("{worker}:{line}");
+                std::thread::spawn(move || {
+                    for _ in 0..50 {
+                        with_session_log_lock(&locks, "20250101_000000_000_000", || {
+                            append_log_line(&log_path, &line)
+                        })
+                        .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        let contents = std::fs::read_to_string(&log_path).unwrap();
+        assert_eq!(contents.lines().count(), 400);
+        assert!(contents.lines().all(|l| l.len() == line.len() + 2 && l.ends_with(&line)));
+        assert!(locks.0.lock().unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sanitized_file_name_keeps_unicode_and_drops_folders() {
+        assert_eq!(sanitized_file_name("日本語 🎶.mp3", "mp3"), "日本語 🎶.mp3");
+        assert_eq!(sanitized_file_name("exports/歌.flac", "flac"), "歌.flac");
+        assert_eq!(sanitized_file_name("  ", "wav"), "audioworkshop-output.wav");
+    }
 }